@@ -0,0 +1,92 @@
+use super::Scale;
+use crate::{
+    config::Hough,
+    object::{pairs, Circle},
+    WHITE,
+};
+use anyhow::Result;
+use cv::Draw;
+use opencv::{
+    core::{Mat, Point2f, Size, VecN, Vector, CV_8U},
+    imgproc::{hough_circles, FILLED},
+    prelude::*,
+};
+use serde::{Deserialize, Serialize};
+
+/// Finds circles in the filled contours
+///
+/// Radius bounds in µm take precedence over the pixel ones when the scale is
+/// known. Circles overlapping a stronger one by more than `config.nms` are
+/// suppressed.
+pub fn hough(
+    size: Size,
+    contours: &Vector<Mat>,
+    config: &Hough,
+    scale: Option<&Scale>,
+) -> Result<Vec<Circle>> {
+    let (min_radius, max_radius) = match (config.radius, scale) {
+        (Some((min, max)), Some(scale)) => (
+            (min * scale.pixels).floor() as _,
+            (max * scale.pixels).ceil() as _,
+        ),
+        _ => (config.min_radius, config.max_radius),
+    };
+    let mut filled = Mat::zeros_size(size, CV_8U)?.to_mat()?;
+    filled.draw_contours(contours, WHITE, FILLED)?;
+    let mut detected = Vector::<VecN<f32, 3>>::new();
+    hough_circles(
+        &filled,
+        &mut detected,
+        config.method,
+        config.dp,
+        config.min_dist,
+        config.param1,
+        config.param2,
+        min_radius,
+        max_radius,
+    )?;
+    // Non-maximum suppression, circles come sorted by accumulator votes
+    let mut circles = Vec::<Circle>::new();
+    for VecN([x, y, radius]) in detected {
+        let circle = Circle {
+            center: Point2f::new(x, y),
+            radius,
+        };
+        if circles.iter().all(|kept| kept.iou(&circle) <= config.nms) {
+            circles.push(circle);
+        }
+    }
+    Ok(circles)
+}
+
+/// Match of a circle and a contour
+#[derive(Clone, Copy, Debug, Default, Deserialize, Serialize)]
+pub struct Match {
+    pub circle: Option<usize>,
+    pub contour: Option<usize>,
+    pub iou: f64,
+}
+
+impl Match {
+    /// Only one of the detectors found the object
+    pub fn is_disagreement(&self) -> bool {
+        self.circle.is_none() || self.contour.is_none()
+    }
+}
+
+/// Matches circles and contours one to one, greedily by intersection over
+/// union
+pub fn matches(
+    circles: &Vector<Mat>,
+    contours: &Vector<Mat>,
+    threshold: f64,
+) -> Result<Vec<Match>> {
+    Ok(pairs(&circles.to_vec(), &contours.to_vec(), threshold)?
+        .into_iter()
+        .map(|pair| Match {
+            circle: pair.left,
+            contour: pair.right,
+            iou: pair.iou,
+        })
+        .collect())
+}
@@ -0,0 +1,499 @@
+pub use self::{
+    circles::{hough, matches, Match},
+    score::{choose, Scores},
+};
+
+use crate::{
+    config::{self, Config, Selection},
+    filter::Measured,
+    object::{solidity, Circle},
+    statistics::{statistics, Region},
+    Hsb,
+};
+use anyhow::Result;
+use cv::{Contour, MatExt, MatTraitConstExt, MomentsExt, ToInputArrayExt};
+use itertools::Itertools;
+use kmeans_colors::{get_kmeans_hamerly, Kmeans, MapColor};
+use opencv::{
+    core::{Point2f, Rect, Scalar, Size, VecN, Vector, CV_8UC1, CV_8UC3},
+    imgproc::{
+        rectangle, COLOR_BGR2GRAY, COLOR_BGR2HSV, COLOR_BGR2RGB, COLOR_RGB2BGR, FILLED, LINE_8,
+        TM_CCOEFF_NORMED,
+    },
+    prelude::*,
+};
+use palette::{
+    cast::{AsComponents, ComponentsAs},
+    white_point::D65,
+    IntoColor, Lab, Srgb,
+};
+use serde::{Deserialize, Serialize};
+use std::{collections::BTreeMap, f64::consts::PI};
+
+const CONVERGE: f32 = 0.0025;
+
+/// Clustering
+#[derive(Clone, Debug)]
+pub struct Clustering {
+    pub k: usize,
+    pub scores: Scores,
+    pub centroids: Vec<Srgb<u8>>,
+    /// Cluster index of every pixel, row by row
+    pub indices: Vec<u8>,
+    /// Image with every pixel replaced by its centroid, BGR
+    pub image: Mat,
+}
+
+impl Clustering {
+    /// Label image, `CV_8UC1` with the cluster index of every pixel
+    pub fn labels(&self) -> Result<Mat> {
+        Ok(Mat::from_slice(&self.indices)?
+            .reshape(1, self.image.rows())?
+            .try_clone()?)
+    }
+
+    /// Binary mask of the cluster
+    pub fn mask(&self, label: usize) -> Result<Mat> {
+        self.masks(&[label])
+    }
+
+    /// Binary mask of the clusters
+    pub fn masks(&self, labels: &[usize]) -> Result<Mat> {
+        let mask = self.indices.iter().map(|&index| {
+            if labels.contains(&(index as usize)) {
+                255u8
+            } else {
+                0
+            }
+        });
+        Ok(Mat::from_exact_iter(mask)?
+            .reshape(1, self.image.rows())?
+            .try_clone()?)
+    }
+
+    /// Centroids in Lab
+    pub fn lab(&self) -> Vec<Lab<D65, f32>> {
+        self.centroids
+            .iter()
+            .map(|&color| color.into_linear::<f32>().into_color())
+            .collect()
+    }
+
+    /// Background cluster
+    pub fn background(&self, selection: Selection) -> Option<usize> {
+        let lab = self.lab();
+        let key = |index: &usize| -> f32 {
+            match selection {
+                Selection::Lightness => lab[*index].l,
+                Selection::Reference(color) => {
+                    let reference: Lab<D65, f32> =
+                        Srgb::from(color).into_linear::<f32>().into_color();
+                    let (l, a, b) = (
+                        lab[*index].l - reference.l,
+                        lab[*index].a - reference.a,
+                        lab[*index].b - reference.b,
+                    );
+                    -(l * l + a * a + b * b)
+                }
+            }
+        };
+        (0..lab.len()).max_by(|a, b| key(a).total_cmp(&key(b)))
+    }
+
+    /// Cluster of most pixels within the contour, the part outside the image
+    /// is not counted
+    pub fn label(&self, contour: &Mat) -> Result<usize> {
+        let region = Region::Contour(contour);
+        let rectangle = region.rectangle()? & Rect::new(0, 0, self.image.cols(), self.image.rows());
+        let mut counts = vec![0usize; self.k];
+        if !rectangle.empty() {
+            let columns = self.image.cols() as usize;
+            let width = rectangle.width as usize;
+            for (offset, &value) in region.mask(rectangle)?.data_bytes()?.iter().enumerate() {
+                if value != 0 {
+                    let row = rectangle.y as usize + offset / width;
+                    let column = rectangle.x as usize + offset % width;
+                    counts[self.indices[row * columns + column] as usize] += 1;
+                }
+            }
+        }
+        Ok(counts.iter().position_max().unwrap_or_default())
+    }
+
+    /// Label → centroid table
+    pub fn palette(&self) -> Vec<Centroid> {
+        let mut pixels = vec![0; self.centroids.len()];
+        for &index in &self.indices {
+            pixels[index as usize] += 1;
+        }
+        self.centroids
+            .iter()
+            .zip(self.lab())
+            .zip(pixels)
+            .enumerate()
+            .map(|(label, ((color, lab), pixels))| Centroid {
+                label,
+                srgb: [color.red, color.green, color.blue],
+                lab: [lab.l, lab.a, lab.b],
+                pixels,
+            })
+            .collect()
+    }
+
+    /// Palette image, a row of squares of the centroid colors, BGR
+    pub fn palette_image(&self, side: i32) -> Result<Mat> {
+        let mut image = Mat::zeros(side, side * self.centroids.len() as i32, CV_8UC3)?.to_mat()?;
+        for (index, color) in self.centroids.iter().enumerate() {
+            rectangle(
+                &mut image,
+                Rect::new(side * index as i32, 0, side, side),
+                Scalar::new(color.blue as _, color.green as _, color.red as _, 255.0),
+                FILLED,
+                LINE_8,
+                0,
+            )?;
+        }
+        Ok(image)
+    }
+}
+
+/// Centroid
+#[derive(Clone, Copy, Debug, Default, Deserialize, Serialize)]
+pub struct Centroid {
+    pub label: usize,
+    pub srgb: [u8; 3],
+    pub lab: [f32; 3],
+    /// Number of pixels of the cluster
+    pub pixels: usize,
+}
+
+/// Clusters the colors of the image with k-means for each `k` in
+/// `1..=config.kmeans.k`
+pub fn kmeans(source: &Mat, config: &Config) -> Result<Vec<Clustering>> {
+    let rgb = source.convert_color(COLOR_BGR2RGB)?;
+    let rows = rgb.rows();
+    // Convert image from Srgb to Lab
+    let lab = rgb
+        .data_bytes()?
+        .components_as()
+        .iter()
+        .map(|&color: &Srgb<u8>| color.into_linear::<f32>().into_color())
+        .collect::<Vec<Lab<D65, f32>>>();
+    let mut clusterings = Vec::with_capacity(config.kmeans.k);
+    for k in 1..=config.kmeans.k {
+        // Iterate over amount of runs keeping best results
+        let mut kmeans = Kmeans::new();
+        for index in 0..config.kmeans.runs {
+            let r#try = get_kmeans_hamerly(
+                k,
+                config.kmeans.iterations,
+                CONVERGE,
+                false,
+                &lab,
+                config.kmeans.seed + index,
+            );
+            if r#try.score < kmeans.score {
+                kmeans = r#try;
+            }
+        }
+        // Convert centroids to Srgb<u8> before mapping to buffer
+        let centroids = kmeans
+            .centroids
+            .iter()
+            .map(|&color| Srgb::from_linear(color.into_color()))
+            .collect::<Vec<Srgb<u8>>>();
+        let mapped = Srgb::map_indices_to_centroids(&centroids, &kmeans.indices);
+        let image = Mat::from_slice(mapped.as_components())?
+            .reshape(3, rows)?
+            .try_clone()?
+            .convert_color(COLOR_RGB2BGR)?;
+        let scores = Scores::new(
+            k,
+            &lab,
+            &kmeans.centroids,
+            &kmeans.indices,
+            config.algae.segmentation.sample,
+        );
+        clusterings.push(Clustering {
+            k,
+            scores,
+            centroids,
+            indices: kmeans.indices,
+            image,
+        });
+    }
+    Ok(clusterings)
+}
+
+/// Scale
+#[derive(Clone, Debug)]
+pub struct Scale {
+    /// Position of the template
+    pub rectangle: Rect,
+    /// Mask of the template
+    pub mask: Mat,
+    /// Pixels per µm
+    pub pixels: f64,
+}
+
+/// Finds the template with a scale bar of a known length in the image
+pub fn scale(source: &Mat, template: &Mat, config: &config::Scale) -> Result<Scale> {
+    let rectangle = source.match_template(template, TM_CCOEFF_NORMED, config.threshold)?;
+    let mut mask = Mat::zeros_size(source.size()?, CV_8UC1)?.to_mat()?;
+    template
+        .convert_color(COLOR_BGR2GRAY)?
+        .copy_to(&mut mask.roi_mut(rectangle)?)?;
+    Ok(Scale {
+        rectangle,
+        mask,
+        pixels: rectangle.width as f64 / config.length,
+    })
+}
+
+/// Segmentation
+#[derive(Clone, Debug)]
+pub struct Segmentation {
+    /// Background cluster
+    pub label: Option<usize>,
+    /// Pixels of the background cluster
+    pub background: Mat,
+    /// Pixels of all clusters but the background one
+    pub foreground: Mat,
+    /// Pixels of the darkest foreground cluster
+    pub core: Mat,
+    /// Foreground pixels which are not in the core
+    pub unknown: Mat,
+    /// Contours of the foreground, filtered by area, not touching the border
+    pub contours: Vector<Mat>,
+}
+
+/// Segments the clustered image into the background cluster and the others
+pub fn segment(clustering: &Clustering, config: &Config) -> Result<Segmentation> {
+    let label = clustering.background(config.algae.segmentation.selection);
+    let background = match label {
+        Some(label) => clustering.mask(label)?,
+        None => Mat::zeros_size(clustering.image.size()?, CV_8UC1)?.to_mat()?,
+    };
+    let foreground = background.bitwise_not()?;
+    let lab = clustering.lab();
+    let core = (0..lab.len())
+        .filter(|&index| Some(index) != label)
+        .min_by(|&a, &b| lab[a].l.total_cmp(&lab[b].l));
+    let core = clustering.masks(&Vec::from_iter(core))?;
+    let unknown = foreground.subtract(&core)?;
+    let size = foreground.size()?;
+    let mut contours = Vector::new();
+    for contour in &foreground.find_contours(config.contours.mode, config.contours.method)? {
+        if contour.area()? < config.contours.min_area {
+            continue;
+        }
+        if contour.iter::<VecN<i32, 2>>()?.any(|(_, VecN([x, y]))| {
+            x == 0 || y == 0 || x == size.width - 1 || y == size.height - 1
+        }) {
+            continue;
+        }
+        contours.push(contour);
+    }
+    Ok(Segmentation {
+        label,
+        background,
+        foreground,
+        core,
+        unknown,
+        contours,
+    })
+}
+
+/// Names of the cell measurements usable in expressions
+pub const VARIABLES: [&str; 11] = [
+    "area",
+    "perimeter",
+    "circumcircle_radius",
+    "incircle_radius",
+    "circularity",
+    "solidity",
+    "elongation",
+    "hue",
+    "saturation",
+    "brightness",
+    "label",
+];
+
+/// Algae
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+pub struct Algae {
+    pub index: usize,
+    /// Area, µm² or px² without a scale
+    pub area: f64,
+    pub perimeter: f64,
+    pub circumcircle_radius: f64,
+    pub incircle_radius: f64,
+    /// `4πA/P²`, 1 for a circle
+    pub circularity: f64,
+    /// Ratio of the long side of the rotated rectangle to the short one
+    pub elongation: f64,
+    /// Ratio of the area to the area of the convex hull
+    #[serde(default)]
+    pub solidity: f64,
+    pub colors: Colors,
+    /// K-means cluster of most pixels of the cell
+    pub label: usize,
+    /// Columns computed by `config.filter`
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub columns: BTreeMap<String, f64>,
+}
+
+impl Measured for Algae {
+    /// Value of the measurement, the mean contour color for `hue`,
+    /// `saturation` and `brightness`, or of the computed column
+    fn variable(&self, name: &str) -> Option<f64> {
+        Some(match name {
+            "area" => self.area,
+            "perimeter" => self.perimeter,
+            "circumcircle_radius" => self.circumcircle_radius,
+            "incircle_radius" => self.incircle_radius,
+            "circularity" => self.circularity,
+            "solidity" => self.solidity,
+            "elongation" => self.elongation,
+            "hue" => self.colors.contour.hue,
+            "saturation" => self.colors.contour.saturation,
+            "brightness" => self.colors.contour.brightness,
+            "label" => self.label as _,
+            name => return self.columns.get(name).copied(),
+        })
+    }
+
+    fn columns_mut(&mut self) -> &mut BTreeMap<String, f64> {
+        &mut self.columns
+    }
+}
+
+/// Colors
+#[derive(Clone, Copy, Debug, Default, Deserialize, Serialize)]
+pub struct Colors {
+    pub contour: Hsb,
+    pub max_incircle: Hsb,
+    pub incircle: Hsb,
+}
+
+/// Measures the cells outlined by the contours
+pub fn measure(
+    source: &Mat,
+    clustering: &Clustering,
+    contours: &Vector<Mat>,
+    scale: Option<&Scale>,
+) -> Result<Vec<Algae>> {
+    let pixels = scale.map_or(1.0, |scale| scale.pixels);
+    let hsv = source.convert_color(COLOR_BGR2HSV)?;
+    let mean = |region| -> Result<Hsb> {
+        let statistics = statistics(&hsv, region, &[0, 1, 2])?;
+        Ok(VecN([statistics[0].mean, statistics[1].mean, statistics[2].mean]).into())
+    };
+    let mut cells = Vec::with_capacity(contours.len());
+    for (index, contour) in contours.iter().enumerate() {
+        let area = contour.area()?;
+        let perimeter = contour.perimeter(true)?;
+        let centroid = contour.moments(false)?.centroid();
+        let rotated_rectangle = contour.rotated_rectangle()?;
+        let min_circumcircle = contour.min_circumcircle()?;
+        let max_incircle = contour.max_incircle()?;
+        let incircle = contour.incircle(centroid)?;
+        let colors = Colors {
+            contour: mean(Region::Contour(&contour))?,
+            max_incircle: mean(Region::Circle(Circle {
+                center: Point2f::new(max_incircle.center.x as _, max_incircle.center.y as _),
+                radius: max_incircle.radius as _,
+            }))?,
+            incircle: mean(Region::Circle(Circle {
+                center: Point2f::new(incircle.center.x as _, incircle.center.y as _),
+                radius: incircle.radius as _,
+            }))?,
+        };
+        let label = clustering.label(&contour)?;
+        let size = rotated_rectangle.size;
+        cells.push(Algae {
+            index,
+            area: area / (pixels * pixels),
+            perimeter: perimeter / pixels,
+            circumcircle_radius: min_circumcircle.radius as f64 / pixels,
+            incircle_radius: max_incircle.radius as f64 / pixels,
+            circularity: 4.0 * PI * area / (perimeter * perimeter),
+            elongation: size.width.max(size.height) as f64
+                / size.width.min(size.height).max(f32::EPSILON) as f64,
+            solidity: solidity(&contour)?,
+            colors,
+            label,
+            columns: BTreeMap::new(),
+        });
+    }
+    Ok(cells)
+}
+
+/// Computes the columns of the cells and keeps the ones the filter keeps
+pub fn filter(cells: Vec<Algae>, config: &config::Filter) -> Result<Vec<Algae>> {
+    let mut kept = Vec::with_capacity(cells.len());
+    for mut cell in cells {
+        if crate::filter::filter(&mut cell, config)? {
+            kept.push(cell);
+        }
+    }
+    Ok(kept)
+}
+
+/// Summary
+#[derive(Clone, Copy, Debug, Default, Deserialize, Serialize)]
+pub struct Summary {
+    pub count: usize,
+    /// Image area, µm² or px² without a scale
+    pub area: f64,
+    /// Cells per µm² or px² without a scale
+    pub density: f64,
+}
+
+impl Summary {
+    pub fn new(size: Size, cells: &[Algae], scale: Option<&Scale>) -> Self {
+        let pixels = scale.map_or(1.0, |scale| scale.pixels);
+        let area = size.area() as f64 / (pixels * pixels);
+        Self {
+            count: cells.len(),
+            area,
+            density: cells.len() as f64 / area,
+        }
+    }
+}
+
+mod circles;
+mod score;
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn label_at_border() -> Result<()> {
+        // Left half 0, right half 1
+        let (columns, rows) = (10, 10);
+        let clustering = Clustering {
+            k: 2,
+            scores: Scores::default(),
+            centroids: vec![Srgb::new(0, 0, 0), Srgb::new(255, 255, 255)],
+            indices: (0..rows * columns)
+                .map(|index| u8::from(index % columns >= columns / 2))
+                .collect(),
+            image: Mat::zeros(rows as _, columns as _, CV_8UC3)?.to_mat()?,
+        };
+        let label = |x, y| -> Result<usize> {
+            let circle = Circle {
+                center: Point2f::new(x, y),
+                radius: 4.0,
+            };
+            clustering.label(&circle.contour()?)
+        };
+        assert_eq!(label(9.0, 5.0)?, 1);
+        assert_eq!(label(0.0, 0.0)?, 0);
+        assert_eq!(label(9.0, 9.0)?, 1);
+        // Outside the image
+        label(-20.0, -20.0)?;
+        Ok(())
+    }
+}
@@ -0,0 +1,150 @@
+use super::Clustering;
+use crate::config::Criterion;
+use itertools::Itertools;
+use palette::{white_point::D65, Lab};
+use serde::{Deserialize, Serialize};
+
+/// Scores of a clustering
+#[derive(Clone, Copy, Debug, Default, Deserialize, Serialize)]
+pub struct Scores {
+    pub k: usize,
+    /// Sum of squared distances to the centroids
+    pub inertia: f64,
+    /// Mean silhouette of a subsample, `None` for a single cluster
+    pub silhouette: Option<f64>,
+    /// Calinski-Harabasz index, `None` for a single cluster
+    pub calinski_harabasz: Option<f64>,
+}
+
+impl Scores {
+    pub fn new(
+        k: usize,
+        colors: &[Lab<D65, f32>],
+        centroids: &[Lab<D65, f32>],
+        indices: &[u8],
+        sample: usize,
+    ) -> Self {
+        let inertia = colors
+            .iter()
+            .zip(indices)
+            .map(|(color, &index)| distance2(color, &centroids[index as usize]))
+            .sum::<f64>();
+        Self {
+            k,
+            inertia,
+            silhouette: silhouette(k, colors, indices, sample),
+            calinski_harabasz: calinski_harabasz(k, colors, centroids, indices, inertia),
+        }
+    }
+}
+
+/// Chooses the clustering by the criterion
+pub fn choose(clusterings: &[Clustering], criterion: Criterion) -> Option<&Clustering> {
+    let by = |score: fn(&Scores) -> Option<f64>| {
+        clusterings
+            .iter()
+            .filter_map(|clustering| Some((clustering, score(&clustering.scores)?)))
+            .max_by(|(_, a), (_, b)| a.total_cmp(b))
+            .map(|(clustering, _)| clustering)
+    };
+    match criterion {
+        Criterion::Fixed(k) => clusterings.iter().find(|clustering| clustering.k == k),
+        Criterion::Elbow => elbow(clusterings),
+        Criterion::Silhouette => by(|scores| scores.silhouette),
+        Criterion::CalinskiHarabasz => by(|scores| scores.calinski_harabasz),
+    }
+}
+
+/// Knee of the inertia curve, the point farthest from the chord between the
+/// first and the last points of the normalized curve
+fn elbow(clusterings: &[Clustering]) -> Option<&Clustering> {
+    let (first, last) = (clusterings.first()?, clusterings.last()?);
+    let dk = (last.k - first.k).max(1) as f64;
+    let di = first.scores.inertia - last.scores.inertia;
+    if di <= 0.0 {
+        return Some(first);
+    }
+    clusterings
+        .iter()
+        .map(|clustering| {
+            let x = (clustering.k - first.k) as f64 / dk;
+            let y = (first.scores.inertia - clustering.scores.inertia) / di;
+            (clustering, y - x)
+        })
+        .max_by(|(_, a), (_, b)| a.total_cmp(b))
+        .map(|(clustering, _)| clustering)
+}
+
+/// Mean silhouette of evenly spaced samples
+fn silhouette(k: usize, colors: &[Lab<D65, f32>], indices: &[u8], sample: usize) -> Option<f64> {
+    if k < 2 || colors.is_empty() || sample == 0 {
+        return None;
+    }
+    let step = (colors.len() / sample).max(1);
+    let samples = (0..colors.len())
+        .step_by(step)
+        .map(|index| (&colors[index], indices[index] as usize))
+        .collect_vec();
+    let mut sum = 0.0;
+    for &(color, cluster) in &samples {
+        let mut distances = vec![(0.0, 0usize); k];
+        for &(other, index) in &samples {
+            distances[index].0 += distance2(color, other).sqrt();
+            distances[index].1 += 1;
+        }
+        // The sample itself is counted with a zero distance
+        let (own, count) = distances[cluster];
+        if count < 2 {
+            continue;
+        }
+        let a = own / (count - 1) as f64;
+        let b = distances
+            .iter()
+            .enumerate()
+            .filter(|&(index, &(_, count))| index != cluster && count != 0)
+            .map(|(_, &(distance, count))| distance / count as f64)
+            .min_by(f64::total_cmp);
+        if let Some(b) = b {
+            sum += (b - a) / a.max(b).max(f64::EPSILON);
+        }
+    }
+    Some(sum / samples.len() as f64)
+}
+
+/// Ratio of the between-cluster dispersion to the within-cluster one
+fn calinski_harabasz(
+    k: usize,
+    colors: &[Lab<D65, f32>],
+    centroids: &[Lab<D65, f32>],
+    indices: &[u8],
+    inertia: f64,
+) -> Option<f64> {
+    let n = colors.len();
+    if k < 2 || n <= k || inertia <= 0.0 {
+        return None;
+    }
+    let mut counts = vec![0usize; centroids.len()];
+    let mut mean = [0.0f64; 3];
+    for (color, &index) in colors.iter().zip(indices) {
+        counts[index as usize] += 1;
+        mean[0] += color.l as f64;
+        mean[1] += color.a as f64;
+        mean[2] += color.b as f64;
+    }
+    let mean = Lab::<D65, f32>::new(
+        (mean[0] / n as f64) as _,
+        (mean[1] / n as f64) as _,
+        (mean[2] / n as f64) as _,
+    );
+    let between = centroids
+        .iter()
+        .zip(&counts)
+        .map(|(centroid, &count)| count as f64 * distance2(centroid, &mean))
+        .sum::<f64>();
+    Some((between / (k - 1) as f64) / (inertia / (n - k) as f64))
+}
+
+fn distance2(a: &Lab<D65, f32>, b: &Lab<D65, f32>) -> f64 {
+    let (l, a, b) = (a.l - b.l, a.a - b.a, a.b - b.b);
+    (l * l + a * a + b * b) as f64
+}
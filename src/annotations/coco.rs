@@ -0,0 +1,191 @@
+use super::{object, points};
+use crate::object::Object;
+use anyhow::{bail, Context, Result};
+use opencv::core::Size;
+use serde::{Deserialize, Serialize};
+use std::{
+    fs::File,
+    io::{BufReader, BufWriter},
+    path::Path,
+};
+
+const CATEGORY: &str = "object";
+
+/// COCO instance segmentation
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+pub struct Coco {
+    pub images: Vec<Image>,
+    pub annotations: Vec<Annotation>,
+    pub categories: Vec<Category>,
+}
+
+impl Coco {
+    /// Objects of an image, coordinates are divided by the scale in pixels per
+    /// unit, `1.0` keeps pixels
+    pub fn new(file_name: &str, size: Size, objects: &[Object], scale: f64) -> Result<Self> {
+        let mut coco = Self::default();
+        coco.add(file_name, size, objects, scale)?;
+        Ok(coco)
+    }
+
+    /// Adds the objects of an image
+    pub fn add(
+        &mut self,
+        file_name: &str,
+        size: Size,
+        objects: &[Object],
+        scale: f64,
+    ) -> Result<()> {
+        let image_id = self.images.len() as u64 + 1;
+        self.images.push(Image {
+            id: image_id,
+            file_name: file_name.to_owned(),
+            width: size.width,
+            height: size.height,
+        });
+        for object in objects {
+            let name = object.class.as_deref().unwrap_or(CATEGORY);
+            let category_id = match self
+                .categories
+                .iter()
+                .find(|category| category.name == name)
+            {
+                Some(category) => category.id,
+                None => {
+                    let id = self.categories.len() as u64 + 1;
+                    self.categories.push(Category {
+                        id,
+                        name: name.to_owned(),
+                        supercategory: String::new(),
+                    });
+                    id
+                }
+            };
+            let rect = object.bounding_rectangle;
+            self.annotations.push(Annotation {
+                id: self.annotations.len() as u64 + 1,
+                image_id,
+                category_id,
+                segmentation: Segmentation::Polygons(
+                    vec![points(&object.contour, scale)?.concat()],
+                ),
+                bbox: [
+                    rect.x as f64 / scale,
+                    rect.y as f64 / scale,
+                    rect.width as f64 / scale,
+                    rect.height as f64 / scale,
+                ],
+                area: object.area / (scale * scale),
+                iscrowd: 0,
+            });
+        }
+        Ok(())
+    }
+
+    /// Objects of the image with the file name or its stem, the first polygon
+    /// of every annotation is its contour
+    pub fn objects(&self, file_name: &str, scale: f64) -> Result<Vec<Object>> {
+        let image = self
+            .images
+            .iter()
+            .find(|image| image.file_name == file_name)
+            .or_else(|| {
+                self.images
+                    .iter()
+                    .find(|image| stem(&image.file_name) == stem(file_name))
+            })
+            .or_else(|| (self.images.len() == 1).then(|| &self.images[0]))
+            .with_context(|| format!("no image {file_name} in the annotations"))?;
+        let mut objects = Vec::new();
+        for annotation in &self.annotations {
+            if annotation.image_id != image.id {
+                continue;
+            }
+            let polygon = match &annotation.segmentation {
+                Segmentation::Polygons(polygons) => match polygons.first() {
+                    Some(polygon) => polygon,
+                    None => continue,
+                },
+                Segmentation::Rle { .. } => bail!(
+                    "annotation {}: RLE segmentation is not supported",
+                    annotation.id,
+                ),
+            };
+            let class = self
+                .categories
+                .iter()
+                .find(|category| category.id == annotation.category_id)
+                .map(|category| category.name.clone())
+                .filter(|name| name != CATEGORY);
+            let points = polygon.chunks_exact(2).map(|point| [point[0], point[1]]);
+            objects.extend(object(points, class, scale)?);
+        }
+        Ok(objects)
+    }
+
+    pub fn load(path: &Path) -> Result<Self> {
+        Ok(serde_json::from_reader(BufReader::new(File::open(path)?))?)
+    }
+
+    pub fn save(&self, path: &Path) -> Result<()> {
+        serde_json::to_writer_pretty(BufWriter::new(File::create(path)?), self)?;
+        Ok(())
+    }
+}
+
+/// File name up to the first dot
+fn stem(file_name: &str) -> &str {
+    let name = file_name.rsplit(['/', '\\']).next().unwrap_or(file_name);
+    name.split('.').next().unwrap_or(name)
+}
+
+/// Image
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+pub struct Image {
+    pub id: u64,
+    pub file_name: String,
+    pub width: i32,
+    pub height: i32,
+}
+
+/// Annotation
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+pub struct Annotation {
+    pub id: u64,
+    pub image_id: u64,
+    pub category_id: u64,
+    pub segmentation: Segmentation,
+    /// `[x, y, width, height]`
+    pub bbox: [f64; 4],
+    pub area: f64,
+    #[serde(default)]
+    pub iscrowd: u8,
+}
+
+/// Segmentation
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(untagged)]
+pub enum Segmentation {
+    /// Polygons as flat `[x0, y0, x1, y1, ...]` lists
+    Polygons(Vec<Vec<f64>>),
+    /// Run-length encoded mask, not supported
+    Rle {
+        counts: serde_json::Value,
+        size: [u32; 2],
+    },
+}
+
+impl Default for Segmentation {
+    fn default() -> Self {
+        Self::Polygons(Vec::new())
+    }
+}
+
+/// Category
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+pub struct Category {
+    pub id: u64,
+    pub name: String,
+    #[serde(default)]
+    pub supercategory: String,
+}
@@ -0,0 +1,112 @@
+use super::{object, points};
+use crate::object::Object;
+use anyhow::{ensure, Result};
+use serde::{Deserialize, Serialize};
+use std::{
+    fs::File,
+    io::{BufReader, BufWriter},
+    path::Path,
+};
+
+/// GeoJSON feature collection
+///
+/// Coordinates are image ones, y grows downwards.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct FeatureCollection {
+    pub r#type: String,
+    pub features: Vec<Feature>,
+}
+
+impl FeatureCollection {
+    /// Objects as polygons, coordinates are divided by the scale in pixels per
+    /// unit, `1.0` keeps pixels
+    pub fn new(objects: &[Object], scale: f64) -> Result<Self> {
+        let mut features = Vec::with_capacity(objects.len());
+        for (index, object) in objects.iter().enumerate() {
+            let mut ring = points(&object.contour, scale)?;
+            // Rings are closed
+            if let Some(&first) = ring.first() {
+                ring.push(first);
+            }
+            features.push(Feature {
+                r#type: "Feature".to_owned(),
+                geometry: Geometry {
+                    r#type: "Polygon".to_owned(),
+                    coordinates: vec![ring],
+                },
+                properties: Properties {
+                    index,
+                    area: object.area / (scale * scale),
+                    perimeter: object.perimeter / scale,
+                    class: object.class.clone(),
+                },
+            });
+        }
+        Ok(Self {
+            r#type: "FeatureCollection".to_owned(),
+            features,
+        })
+    }
+
+    /// Objects of the polygons, the exterior ring is the contour
+    pub fn objects(&self, scale: f64) -> Result<Vec<Object>> {
+        let mut objects = Vec::with_capacity(self.features.len());
+        for feature in &self.features {
+            ensure!(
+                feature.geometry.r#type == "Polygon",
+                "unsupported geometry {}",
+                feature.geometry.r#type,
+            );
+            let Some(ring) = feature.geometry.coordinates.first() else {
+                continue;
+            };
+            let ring = match ring.split_last() {
+                Some((last, rest)) if Some(last) == rest.first() => rest,
+                _ => ring,
+            };
+            objects.extend(object(
+                ring.iter().copied(),
+                feature.properties.class.clone(),
+                scale,
+            )?);
+        }
+        Ok(objects)
+    }
+
+    pub fn load(path: &Path) -> Result<Self> {
+        Ok(serde_json::from_reader(BufReader::new(File::open(path)?))?)
+    }
+
+    pub fn save(&self, path: &Path) -> Result<()> {
+        serde_json::to_writer_pretty(BufWriter::new(File::create(path)?), self)?;
+        Ok(())
+    }
+}
+
+/// Feature
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct Feature {
+    pub r#type: String,
+    pub geometry: Geometry,
+    #[serde(default)]
+    pub properties: Properties,
+}
+
+/// Geometry
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct Geometry {
+    pub r#type: String,
+    /// Rings of `[x, y]` positions, the first one is the exterior
+    pub coordinates: Vec<Vec<[f64; 2]>>,
+}
+
+/// Properties
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+#[serde(default)]
+pub struct Properties {
+    pub index: usize,
+    pub area: f64,
+    pub perimeter: f64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub class: Option<String>,
+}
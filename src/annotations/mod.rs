@@ -0,0 +1,119 @@
+pub use self::{
+    coco::{Coco, Segmentation},
+    geojson::FeatureCollection,
+};
+
+use crate::object::Object;
+use anyhow::Result;
+use opencv::{
+    core::{Mat, Point},
+    prelude::*,
+};
+
+/// Points of the contour, divided by the scale in pixels per unit
+fn points(contour: &Mat, scale: f64) -> Result<Vec<[f64; 2]>> {
+    Ok(contour
+        .data_typed::<Point>()?
+        .iter()
+        .map(|point| [point.x as f64 / scale, point.y as f64 / scale])
+        .collect())
+}
+
+/// Object of the points, multiplied by the scale in pixels per unit, `None`
+/// for degenerate polygons of less than 3 points
+fn object(
+    points: impl IntoIterator<Item = [f64; 2]>,
+    class: Option<String>,
+    scale: f64,
+) -> Result<Option<Object>> {
+    let points = points
+        .into_iter()
+        .map(|[x, y]| Point::new((x * scale).round() as _, (y * scale).round() as _))
+        .collect::<Vec<_>>();
+    if points.len() < 3 {
+        return Ok(None);
+    }
+    let mut object = Object::new(Mat::from_slice(&points)?.try_clone()?)?;
+    object.class = class;
+    Ok(Some(object))
+}
+
+mod coco;
+mod geojson;
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::object::Circle;
+    use opencv::core::{Point2f, Size};
+
+    fn objects() -> Result<Vec<Object>> {
+        [((20.0, 20.0), None), ((60.0, 40.0), Some("damaged"))]
+            .into_iter()
+            .map(|((x, y), class)| {
+                let circle = Circle {
+                    center: Point2f::new(x, y),
+                    radius: 10.0,
+                };
+                let mut object = Object::new(circle.contour()?)?;
+                object.class = class.map(ToOwned::to_owned);
+                Ok(object)
+            })
+            .collect()
+    }
+
+    fn assert_same(left: &[Object], right: &[Object]) -> Result<()> {
+        assert_eq!(left.len(), right.len());
+        for (left, right) in left.iter().zip(right) {
+            assert_eq!(
+                left.contour.data_typed::<Point>()?,
+                right.contour.data_typed::<Point>()?,
+            );
+            assert_eq!(left.class, right.class);
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn round_trip() -> Result<()> {
+        let objects = objects()?;
+        let scale = 2.0;
+        let coco = Coco::new("image.png", Size::new(100, 100), &objects, scale)?;
+        let coco = serde_json::from_str::<Coco>(&serde_json::to_string(&coco)?)?;
+        assert_same(&coco.objects("image.png", scale)?, &objects)?;
+        let features = FeatureCollection::new(&objects, scale)?;
+        let features =
+            serde_json::from_str::<FeatureCollection>(&serde_json::to_string(&features)?)?;
+        assert_same(&features.objects(scale)?, &objects)?;
+        Ok(())
+    }
+
+    #[test]
+    fn degenerate() -> Result<()> {
+        let mut coco = Coco::new("image.png", Size::new(100, 100), &objects()?, 1.0)?;
+        coco.annotations[0].segmentation = Segmentation::Polygons(vec![vec![1.0, 2.0]]);
+        assert_eq!(coco.objects("image.png", 1.0)?.len(), 1);
+        Ok(())
+    }
+
+    #[test]
+    fn rle() -> Result<()> {
+        let coco = serde_json::from_str::<Coco>(
+            r#"{
+                "images": [{"id": 1, "file_name": "image.png", "width": 4, "height": 4}],
+                "annotations": [{
+                    "id": 1,
+                    "image_id": 1,
+                    "category_id": 1,
+                    "segmentation": {"counts": [2, 4, 10], "size": [4, 4]},
+                    "bbox": [0, 0, 4, 4],
+                    "area": 4
+                }],
+                "categories": [{"id": 1, "name": "object"}]
+            }"#,
+        )?;
+        let error = coco.objects("image.png", 1.0).unwrap_err();
+        assert!(error.to_string().contains("RLE"));
+        Ok(())
+    }
+}
@@ -1,4 +1,8 @@
-use crate::{node::Node, view::Viewer};
+use crate::{
+    node::{Library, Node},
+    sweep::Sweep,
+    view::Viewer,
+};
 use clap::crate_version;
 use eframe::{get_value, set_value, CreationContext, Frame, Storage, APP_KEY};
 use egui::{
@@ -8,15 +12,25 @@ use egui_snarl::{
     ui::{BackgroundPattern, SnarlStyle, WireStyle},
     NodeId, Snarl,
 };
-use std::{collections::HashSet, path::PathBuf};
+use std::{
+    collections::HashSet,
+    path::{Path, PathBuf},
+};
+use tracing::error;
+
+/// Library file of subgraphs
+pub const LIBRARY: &str = "library.ron";
 
 // crate_version!()
 pub struct App {
     #[cfg(not(target_arch = "wasm32"))]
     path: Option<PathBuf>,
     snarl: Snarl<Node>,
+    library: Library,
     removed_node_indices: HashSet<NodeId>,
+    selected_node_indices: HashSet<NodeId>,
     updated_node_indices: HashSet<NodeId>,
+    sweep: Sweep,
     version: usize,
 }
 
@@ -26,7 +40,12 @@ impl App {
             .storage
             .and_then(|storage| get_value(storage, APP_KEY))
             .unwrap_or_default();
+        let library = Library::load(Path::new(LIBRARY)).unwrap_or_else(|error| {
+            error!(%error);
+            Default::default()
+        });
         let removed_node_indices = Default::default();
+        let selected_node_indices = Default::default();
         let updated_node_indices = Default::default();
         // let updated_node_indices = snarl
         //     .node_ids()
@@ -37,8 +56,11 @@ impl App {
             #[cfg(not(target_arch = "wasm32"))]
             path: None,
             snarl,
+            library,
             removed_node_indices,
+            selected_node_indices,
             updated_node_indices,
+            sweep: Default::default(),
             version: 0,
         }
     }
@@ -53,8 +75,11 @@ impl eframe::App for App {
         CentralPanel::default().show(ctx, |ui| {
             self.snarl.show(
                 &mut Viewer {
+                    library: &mut self.library,
                     removed_ids: &mut self.removed_node_indices,
+                    selected_ids: &mut self.selected_node_indices,
                     updated_ids: &mut self.updated_node_indices,
+                    sweep: &mut self.sweep,
                 },
                 &SnarlStyle {
                     _collapsible: Some(true),
@@ -68,6 +93,7 @@ impl eframe::App for App {
                 warn_if_debug_build(ui);
             });
         });
+        self.sweep.show(ctx, &mut self.snarl);
         // if self.has_changes() {
         //     self.remove_nodes();
         //     self.update_nodes(ctx);
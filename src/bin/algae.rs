@@ -1,39 +1,21 @@
-use anyhow::{anyhow, Result};
-use clap::{command, Parser};
+use anyhow::Result;
+use clap::{command, Parser, ValueEnum};
 use cv::{Contour, Draw, MatExt, MatTraitConstExt, ToInputArrayExt};
-use egui::epaint::util::FloatOrd;
-use finder::{Config, Hsb, BLACK, BLUE, CYAN, GREEN, MAGENTA, RED, WHITE, YELLOW};
-use image::{
-    codecs::png::{
-        CompressionType, FilterType,
-        FilterType::{Adaptive, NoFilter},
-        PngEncoder,
-    },
-    open, ExtendedColorType, ImageEncoder,
+use finder::{
+    algae::{choose, filter, hough, kmeans, matches, measure, scale, segment, Summary},
+    annotations::{Coco, FeatureCollection},
+    object::{Circle, Object},
+    pipeline::Pipeline,
+    preprocess::preprocess,
+    Config, BLUE, CYAN, GREEN, MAGENTA, RED, WHITE, YELLOW,
 };
-use itertools::Itertools;
-use kmeans_colors::{get_kmeans_hamerly, Kmeans, MapColor};
 use opencv::{
-    core::{
-        copy_to, no_array, normalize_def, MatTraitConst, Point, Point2f, Point2i, Rect, Scalar,
-        Size, VecN, Vector, CV_32S, CV_8U, CV_8UC1, CV_8UC3,
-    },
-    imgcodecs::{imread, IMREAD_COLOR, IMREAD_GRAYSCALE},
-    imgproc::{
-        hough_circles, hough_circles_def, match_template, match_template_def, CHAIN_APPROX_SIMPLE,
-        COLOR_BGR2GRAY, FILLED, HOUGH_GRADIENT, HOUGH_GRADIENT_ALT, RETR_EXTERNAL, THRESH_BINARY,
-        TM_CCOEFF_NORMED,
-    },
+    core::{Vector, CV_32S, CV_8UC1},
+    imgcodecs::IMREAD_COLOR,
     prelude::*,
 };
-use palette::{
-    cast::{AsComponents, ComponentsAs},
-    white_point::D65,
-    IntoColor, Lab, Srgb, Srgba,
-};
 use ron::ser::{to_writer_pretty, PrettyConfig};
-use serde::{Deserialize, Serialize};
-use std::{fs::File, io::BufWriter, path::PathBuf, process::exit};
+use std::{fs::File, path::PathBuf, process::exit};
 
 #[derive(Parser)]
 #[command(about, arg_required_else_help = true, long_about = None, version)]
@@ -44,17 +26,39 @@ struct Cli {
     /// Sets a custom config file
     #[arg(short, long, value_name = "CONFIG")]
     config: Option<PathBuf>,
-    // /// Turn debugging information on
-    // #[arg(short, long, action = clap::ArgAction::Count)]
-    // debug: u8,
+
+    /// Path to scale bar template image
+    #[arg(short, long, value_name = "TEMPLATE")]
+    template: Option<PathBuf>,
+
+    /// Blank reference image for the flat-field correction
+    #[arg(long, value_name = "REFERENCE")]
+    flat_field: Option<PathBuf>,
+
+    /// Exports the cells as annotations
+    #[arg(long, value_enum, value_name = "FORMAT")]
+    export: Option<Format>,
+
+    /// Pixels per unit of the annotation coordinates
+    #[arg(long, default_value_t = 1.0)]
+    scale: f64,
 }
 
-// let path = "assets/images/water_coins.jpg";
-// cargo run -- "assets/images/20240416_164427.jpg"
+/// Annotation format
+#[derive(Clone, Copy, Debug, ValueEnum)]
+enum Format {
+    Coco,
+    Geojson,
+}
+
+// cargo run --bin=algae -- "assets/SNAP-212329-0051/SNAP-212329-0051.tif"
 fn main() -> Result<()> {
     let cli = Cli::parse();
     let config = if let Some(path) = &cli.config {
-        match Config::new(path) {
+        match Config::new(path).and_then(|config| {
+            config.validate(Pipeline::Algae)?;
+            Ok(config)
+        }) {
             Ok(config) => config,
             Err(error) => {
                 println!("Failed to load config: {error}");
@@ -66,136 +70,106 @@ fn main() -> Result<()> {
     };
     to_writer_pretty(File::create("config.ron")?, &config, PrettyConfig::new())?;
 
-    // let source2 = imread(&cli.path.to_string_lossy(), IMREAD_COLOR)?;
-    // println!("{source2:?}");
-    // let t = source2.to(CV_32F)?.reshape_def(1)?.clone_pointee();
-    // println!("t: {t:?}");
-    // let (compactness, labels) = t.kmeans(2, TermCriteria::default()?, 10, KMEANS_RANDOM_CENTERS)?;
-    // println!("labels: {labels:?}");
-    // labels
-    //     .reshape(source2.channels(), source2.rows())?
-    //     .write(cli.path.with_extension("labels.png"))?;
-
-    let converge = 0.0025;
-    // let converge = opt.factor.unwrap_or(if !opt.rgb { 5.0 } else { 0.0025 });
-
-    let image = open(&cli.path)?.into_rgba8();
-    let (width, height) = image.dimensions();
-    // Convert image from Srgb to Lab
-    let lab = image
-        .as_raw()
-        .components_as()
-        .iter()
-        .map(|&color| Srgba::<u8>::into_linear::<_, f32>(color).into_color())
-        .collect::<Vec<Lab<D65, f32>>>();
-    for k in 1..=config.kmeans.k {
-        // Iterate over amount of runs keeping best results
-        let mut kmeans = Kmeans::new();
-        for index in 0..config.kmeans.runs {
-            let r#try = get_kmeans_hamerly(
-                k,
-                config.kmeans.iterations,
-                converge,
-                false,
-                &lab,
-                config.kmeans.seed + index,
-            );
-            if r#try.score < kmeans.score {
-                kmeans = r#try;
-            }
-        }
-        // Convert centroids to Srgb<u8> before mapping to buffer
-        let centroids = &kmeans
-            .centroids
-            .iter()
-            .map(|&color| Srgb::from_linear(color.into_color()))
-            .collect::<Vec<Srgb<u8>>>();
-        let rgb: Vec<Srgb<u8>> = Srgb::map_indices_to_centroids(centroids, &kmeans.indices);
-        // Write
-        let writer = BufWriter::new(File::create(cli.path.with_extension(format!("{k}.png")))?);
-        // If file is a palette, use Adaptive filtering to save more space
-        let encoder =
-            PngEncoder::new_with_quality(writer, CompressionType::Best, FilterType::NoFilter);
-        // Clean up if file is created but there's a problem writing to it
-        encoder.write_image(rgb.as_components(), width, height, ExtendedColorType::Rgb8)?;
-    }
-
+    // Read
     let source = Mat::read(&cli.path, IMREAD_COLOR)?;
-    let source2 = Mat::read(cli.path.with_extension("2.png"), IMREAD_COLOR)?;
-    let source3 = Mat::read(cli.path.with_extension("3.png"), IMREAD_COLOR)?;
-
-    // // Subtract
-    // let subtracted = source2.subtract(&source1)?;
-    // subtracted.write(cli.path.with_extension("subtracted.png"))?;
-
-    // Gray
-    let gray2 = source2.convert_color(COLOR_BGR2GRAY)?;
-    gray2.write(cli.path.with_extension("gray2.png"))?;
-    let gray3 = source3.convert_color(COLOR_BGR2GRAY)?;
-    gray3.write(cli.path.with_extension("gray3.png"))?;
-
-    // Template
-    let template = Mat::read(&cli.path.with_file_name("template.10mum.png"), IMREAD_COLOR)?;
-    let match_template = source.match_template(&template, TM_CCOEFF_NORMED, 0.9)?;
-    let mut scale = source.clone();
-    scale.draw_rectangle(match_template, RED)?;
-    scale.write(cli.path.with_extension("scale.png"))?;
-    let mum = match_template.width / 10;
-    println!("mum: {mum:?}");
-
-    // let gaussian_blur = source.gaussian_blur(Size::new(9, 9), 100.0)?;
-    // gaussian_blur.write(cli.path.with_extension("_template.png"))?;
+    if source.empty() {
+        println!("Source image is empty");
+        exit(1);
+    }
 
-    let mask = {
-        let template = template.convert_color(COLOR_BGR2GRAY)?;
-        let mut mask = Mat::zeros_size(source.size()?, CV_8UC1)?.to_mat()?;
-        template.copy_to(&mut mask.roi_mut(match_template)?)?;
-        let mean = source.mean(&mask)?;
-        println!("mean: {mean:?}");
-        let mut t = source.clone();
-        let temp = Mat::new_size_with_default(template.size()?, CV_8UC3, mean)?;
-        temp.copy_to(&mut t.roi_mut(match_template)?)?;
-        t.write(cli.path.with_extension("_temp.png"))?;
-        mask
+    // Scale
+    let scale = if let Some(path) = &cli.template {
+        let template = Mat::read(path, IMREAD_COLOR)?;
+        let scale = scale(&source, &template, &config.algae.scale)?;
+        println!("pixels per µm: {}", scale.pixels);
+        let mut target = source.clone();
+        target.draw_rectangle(scale.rectangle, RED)?;
+        target.write(cli.path.with_extension("scale.png"))?;
+        scale
+            .mask
+            .write(cli.path.with_extension("scale.mask.png"))?;
+        Some(scale)
+    } else {
+        None
     };
-    mask.write(cli.path.with_extension("_mask.png"))?;
 
-    // let mut dst = Mat::zeros_size(source.size()?, CV_8UC1)?.to_mat()?;
-    // copy_to(&source, &mut dst, &mask)?;
-
-    // // let mask = template.convert_color(COLOR_BGR2GRAY)?;
-    // println!("source: {:?}", source);
-    // println!("template: {:?}", mask);
-    // mask.write(cli.path.with_extension("_template.png"))?;
-    // let mean = source.mean(&mask)?;
-    // println!("mean: {mean:?}");
+    // Preprocess, the scale bar inpainted
+    let reference = cli
+        .flat_field
+        .as_ref()
+        .map(|path| Mat::read(path, IMREAD_COLOR))
+        .transpose()?;
+    let mask = scale
+        .as_ref()
+        .map(|scale| -> Result<Mat> {
+            let mut mask = Mat::zeros_size(source.size()?, CV_8UC1)?.to_mat()?;
+            Mat::new_size_with_default(scale.rectangle.size(), CV_8UC1, WHITE)?
+                .copy_to(&mut mask.roi_mut(scale.rectangle)?)?;
+            Ok(mask)
+        })
+        .transpose()?;
+    let source = preprocess(
+        &source,
+        &config.preprocess,
+        reference.as_ref(),
+        mask.as_ref(),
+    )?;
+    source.write(cli.path.with_extension("preprocessed.png"))?;
+
+    // K-means
+    let clusterings = kmeans(&source, &config)?;
+    for clustering in &clusterings {
+        clustering
+            .image
+            .write(cli.path.with_extension(format!("{}.png", clustering.k)))?;
+    }
+    let scores = clusterings
+        .iter()
+        .map(|clustering| clustering.scores)
+        .collect::<Vec<_>>();
+    to_writer_pretty(
+        File::create(cli.path.with_extension("kmeans.ron"))?,
+        &scores,
+        PrettyConfig::new().depth_limit(1),
+    )?;
+    let Some(clustering) = choose(&clusterings, config.algae.segmentation.criterion) else {
+        println!(
+            "No k-means clustering matches {:?} in range 1..={}",
+            config.algae.segmentation.criterion, config.kmeans.k,
+        );
+        exit(1);
+    };
+    println!("k: {}", clustering.k);
+    clustering
+        .labels()?
+        .write(cli.path.with_extension("labels.png"))?;
+    clustering
+        .palette_image(32)?
+        .write(cli.path.with_extension("palette.png"))?;
+    to_writer_pretty(
+        File::create(cli.path.with_extension("palette.ron"))?,
+        &clustering.palette(),
+        PrettyConfig::new().depth_limit(1),
+    )?;
 
-    // Foreground
-    let greater_than3 = gray3
-        .greater_than(gray3.min(&no_array())?.1)?
-        .bitwise_not()?;
-    greater_than3.write(cli.path.with_extension("greater_than3.png"))?;
-    // Background
-    let less_than3 = gray3.less_than(gray3.max(&no_array())?.1)?;
-    less_than3.write(cli.path.with_extension("less_than3.png"))?;
-    // Unknown
-    let subtract3 = less_than3.subtract(&greater_than3)?;
-    subtract3.write(cli.path.with_extension("subtract3.png"))?;
+    // Segmentation
+    let segmentation = segment(clustering, &config)?;
+    segmentation
+        .foreground
+        .write(cli.path.with_extension("foreground.png"))?;
+    segmentation
+        .background
+        .write(cli.path.with_extension("background.png"))?;
+    segmentation
+        .core
+        .write(cli.path.with_extension("core.png"))?;
+    segmentation
+        .unknown
+        .write(cli.path.with_extension("unknown.png"))?;
 
-    let mut target = source.clone();
     // Contours
-    let contours = less_than3.find_contours(RETR_EXTERNAL, CHAIN_APPROX_SIMPLE)?;
-    let size = less_than3.size()?;
-    let mut filtered = Vector::<Mat>::default();
-    for contour in &contours {
-        if contour.area()? < config.contours.min_area {
-            continue;
-        }
-        if contour.iter::<VecN<i32, 2>>()?.any(|(_, VecN([x, y]))| {
-            x == 0 || y == 0 || x == size.width - 1 || y == size.height - 1
-        }) {
-            continue;
-        }
+    let mut target = source.clone();
+    for contour in &segmentation.contours {
         target.draw_contour(&contour, RED, 1)?;
         // convex hull
         let convex_hull = contour.convex_hull()?;
@@ -211,165 +185,98 @@ fn main() -> Result<()> {
         // min circumcircle
         let min_circumcircle = contour.min_circumcircle()?;
         target.draw_circle(min_circumcircle.center, min_circumcircle.radius, MAGENTA, 1)?;
-        filtered.push(contour);
     }
     target.write(cli.path.with_extension("target.png"))?;
 
-    // // Threshold
-    // let binary2 = gray2.threshold(0.0, 255.0, THRESH_BINARY_INV | THRESH_OTSU)?;
-    // binary2.write(cli.path.with_extension("binary2.png"))?;
-    // let binary3 = gray3.threshold(127.0, 255.0, ADAPTIVE_THRESH_GAUSSIAN_C)?;
-    // binary3.write(cli.path.with_extension("binary3.png"))?;
-
     // Markers
-    let mut markers = greater_than3.connected_components(8, CV_32S)?;
-    markers.write(cli.path.with_extension("markers21.png"))?;
+    let mut markers = segmentation.core.connected_components(8, CV_32S)?;
     for (index, (_, value)) in markers.iter_mut::<i32>()?.enumerate() {
-        if *subtract3.at::<u8>(index as _)? == 255 {
-            *value = 255;
-        } else if *value != 0 {
+        if *segmentation.unknown.at::<u8>(index as _)? == 255 || *value != 0 {
             *value = 255;
-        } else {
-            *value = 0;
         }
     }
-    markers.write(cli.path.with_extension("markers22.png"))?;
+    markers.write(cli.path.with_extension("markers.png"))?;
 
-    // hough circles !!!!!!!!!!!!!!!!!!!!!!!!!!!
-    let mut c = Mat::ones_size(source.size()?, CV_8U)?.to_mat()?;
-    c.draw_contours(&filtered, WHITE, FILLED)?;
-    c.write(cli.path.with_extension("_c.png"))?;
-    let mut hough = source2.clone();
-    let mut circles = Vector::<VecN<f32, 4>>::default();
-    hough_circles(
-        &c,
-        &mut circles,
-        HOUGH_GRADIENT_ALT,
-        1.5,
-        10.0,
-        300.0,
-        0.75,
-        10,
-        50,
+    // Cells
+    let cells = filter(
+        measure(&source, clustering, &segmentation.contours, scale.as_ref())?,
+        &config.filter,
+    )?;
+    to_writer_pretty(
+        File::create(cli.path.with_extension("ron"))?,
+        &cells,
+        PrettyConfig::new().depth_limit(1),
     )?;
-    for circle in circles {
-        println!("circle: {circle:?}");
-        hough.draw_circle(Point2f::new(circle[0], circle[1]), circle[2], RED, 1)?;
+    if let Some(format) = cli.export {
+        let objects = cells
+            .iter()
+            .map(|cell| Object::new(segmentation.contours.get(cell.index)?))
+            .collect::<Result<Vec<_>>>()?;
+        let name = cli.path.file_name().unwrap_or_default().to_string_lossy();
+        match format {
+            Format::Coco => Coco::new(&name, source.size()?, &objects, cli.scale)?
+                .save(&cli.path.with_extension("coco.json"))?,
+            Format::Geojson => FeatureCollection::new(&objects, cli.scale)?
+                .save(&cli.path.with_extension("geojson"))?,
+        }
     }
-    hough.write(cli.path.with_extension("_hough.png"))?;
-
-    // source2.watershed(&mut markers)?;
-    // markers.write(cli.path.with_extension("markers23.png"))?;
-
-    // // Erode
-    // let erode2 = markers.to(CV_32F)?.erode(
-    //     &get_structuring_element_def(MORPH_ELLIPSE, Size::new(9, 9))?,
-    //     1,
-    // )?;
-    // erode2.write(cli.path.with_extension("erode2.png"))?;
-    // // Close
-    // let close2 = markers.to(CV_32F)?.morphology(
-    //     MORPH_OPEN,
-    //     &get_structuring_element_def(MORPH_ELLIPSE, Size::new(3, 3))?,
-    //     1,
-    // )?;
-    // close2.write(cli.path.with_extension("close2.png"))?;
-
-    // let mut markers = temp3.connected_components(8, CV_32S)?;
-    // markers.write(cli.path.with_extension("markers31.png"))?;
-    // source3.watershed(&mut markers)?;
-    // markers.write(cli.path.with_extension("markers32.png"))?;
-
-    // // Distance transform
-    // let distance_transform3 = less_than3.distance_transform(DIST_C, DIST_MASK_5)?;
-    // distance_transform3.write(cli.path.with_extension("distance_transform3.png"))?;
-    // let temp3 = distance_transform3
-    //     .threshold(0.0, 255.0, ADAPTIVE_THRESH_MEAN_C)?
-    //     .to(CV_8U)?;
-    // temp3.write(cli.path.with_extension("temp3.png"))?;
 
-    // // Remove noise
-    // let opened = binary.morphology(MORPH_OPEN, &Mat::ones(3, 3, CV_8U)?, 3)?;
-    // opened.write(cli.path.with_extension("opened.png"))?;
-
-    // // Foreground
-    // let distance_transform = opened.distance_transform(DIST_L2, DIST_MASK_5)?;
-    // distance_transform.write(cli.path.with_extension("distance_transform.png"))?;
-
-    // Canny
-    let canny3 = gray3.canny(1.0, 0.0)?;
-    canny3.write(cli.path.with_extension("canny3.png"))?;
-    // Blur
-    // let blur3 = canny3.median_blur(1)?;
-    // blur3.write(cli.path.with_extension("blur3.png"))?;
-
-    // // Dilate
-    // let dilate3 = canny3.dilate(&Mat::ones(3, 3, CV_8U)?, 1)?;
-    // dilate3.write(cli.path.with_extension("dilate3.png"))?;
-    // // Erode
-    // let erode3 = canny3.erode(&get_structuring_element_def(MORPH_CROSS, Size::new(7, 7))?)?;
-    // erode3.write(cli.path.with_extension("erode3.png"))?;
-
-    // let data = labels
-    //     .iter::<i32>()?
-    //     .map(|(_, value)| match value {
-    //         0 => WHITE,
-    //         value => color(value),
-    //     })
-    //     .collect::<Vec<_>>();
-    // let colored_labels = Mat::from_slice(&data)?;
-    // let m = colored_labels.reshape(4, rows)?;
-    // m.write(cli.path.with_extension("colored_labels.png"))?;
-
-    // for (_, value) in labels.iter_mut::<i32>()? {
-    //     if *value == 0 {
-    //         *value = 255;
-    //     }
-    // }
-    // labels.write(cli.path.with_extension("labels.png"))?;
+    // Circles
+    let circles = hough(
+        source.size()?,
+        &segmentation.contours,
+        &config.algae.hough,
+        scale.as_ref(),
+    )?
+    .iter()
+    .map(Circle::contour)
+    .collect::<Result<Vector<_>>>()?;
+    let objects = filter(
+        measure(&source, clustering, &circles, scale.as_ref())?,
+        &config.filter,
+    )?;
+    to_writer_pretty(
+        File::create(cli.path.with_extension("circles.ron"))?,
+        &objects,
+        PrettyConfig::new().depth_limit(1),
+    )?;
+    let matches = matches(&circles, &segmentation.contours, config.algae.hough.iou)?;
+    to_writer_pretty(
+        File::create(cli.path.with_extension("matches.ron"))?,
+        &matches,
+        PrettyConfig::new().depth_limit(1),
+    )?;
+    // Matched green, circles only red, contours only yellow
+    let mut target = source.clone();
+    for r#match in &matches {
+        let color = match (r#match.circle, r#match.contour) {
+            (Some(_), Some(_)) => GREEN,
+            (Some(_), None) => RED,
+            _ => YELLOW,
+        };
+        if let Some(circle) = r#match.circle {
+            target.draw_contour(&circles.get(circle)?, color, 1)?;
+        }
+        if let Some(contour) = r#match.contour {
+            target.draw_contour(&segmentation.contours.get(contour)?, color, 1)?;
+        }
+    }
+    target.write(cli.path.with_extension("circles.png"))?;
+    let disagreements = matches
+        .iter()
+        .filter(|r#match| r#match.is_disagreement())
+        .count();
+    println!("disagreements: {disagreements}");
 
-    // // Background
-    // let background = binary.dilate(&Mat::ones(3, 3, CV_8U)?, 3)?;
-    // background.write(cli.path.with_extension("background.png"))?;
+    let summary = Summary::new(source.size()?, &cells, scale.as_ref());
+    let unit = if scale.is_some() { "µm" } else { "px" };
+    println!("count: {}", summary.count);
+    println!("area: {:.2} {unit}²", summary.area);
+    println!("density: {:e} per {unit}²", summary.density);
 
     Ok(())
 }
 
-/// Algae
-#[derive(Clone, Copy, Debug, Default, Deserialize, Serialize)]
-struct Algae {
-    area: f64,
-    circumcircle_radius: f64,
-    incircle_radius: f64,
-    perimeter: f64,
-    colors: Colors,
-}
-
-/// Colors
-#[derive(Clone, Copy, Debug, Default, Deserialize, Serialize)]
-struct Colors {
-    contour: Hsb,
-    max_incircle: Hsb,
-    incircle: Hsb,
-}
-
-/// Circle
-#[derive(Clone, Copy, Debug, Default)]
-struct Circle {
-    center: Point2i,
-    radius: f64,
-}
-
-trait RectExt {
-    fn tr(&self) -> Point2i;
-}
-
-impl RectExt for Rect {
-    fn tr(&self) -> Point2i {
-        Point2i::new(self.x + self.width, self.y)
-    }
-}
-
 // fn probabilistic_hough(edges: &Mat) -> Result<()> {
 //     let mut p_lines = VectorOfVec4i::new();
 //     let mut probabalistic_hough = Mat::default();
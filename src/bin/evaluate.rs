@@ -0,0 +1,73 @@
+use anyhow::{ensure, Result};
+use clap::{command, Parser};
+use finder::evaluate::{aggregate, load, Evaluation, Metrics};
+use ron::ser::{to_writer_pretty, PrettyConfig};
+use std::{fs::File, path::PathBuf};
+
+#[derive(Parser)]
+#[command(about, arg_required_else_help = true, long_about = None, version)]
+struct Cli {
+    /// Paths to predicted objects: COCO (`.json`), GeoJSON (`.geojson`) or
+    /// label images
+    #[arg(required = true)]
+    predicted: Vec<PathBuf>,
+    /// Paths to ground truth objects, in the order of the predicted ones
+    #[arg(short, long, required = true, num_args = 1..)]
+    truth: Vec<PathBuf>,
+    /// Minimum intersection over union of matched objects
+    #[arg(long, default_value_t = 0.5)]
+    iou: f64,
+    /// Pixels per unit of the annotation coordinates
+    #[arg(long, default_value_t = 1.0)]
+    scale: f64,
+    /// Path to the evaluation report
+    #[arg(short, long, default_value = "evaluation.ron")]
+    output: PathBuf,
+}
+
+// cargo run --bin=evaluate -- "assets/20240416_164427/20240416_164427.coco.json" --truth="assets/20240416_164427/20240416_164427.labels.png"
+fn main() -> Result<()> {
+    let cli = Cli::parse();
+    ensure!(
+        cli.predicted.len() == cli.truth.len(),
+        "{} predicted and {} ground truth paths",
+        cli.predicted.len(),
+        cli.truth.len(),
+    );
+    let mut evaluations = Vec::with_capacity(cli.predicted.len());
+    println!("image\tpredicted\ttruth\tprecision\trecall\tf1\tcount error\tmean iou\tarea error");
+    for (predicted, truth) in cli.predicted.iter().zip(&cli.truth) {
+        let name = truth.file_name().unwrap_or_default().to_string_lossy();
+        let evaluation = Evaluation::new(
+            &name,
+            &load(predicted, &name, cli.scale)?,
+            &load(truth, &name, cli.scale)?,
+            cli.iou,
+        )?;
+        print(&name, &evaluation.metrics);
+        evaluations.push(evaluation);
+    }
+    let aggregated = aggregate(&evaluations);
+    print("total", &aggregated);
+    to_writer_pretty(
+        File::create(&cli.output)?,
+        &(evaluations, aggregated),
+        PrettyConfig::new().depth_limit(3),
+    )?;
+    Ok(())
+}
+
+fn print(name: &str, metrics: &Metrics) {
+    println!(
+        "{name}\t{}\t{}\t{:.3}\t{:.3}\t{:.3}\t{}\t{:.3}\t{:+.3} ± {:.3}",
+        metrics.predicted,
+        metrics.truth,
+        metrics.precision,
+        metrics.recall,
+        metrics.f1,
+        metrics.count_error,
+        metrics.mean_iou,
+        metrics.area_error.mean,
+        metrics.area_error.std,
+    );
+}
@@ -0,0 +1,164 @@
+use anyhow::{ensure, Result};
+use clap::{command, Parser, ValueEnum};
+use cv::MatExt;
+use finder::{
+    evaluate::load,
+    optimize::{Optimizer, Sample, Search, Space},
+    pipeline::Pipeline,
+    Config,
+};
+use opencv::{imgcodecs::IMREAD_COLOR, prelude::*};
+use ron::{
+    de::from_reader,
+    ser::{to_writer_pretty, PrettyConfig},
+};
+use std::{
+    fs::File,
+    io::{BufWriter, Write},
+    path::PathBuf,
+};
+
+#[derive(Parser)]
+#[command(about, arg_required_else_help = true, long_about = None, version)]
+struct Cli {
+    /// Paths to source images
+    #[arg(required = true)]
+    images: Vec<PathBuf>,
+    /// Paths to ground truth objects, in the order of the images
+    #[arg(short, long, required = true, num_args = 1..)]
+    truth: Vec<PathBuf>,
+    /// Sets a base config file
+    #[arg(short, long, value_name = "CONFIG")]
+    config: Option<PathBuf>,
+    /// Sets a search space file
+    #[arg(long, value_name = "SPACE")]
+    space: Option<PathBuf>,
+    #[arg(long, value_enum, default_value_t = PipelineArg::Seeds)]
+    pipeline: PipelineArg,
+    #[arg(long, value_enum, default_value_t = SearchArg::Grid)]
+    search: SearchArg,
+    /// Number of trials of the random and bayesian searches
+    #[arg(long, default_value_t = 64)]
+    trials: usize,
+    /// Minimum intersection over union of matched objects
+    #[arg(long, default_value_t = 0.5)]
+    iou: f64,
+    /// Pixels per unit of the annotation coordinates
+    #[arg(long, default_value_t = 1.0)]
+    scale: f64,
+    #[arg(long, default_value_t = 0)]
+    seed: u64,
+    /// Path to the best config
+    #[arg(short, long, default_value = "optimized.ron")]
+    output: PathBuf,
+    /// Path to the ranked table of trials
+    #[arg(long, default_value = "trials.tsv")]
+    table: PathBuf,
+}
+
+#[derive(Clone, Copy, Debug, ValueEnum)]
+enum PipelineArg {
+    Seeds,
+    Algae,
+}
+
+#[derive(Clone, Copy, Debug, ValueEnum)]
+enum SearchArg {
+    Grid,
+    Random,
+    Bayesian,
+}
+
+// cargo run --bin=optimize -- "assets/20240416_164427/20240416_164427.jpg" --truth="truth.json" --search=bayesian
+fn main() -> Result<()> {
+    let cli = Cli::parse();
+    ensure!(
+        cli.images.len() == cli.truth.len(),
+        "{} images and {} ground truth paths",
+        cli.images.len(),
+        cli.truth.len(),
+    );
+    let pipeline = match cli.pipeline {
+        PipelineArg::Seeds => Pipeline::Seeds,
+        PipelineArg::Algae => Pipeline::Algae,
+    };
+    let base = match &cli.config {
+        Some(path) => Config::new(path)?,
+        None => Config::default(),
+    };
+    base.validate(pipeline)?;
+    let space = match &cli.space {
+        Some(path) => from_reader(File::open(path)?)?,
+        None => Space::new(pipeline),
+    };
+    for dimension in &space.dimensions {
+        ensure!(
+            dimension.parameter.applies(pipeline),
+            "{:?} does not apply to the {pipeline:?} pipeline",
+            dimension.parameter,
+        );
+    }
+    let mut samples = Vec::with_capacity(cli.images.len());
+    for (image, truth) in cli.images.iter().zip(&cli.truth) {
+        let name = image.file_name().unwrap_or_default().to_string_lossy();
+        let source = Mat::read(image, IMREAD_COLOR)?;
+        ensure!(!source.empty(), "image {} is empty", image.display());
+        samples.push(Sample {
+            name: name.to_string(),
+            image: source,
+            truth: load(truth, &name, cli.scale)?,
+        });
+    }
+    let optimizer = Optimizer {
+        pipeline,
+        space,
+        search: match cli.search {
+            SearchArg::Grid => Search::Grid,
+            SearchArg::Random => Search::Random,
+            SearchArg::Bayesian => Search::Bayesian,
+        },
+        trials: cli.trials,
+        iou: cli.iou,
+        seed: cli.seed,
+    };
+    let trials = optimizer.run(&base, &samples);
+    // Table
+    let mut table = BufWriter::new(File::create(&cli.table)?);
+    write!(table, "rank")?;
+    for dimension in &optimizer.space.dimensions {
+        write!(table, "\t{:?}", dimension.parameter)?;
+    }
+    writeln!(
+        table,
+        "\tf1\tprecision\trecall\tmean iou\tcount error\terror"
+    )?;
+    for (rank, trial) in trials.iter().enumerate() {
+        write!(table, "{}", rank + 1)?;
+        for (_, value) in &trial.values {
+            write!(table, "\t{value}")?;
+        }
+        let metrics = &trial.metrics;
+        writeln!(
+            table,
+            "\t{:.4}\t{:.4}\t{:.4}\t{:.4}\t{}\t{}",
+            metrics.f1,
+            metrics.precision,
+            metrics.recall,
+            metrics.mean_iou,
+            metrics.count_error,
+            trial.error.as_deref().unwrap_or_default(),
+        )?;
+    }
+    // Best
+    let Some(best) = trials.first() else {
+        println!("No trials");
+        return Ok(());
+    };
+    println!("best: {:?} f1 {:.4}", best.values, best.score);
+    to_writer_pretty(
+        File::create(&cli.output)?,
+        &optimizer.config(&base, &best.values),
+        PrettyConfig::new(),
+    )?;
+    Ok(())
+}
@@ -1,19 +1,30 @@
 use anyhow::Result;
-use clap::{command, Parser};
-use cv::{Contour, Draw, MomentsExt, RectExt, ToInputArrayExt};
-use finder::{hsva_to_bgra, Config, Hsb, BLUE, CYAN, GREEN, MAGENTA, RED, WHITE, YELLOW};
+use clap::{command, Parser, ValueEnum};
+use cv::{Draw, ToInputArrayExt};
+use finder::{
+    annotations::{Coco, FeatureCollection},
+    calibration::calibrate,
+    classify::{classify, Model},
+    crops::{crops, labels},
+    object::Object,
+    pipeline::Pipeline,
+    preprocess::preprocess,
+    render::{layers, render},
+    seeds,
+    svg::{self, Background},
+    Config,
+};
 use opencv::{
-    core::{Vector, CV_8UC1},
-    imgcodecs::IMREAD_COLOR,
-    imgproc::{
-        CHAIN_APPROX_SIMPLE, COLOR_BGR2GRAY, COLOR_BGR2HSV, DIST_L2, DIST_MASK_5, FILLED,
-        FONT_HERSHEY_SIMPLEX, RETR_EXTERNAL, THRESH_BINARY_INV, THRESH_OTSU,
-    },
+    imgcodecs::{IMREAD_COLOR, IMREAD_GRAYSCALE},
     prelude::*,
 };
 use ron::ser::{to_writer_pretty, PrettyConfig};
-use serde::{Deserialize, Serialize};
-use std::{fs::File, path::PathBuf, process::exit};
+use std::{
+    fs::{create_dir_all, File},
+    io::BufWriter,
+    path::{Path, PathBuf},
+    process::exit,
+};
 
 #[derive(Parser)]
 #[command(about, arg_required_else_help = true, long_about = None, version)]
@@ -23,14 +34,64 @@ struct Cli {
     /// Sets a custom config file
     #[arg(short, long, value_name = "CONFIG")]
     config: Option<PathBuf>,
+    /// Writes each annotation layer as a transparent PNG
+    #[arg(long)]
+    layers: bool,
+    /// Writes an SVG drawing with a linked or an embedded background
+    #[arg(long, value_enum, value_name = "BACKGROUND")]
+    svg: Option<Svg>,
+    /// Exports the objects as annotations
+    #[arg(long, value_enum, value_name = "FORMAT")]
+    export: Option<Format>,
+    /// Imports the objects from COCO (`.json`) or GeoJSON (`.geojson`)
+    /// annotations instead of detecting them
+    #[arg(long, value_name = "ANNOTATIONS")]
+    import: Option<PathBuf>,
+    /// Pixels per unit of the annotation coordinates
+    #[arg(long, default_value_t = 1.0)]
+    scale: f64,
+    /// Writes the intermediate images
+    #[arg(long)]
+    debug: bool,
+    /// Blank reference image for the flat-field correction
+    #[arg(long, value_name = "REFERENCE")]
+    flat_field: Option<PathBuf>,
+    /// Mask of the regions to inpaint, such as a scale bar
+    #[arg(long, value_name = "MASK")]
+    mask: Option<PathBuf>,
+    /// Writes a crop `<index>.png` of each object and the 16-bit label image,
+    /// where the object of index `i` has the value `i + 1` (its COCO
+    /// annotation id) and the background `0`
+    #[arg(long)]
+    crops: bool,
+    /// Trains a classifier model on the classes of the imported objects
+    #[arg(long, value_name = "MODEL", requires = "import")]
+    train: Option<PathBuf>,
+}
+
+/// Annotation format
+#[derive(Clone, Copy, Debug, ValueEnum)]
+enum Format {
+    Coco,
+    Geojson,
+}
+
+/// SVG background
+#[derive(Clone, Copy, Debug, ValueEnum)]
+enum Svg {
+    Linked,
+    Embedded,
 }
 
 // let path = "assets/images/water_coins.jpg";
 // cargo run -- "assets/images/20240416_164427.jpg"
 fn main() -> Result<()> {
     let cli = Cli::parse();
-    let config = if let Some(path) = &cli.config {
-        match Config::new(path) {
+    let mut config = if let Some(path) = &cli.config {
+        match Config::new(path).and_then(|config| {
+            config.validate(Pipeline::Seeds)?;
+            Ok(config)
+        }) {
             Ok(config) => config,
             Err(error) => {
                 println!("Failed to load config: {error}");
@@ -40,6 +101,7 @@ fn main() -> Result<()> {
     } else {
         Config::default()
     };
+    config.debug |= cli.debug;
     to_writer_pretty(File::create("config.ron")?, &config, PrettyConfig::new())?;
 
     // Read
@@ -49,144 +111,116 @@ fn main() -> Result<()> {
         println!("Source image is empty");
         exit(1);
     }
-    // HSV
-    let hsv = source.convert_color(COLOR_BGR2HSV)?;
-    // Gray
-    let gray = source.convert_color(COLOR_BGR2GRAY)?;
-    // Threshold
-    let binary = gray.threshold(0.0, 255.0, THRESH_BINARY_INV | THRESH_OTSU)?;
-    // Contours
-    let mut contours = binary.find_contours(RETR_EXTERNAL, CHAIN_APPROX_SIMPLE)?;
-
-    // Filter
-    let mut filtered = (Vector::new(), Vector::<Mat>::new());
-    for contour in contours {
-        if contour.area()? >= config.contours.min_area {
-            filtered.0.push(contour);
-        } else {
-            filtered.1.push(contour);
-        }
+    // Preprocess
+    let reference = cli
+        .flat_field
+        .as_ref()
+        .map(|path| Mat::read(path, IMREAD_COLOR))
+        .transpose()?;
+    let mask = cli
+        .mask
+        .as_ref()
+        .map(|path| Mat::read(path, IMREAD_GRAYSCALE))
+        .transpose()?;
+    let source = preprocess(
+        &source,
+        &config.preprocess,
+        reference.as_ref(),
+        mask.as_ref(),
+    )?;
+    if config.debug {
+        source.write(cli.path.with_extension("preprocessed.png"))?;
     }
-    let mut filter = source.clone();
-    filter.draw_contours(&filtered.0, GREEN, 1)?;
-    filter.draw_contours(&filtered.1, RED, 1)?;
-    filter.write(cli.path.with_extension("filter.png"))?;
-    contours = filtered.0;
-
-    // Process
-    let mut seeds = Vec::new();
-    let mut contoured = source.clone();
-    for (index, contour) in contours.iter().enumerate() {
-        let perimeter = contour.perimeter(true)?;
-        let area = contour.area()?;
-
-        // Contour
-        let centroid = contour.moments(false)?.centroid();
-        contoured.draw_contour(&contour, RED, 1)?;
-        contoured.draw_circle(centroid, 1, RED, 1)?;
-
-        // Bounding rectangle
-        let bounding_rectangle = contour.bounding_rectangle()?;
-        contoured.draw_rectangle(bounding_rectangle, YELLOW)?;
-        contoured.draw_circle(bounding_rectangle.center(), 1, YELLOW, 1)?;
-
-        // Rotated rectangle
-        let rotated_rectangle = contour.rotated_rectangle()?;
-        contoured.draw_rotated_rectangle(rotated_rectangle, GREEN)?;
-        contoured.draw_circle(rotated_rectangle.center, 1, GREEN, 1)?;
-
-        // Min circumcircle
-        let min_circumcircle = contour.min_circumcircle()?;
-        contoured.draw_circle(min_circumcircle.center, min_circumcircle.radius, BLUE, 1)?;
-        contoured.draw_circle(min_circumcircle.center, 1, BLUE, 1)?;
-
-        // Max incircle
-        let max_incircle = contour.max_incircle()?;
-        contoured.draw_circle(max_incircle.center, max_incircle.radius, CYAN, 1)?;
-        contoured.draw_circle(max_incircle.center, 1, CYAN, 1)?;
-
-        // Incircle
-        let incircle = contour.incircle(centroid)?;
-        contoured.draw_circle(incircle.center, incircle.radius, MAGENTA, 1)?;
-        contoured.draw_circle(incircle.center, 1, MAGENTA, 1)?;
-
-        // Mean colors
-        let colors = {
-            let radius = incircle.radius / 2.0;
-            // RED
-            let contour = {
-                let mut mask = Mat::zeros_size(source.size()?, CV_8UC1)?.to_mat()?;
-                mask.draw_contour(&contour, WHITE, FILLED)?;
-                let hsva = hsv.mean(&mut mask)?;
-                contoured.draw_circle(
-                    bounding_rectangle.tl(),
-                    radius,
-                    hsva_to_bgra(hsva)?,
-                    FILLED,
-                )?;
-                contoured.draw_circle(bounding_rectangle.tl(), radius, RED, 1)?;
-                hsva.into()
-            };
-
-            // CYAN
-            let max_incircle = {
-                let mut mask = Mat::zeros_size(source.size()?, CV_8UC1)?.to_mat()?;
-                mask.draw_circle(max_incircle.center, max_incircle.radius, WHITE, FILLED)?;
-                let hsva = hsv.mean(&mut mask)?;
-                contoured.draw_circle(
-                    bounding_rectangle.tr(),
-                    radius,
-                    hsva_to_bgra(hsva)?,
-                    FILLED,
-                )?;
-                contoured.draw_circle(bounding_rectangle.tr(), radius, CYAN, 1)?;
-                hsva.into()
-            };
-
-            // MAGENTA
-            let incircle = {
-                let mut mask = Mat::zeros_size(source.size()?, CV_8UC1)?.to_mat()?;
-                mask.draw_circle(incircle.center, incircle.radius, WHITE, FILLED)?;
-                let hsva = hsv.mean(&mut mask)?;
-                contoured.draw_circle(
-                    bounding_rectangle.br(),
-                    radius,
-                    hsva_to_bgra(hsva)?,
-                    FILLED,
-                )?;
-                contoured.draw_circle(bounding_rectangle.br(), radius, MAGENTA, 1)?;
-                hsva.into()
-            };
-
-            Colors {
-                contour,
-                max_incircle,
-                incircle,
+    // Calibration
+    let source = match calibrate(&source, &config.calibration)? {
+        Some(calibration) => {
+            println!(
+                "ΔE: mean {:.2}, max {:.2}",
+                calibration.mean, calibration.max,
+            );
+            to_writer_pretty(
+                File::create(cli.path.with_extension("calibration.ron"))?,
+                &calibration,
+                PrettyConfig::new().depth_limit(2),
+            )?;
+            calibration.apply(&source)?
+        }
+        None => source,
+    };
+    // Objects
+    let (mut objects, mut seeds) = match &cli.import {
+        Some(path) => {
+            let objects = import(path, &cli)?;
+            let seeds = seeds::measure(&source, &objects, &config)?;
+            let selection = seeds::select(objects, seeds, &config)?;
+            (selection.objects, selection.seeds)
+        }
+        None => {
+            let analysis = seeds::analyze(&source, &config)?;
+            if let Some(images) = &analysis.debug {
+                images.binary.write(cli.path.with_extension("binary.png"))?;
+                images.filter.write(cli.path.with_extension("filter.png"))?;
+                images
+                    .distance_transform
+                    .write(cli.path.with_extension("distance_transform.png"))?;
             }
+            (analysis.objects, analysis.seeds)
+        }
+    };
+    // Classification
+    if let Some(path) = &cli.train {
+        Model::train(&seeds, &config.classifier)?.save(path)?;
+    }
+    // Annotated classes are kept
+    let classes = classify(&seeds, &config.classifier)?;
+    for ((object, seed), class) in objects.iter_mut().zip(&mut seeds).zip(classes) {
+        if object.class.is_none() && class.is_some() {
+            object.class = class.clone();
+            seed.class = class;
+        }
+    }
+    render(&source, &objects, &config.render)?.write(cli.path.with_extension("contoured.png"))?;
+    if cli.layers {
+        for (name, layer) in layers(&source, &objects, &config.render)? {
+            layer.write(cli.path.with_extension(format!("{name}.png")))?;
+        }
+    }
+    if let Some(svg) = cli.svg {
+        let path = cli.path.with_extension("svg");
+        // Linked relative to the drawing
+        let name = cli.path.file_name().map(Path::new).unwrap_or(&cli.path);
+        let background = match svg {
+            Svg::Linked => Background::Linked(name),
+            Svg::Embedded => Background::Embedded(&source),
         };
-
-        contoured.draw_text(
-            index.to_string(),
-            centroid,
-            FONT_HERSHEY_SIMPLEX,
-            0.5,
-            WHITE,
+        svg::write(
+            BufWriter::new(File::create(path)?),
+            &source,
+            background,
+            &objects,
+            &seeds,
+            &config.render,
         )?;
-        seeds.push(Seed {
-            area,
-            circumcircle_radius: min_circumcircle.radius as _,
-            incircle_radius: max_incircle.radius as _,
-            perimeter,
-            colors,
-        });
     }
-    contoured.write(cli.path.with_extension("contoured.png"))?;
-    // Test
-    let mut mask = Mat::zeros_size(source.size()?, CV_8UC1)?.to_mat()?;
-    mask.draw_contours(&contours, WHITE, FILLED)?;
-    let distance_transform = mask.distance_transform(DIST_L2, DIST_MASK_5)?;
-    distance_transform.write(cli.path.with_extension("distance_transform.png"))?;
-
+    if let Some(format) = cli.export {
+        let name = cli.path.file_name().unwrap_or_default().to_string_lossy();
+        match format {
+            Format::Coco => Coco::new(&name, source.size()?, &objects, cli.scale)?
+                .save(&cli.path.with_extension("coco.json"))?,
+            Format::Geojson => FeatureCollection::new(&objects, cli.scale)?
+                .save(&cli.path.with_extension("geojson"))?,
+        }
+    }
+    if cli.crops {
+        let directory = cli.path.with_extension("crops");
+        create_dir_all(&directory)?;
+        for crop in crops(&source, &objects, &config.crops)? {
+            crop.image
+                .write(directory.join(format!("{}.png", crop.index)))?;
+        }
+        // Values are the indices plus one, `0` is the background
+        labels(source.size()?, &objects)?.write(cli.path.with_extension("labels.png"))?;
+    }
     to_writer_pretty(
         File::create(cli.path.with_extension("ron"))?,
         &seeds,
@@ -195,22 +229,17 @@ fn main() -> Result<()> {
     Ok(())
 }
 
-/// Seed
-#[derive(Clone, Copy, Debug, Default, Deserialize, Serialize)]
-struct Seed {
-    area: f64,
-    circumcircle_radius: f64,
-    incircle_radius: f64,
-    perimeter: f64,
-    colors: Colors,
-}
-
-/// Colors
-#[derive(Clone, Copy, Debug, Default, Deserialize, Serialize)]
-struct Colors {
-    contour: Hsb,
-    max_incircle: Hsb,
-    incircle: Hsb,
+/// Imports the objects from annotations
+fn import(path: &Path, cli: &Cli) -> Result<Vec<Object>> {
+    if path
+        .extension()
+        .is_some_and(|extension| extension == "geojson")
+    {
+        FeatureCollection::load(path)?.objects(cli.scale)
+    } else {
+        let name = cli.path.file_name().unwrap_or_default().to_string_lossy();
+        Coco::load(path)?.objects(&name, cli.scale)
+    }
 }
 
 // fn probabilistic_hough(edges: &Mat) -> Result<()> {
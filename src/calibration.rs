@@ -0,0 +1,401 @@
+use crate::{
+    color::Bgr,
+    config::{self, Chart},
+    object::Circle,
+    statistics::{statistics, Region},
+};
+use anyhow::{bail, ensure, Result};
+use opencv::{
+    core::{Mat, Point, Point2f, Rect, Size2f, Vector, BORDER_CONSTANT, CV_8U, CV_8UC3},
+    imgproc::{
+        approx_poly_dp, arc_length, canny, contour_area, cvt_color_def, dilate, find_contours,
+        is_contour_convex, min_area_rect, morphology_default_border_value, CHAIN_APPROX_SIMPLE,
+        COLOR_BGR2GRAY, RETR_LIST,
+    },
+    prelude::*,
+};
+use palette::{color_difference::Ciede2000, LinSrgb};
+use serde::{Deserialize, Serialize};
+
+/// sRGB of the patches of the 24-patch color checker, row by row from dark
+/// skin to black
+pub const REFERENCE: [[u8; 3]; 24] = [
+    [115, 82, 68],
+    [194, 150, 130],
+    [98, 122, 157],
+    [87, 108, 67],
+    [133, 128, 177],
+    [103, 189, 170],
+    [214, 126, 44],
+    [80, 91, 166],
+    [193, 90, 99],
+    [94, 60, 108],
+    [157, 188, 64],
+    [224, 163, 46],
+    [56, 61, 150],
+    [70, 148, 73],
+    [175, 54, 60],
+    [231, 199, 31],
+    [187, 86, 149],
+    [8, 133, 161],
+    [243, 243, 242],
+    [200, 200, 200],
+    [160, 160, 160],
+    [122, 122, 121],
+    [85, 85, 85],
+    [52, 52, 52],
+];
+
+const COLUMNS: usize = 6;
+const ROWS: usize = 4;
+/// Least number of patches found by the detection
+const PATCHES: usize = 12;
+
+/// Calibration
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+pub struct Calibration {
+    /// Corners of the chart: dark skin, bluish green, black and white patch
+    pub corners: [[f32; 2]; 4],
+    /// Color correction matrix, linear RGB with an offset to linear RGB
+    pub matrix: [[f64; 3]; 4],
+    pub patches: Vec<Patch>,
+    /// Mean CIEDE2000 residual of the patches
+    pub mean: f64,
+    /// Max CIEDE2000 residual of the patches
+    pub max: f64,
+}
+
+/// Patch
+#[derive(Clone, Copy, Debug, Default, Deserialize, Serialize)]
+pub struct Patch {
+    pub index: usize,
+    /// Median sRGB in the image
+    pub measured: [u8; 3],
+    /// sRGB after the correction
+    pub corrected: [u8; 3],
+    pub reference: [u8; 3],
+    /// CIEDE2000 between the corrected and the reference colors
+    pub delta_e: f64,
+}
+
+impl Calibration {
+    /// Applies the color correction matrix to an 8-bit BGR image
+    pub fn apply(&self, source: &Mat) -> Result<Mat> {
+        ensure!(source.typ() == CV_8UC3, "image type is not CV_8UC3");
+        let decode = (0..=255u8)
+            .map(|value| Bgr::from([value; 3]).linear().red)
+            .collect::<Vec<_>>();
+        // Linear values in 1/4095 steps to 8-bit sRGB
+        let encode = (0..=4095)
+            .map(|value| {
+                let linear = value as f64 / 4095.0;
+                Bgr::from_linear(LinSrgb::new(linear, linear, linear)).round()[0]
+            })
+            .collect::<Vec<_>>();
+        let mut target = source.try_clone()?;
+        for pixel in target.data_bytes_mut()?.chunks_exact_mut(3) {
+            let rgb = [
+                decode[pixel[2] as usize],
+                decode[pixel[1] as usize],
+                decode[pixel[0] as usize],
+            ];
+            let corrected = self.correct(rgb);
+            for (channel, value) in [2, 1, 0].into_iter().zip(corrected) {
+                pixel[channel] = encode[(value.clamp(0.0, 1.0) * 4095.0).round() as usize];
+            }
+        }
+        Ok(target)
+    }
+
+    /// Corrects a linear RGB color
+    fn correct(&self, [red, green, blue]: [f64; 3]) -> [f64; 3] {
+        let m = &self.matrix;
+        [0, 1, 2].map(|channel| {
+            red * m[0][channel] + green * m[1][channel] + blue * m[2][channel] + m[3][channel]
+        })
+    }
+}
+
+/// Finds the chart and fits the color correction matrix, `None` without a
+/// configured chart
+pub fn calibrate(source: &Mat, config: &config::Calibration) -> Result<Option<Calibration>> {
+    let Some(chart) = config.chart else {
+        return Ok(None);
+    };
+    let corners = match chart {
+        Chart::Detect => match detect(source)? {
+            Some(corners) => corners,
+            None => bail!("color checker is not found"),
+        },
+        Chart::Roi([x, y, width, height]) => {
+            let rectangle = Rect::new(x, y, width, height);
+            [
+                rectangle.tl(),
+                Point::new(x + width, y),
+                rectangle.br(),
+                Point::new(x, y + height),
+            ]
+            .map(|point| Point2f::new(point.x as _, point.y as _))
+        }
+    };
+    // Long side first, then both 180° orientations
+    let corners = if distance(corners[0], corners[1]) < distance(corners[0], corners[3]) {
+        [corners[3], corners[0], corners[1], corners[2]]
+    } else {
+        corners
+    };
+    let mut best: Option<Calibration> = None;
+    for corners in [corners, [corners[2], corners[3], corners[0], corners[1]]] {
+        let calibration = fit(source, corners, config.patch)?;
+        if !best
+            .as_ref()
+            .is_some_and(|best| best.mean <= calibration.mean)
+        {
+            best = Some(calibration);
+        }
+    }
+    Ok(best)
+}
+
+/// Samples the patches and fits the matrix by least squares
+fn fit(source: &Mat, corners: [Point2f; 4], patch: f64) -> Result<Calibration> {
+    let side = distance(corners[0], corners[1]) / COLUMNS as f64;
+    let mut measured = Vec::with_capacity(REFERENCE.len());
+    for (index, center) in centers(corners).into_iter().enumerate() {
+        let region = Region::Circle(Circle {
+            center,
+            radius: (side * patch / 2.0).max(1.0) as _,
+        });
+        let statistics = statistics(source, region, &[0, 1, 2])?;
+        ensure!(
+            statistics[0].count != 0,
+            "patch {index} is out of the image"
+        );
+        measured.push(Bgr::new(
+            statistics[0].median,
+            statistics[1].median,
+            statistics[2].median,
+        ));
+    }
+    let reference = REFERENCE.map(|[red, green, blue]| Bgr::from([blue, green, red]));
+    // Normal equations
+    let mut ata = [[0.0; 4]; 4];
+    let mut atb = [[0.0; 3]; 4];
+    for (measured, reference) in measured.iter().zip(&reference) {
+        let linear = measured.linear();
+        let row = [linear.red, linear.green, linear.blue, 1.0];
+        let linear = reference.linear();
+        let target = [linear.red, linear.green, linear.blue];
+        for ((ata, atb), x) in ata.iter_mut().zip(&mut atb).zip(row) {
+            for (ata, y) in ata.iter_mut().zip(row) {
+                *ata += x * y;
+            }
+            for (atb, y) in atb.iter_mut().zip(target) {
+                *atb += x * y;
+            }
+        }
+    }
+    let Some(matrix) = solve(ata, atb) else {
+        bail!("color correction matrix is singular");
+    };
+    let mut calibration = Calibration {
+        corners: corners.map(|corner| [corner.x, corner.y]),
+        matrix,
+        ..Default::default()
+    };
+    for (index, (measured, reference)) in measured.iter().zip(&reference).enumerate() {
+        let linear = measured.linear();
+        let [red, green, blue] = calibration.correct([linear.red, linear.green, linear.blue]);
+        let corrected = Bgr::from_linear(LinSrgb::new(
+            red.clamp(0.0, 1.0),
+            green.clamp(0.0, 1.0),
+            blue.clamp(0.0, 1.0),
+        ));
+        let delta_e = corrected.lab().difference(reference.lab());
+        let rgb = |bgr: &Bgr| {
+            let [blue, green, red] = bgr.round();
+            [red, green, blue]
+        };
+        calibration.patches.push(Patch {
+            index,
+            measured: rgb(measured),
+            corrected: rgb(&corrected),
+            reference: rgb(reference),
+            delta_e,
+        });
+    }
+    let residuals = calibration.patches.iter().map(|patch| patch.delta_e);
+    calibration.mean = residuals.clone().sum::<f64>() / REFERENCE.len() as f64;
+    calibration.max = residuals.fold(0.0, f64::max);
+    Ok(calibration)
+}
+
+/// Solves the normal equations by Gauss-Jordan elimination
+fn solve(mut a: [[f64; 4]; 4], mut b: [[f64; 3]; 4]) -> Option<[[f64; 3]; 4]> {
+    for column in 0..4 {
+        let pivot =
+            (column..4).max_by(|&i, &j| a[i][column].abs().total_cmp(&a[j][column].abs()))?;
+        if a[pivot][column].abs() < 1e-12 {
+            return None;
+        }
+        a.swap(column, pivot);
+        b.swap(column, pivot);
+        let (pivot_a, pivot_b) = (a[column], b[column]);
+        for (row, (a, b)) in a.iter_mut().zip(&mut b).enumerate() {
+            if row == column {
+                continue;
+            }
+            let factor = a[column] / pivot_a[column];
+            a.iter_mut()
+                .zip(pivot_a)
+                .for_each(|(value, pivot)| *value -= factor * pivot);
+            b.iter_mut()
+                .zip(pivot_b)
+                .for_each(|(value, pivot)| *value -= factor * pivot);
+        }
+    }
+    for (row, b) in b.iter_mut().enumerate() {
+        let pivot = a[row][row];
+        b.iter_mut().for_each(|value| *value /= pivot);
+    }
+    Some(b)
+}
+
+/// Centers of the patches, row by row
+fn centers([a, b, c, d]: [Point2f; 4]) -> Vec<Point2f> {
+    let mut centers = Vec::with_capacity(COLUMNS * ROWS);
+    for row in 0..ROWS {
+        let v = (row as f32 + 0.5) / ROWS as f32;
+        for column in 0..COLUMNS {
+            let u = (column as f32 + 0.5) / COLUMNS as f32;
+            // Bilinear interpolation of the corners
+            let top = a + (b - a) * u;
+            let bottom = d + (c - d) * u;
+            centers.push(top + (bottom - top) * v);
+        }
+    }
+    centers
+}
+
+/// Finds the chart as a grid of similar convex quadrilaterals
+///
+/// The corner patches have to be found, the corners are clockwise from an
+/// arbitrary one.
+fn detect(source: &Mat) -> Result<Option<[Point2f; 4]>> {
+    let mut gray = Mat::default();
+    cvt_color_def(source, &mut gray, COLOR_BGR2GRAY)?;
+    let mut edges = Mat::default();
+    canny(&gray, &mut edges, 20.0, 60.0, 3, false)?;
+    let mut dilated = Mat::default();
+    dilate(
+        &edges,
+        &mut dilated,
+        &Mat::ones(3, 3, CV_8U)?,
+        Point::new(-1, -1),
+        1,
+        BORDER_CONSTANT,
+        morphology_default_border_value()?,
+    )?;
+    let mut contours = Vector::<Vector<Point>>::new();
+    find_contours(
+        &dilated,
+        &mut contours,
+        RETR_LIST,
+        CHAIN_APPROX_SIMPLE,
+        Point::default(),
+    )?;
+    // Square candidates
+    let mut squares = Vec::new();
+    for contour in &contours {
+        let mut polygon = Vector::<Point>::new();
+        approx_poly_dp(
+            &contour,
+            &mut polygon,
+            0.1 * arc_length(&contour, true)?,
+            true,
+        )?;
+        let area = contour_area(&polygon, false)?;
+        if polygon.len() != 4 || area < 100.0 || !is_contour_convex(&polygon)? {
+            continue;
+        }
+        let rectangle = min_area_rect(&polygon)?;
+        let Size2f { width, height } = rectangle.size;
+        let aspect = width.max(height) / width.min(height).max(f32::EPSILON);
+        if aspect < 1.4 {
+            squares.push((rectangle.center, area));
+        }
+    }
+    if squares.len() < PATCHES {
+        return Ok(None);
+    }
+    // Similar areas, outer and inner contours of a patch merged
+    let mut areas = squares.iter().map(|&(_, area)| area).collect::<Vec<_>>();
+    areas.sort_by(f64::total_cmp);
+    let median = areas[areas.len() / 2];
+    squares.retain(|&(_, area)| (0.5 * median..=2.0 * median).contains(&area));
+    squares.sort_by(|a, b| b.1.total_cmp(&a.1));
+    let mut patches = Vector::<Point2f>::new();
+    for (center, area) in squares {
+        if patches
+            .iter()
+            .all(|patch| distance(patch, center) > area.sqrt() / 2.0)
+        {
+            patches.push(center);
+        }
+    }
+    if patches.len() < PATCHES {
+        return Ok(None);
+    }
+    // Rectangle of the centers grown by half a patch on each side
+    let rectangle = min_area_rect(&patches)?;
+    let [p0, p1, p2, _] = {
+        let mut points = [Point2f::default(); 4];
+        rectangle.points(&mut points)?;
+        points
+    };
+    let (mut u, mut v) = (p2 - p1, p0 - p1);
+    if u.norm() < v.norm() {
+        (u, v) = (v, u);
+    }
+    let u = u * (COLUMNS as f32 / (COLUMNS - 1) as f32 / 2.0);
+    let mut v = v * (ROWS as f32 / (ROWS - 1) as f32 / 2.0);
+    // Clockwise on the screen
+    if u.x * v.y - u.y * v.x < 0.0 {
+        v = v * -1.0;
+    }
+    let center = rectangle.center;
+    Ok(Some([
+        center - u - v,
+        center + u - v,
+        center + u + v,
+        center - u + v,
+    ]))
+}
+
+fn distance(a: Point2f, b: Point2f) -> f64 {
+    ((a.x - b.x) as f64).hypot((a.y - b.y) as f64)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn identity() {
+        let matrix = [[1.0, 0.0, 0.0], [0.0, 1.0, 0.0], [0.0, 0.0, 1.0], [0.0; 3]];
+        let a = [
+            [4.0, 1.0, 0.0, 2.0],
+            [1.0, 3.0, 1.0, 1.0],
+            [0.0, 1.0, 2.0, 1.0],
+            [2.0, 1.0, 1.0, 5.0],
+        ];
+        // Right-hand side of the identity matrix is the first 3 columns
+        let b = a.map(|row| [row[0], row[1], row[2]]);
+        let solution = solve(a, b).unwrap();
+        for (row, expected) in solution.iter().zip(matrix) {
+            for (value, expected) in row.iter().zip(expected) {
+                assert!((value - expected).abs() < 1e-9);
+            }
+        }
+    }
+}
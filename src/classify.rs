@@ -0,0 +1,244 @@
+use crate::{
+    config::{Classifier, Method},
+    filter::Measured,
+    seeds::{Seed, VARIABLES},
+};
+use anyhow::{bail, ensure, Context, Result};
+use ron::ser::{to_writer_pretty, PrettyConfig};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::BTreeMap,
+    fs::File,
+    io::{BufReader, BufWriter},
+    path::Path,
+};
+
+/// Classes of the seeds, `None` for the seeds no rule matches and the model
+/// does not classify
+pub fn classify(seeds: &[Seed], config: &Classifier) -> Result<Vec<Option<String>>> {
+    let model = config.model.as_deref().map(Model::load).transpose()?;
+    let mut classes = Vec::with_capacity(seeds.len());
+    for seed in seeds {
+        let variables = |name: &str| seed.variable(name);
+        let mut class = None;
+        for rule in &config.rules {
+            if rule
+                .expression
+                .test(&variables)
+                .with_context(|| format!("rule of class `{}`", rule.class))?
+            {
+                class = Some(rule.class.clone());
+                break;
+            }
+        }
+        if class.is_none() {
+            if let Some(model) = &model {
+                class = Some(model.predict(seed)?);
+            }
+        }
+        classes.push(class);
+    }
+    Ok(classes)
+}
+
+/// Trained model
+///
+/// The features are standardized by the mean and the standard deviation of
+/// the training samples.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct Model {
+    pub method: Method,
+    pub features: Vec<String>,
+    /// Mean and standard deviation of each feature
+    pub scales: Vec<[f64; 2]>,
+    pub samples: Vec<Sample>,
+}
+
+/// Standardized training sample
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct Sample {
+    pub class: String,
+    pub features: Vec<f64>,
+}
+
+impl Model {
+    /// Trains the model on the classified seeds, the unclassified ones are
+    /// skipped
+    pub fn train(seeds: &[Seed], config: &Classifier) -> Result<Self> {
+        if let Method::Knn(k) = config.method {
+            ensure!(k > 0, "k of the k nearest neighbors is zero");
+        }
+        ensure!(!config.features.is_empty(), "no features");
+        let mut samples = Vec::new();
+        for seed in seeds {
+            let Some(class) = &seed.class else {
+                continue;
+            };
+            samples.push(Sample {
+                class: class.clone(),
+                features: features(seed, &config.features)?,
+            });
+        }
+        ensure!(!samples.is_empty(), "no classified seeds");
+        let count = samples.len() as f64;
+        let scales = (0..config.features.len())
+            .map(|feature| {
+                let values = || samples.iter().map(|sample| sample.features[feature]);
+                let mean = values().sum::<f64>() / count;
+                let variance = values().map(|value| (value - mean).powi(2)).sum::<f64>() / count;
+                // Constant features keep their unit
+                let std = if variance > 0.0 { variance.sqrt() } else { 1.0 };
+                [mean, std]
+            })
+            .collect::<Vec<_>>();
+        for sample in &mut samples {
+            standardize(&mut sample.features, &scales);
+        }
+        Ok(Self {
+            method: config.method,
+            features: config.features.clone(),
+            scales,
+            samples,
+        })
+    }
+
+    pub fn load(path: &Path) -> Result<Self> {
+        let file = File::open(path).with_context(|| format!("model {}", path.display()))?;
+        Ok(ron::de::from_reader(BufReader::new(file))?)
+    }
+
+    pub fn save(&self, path: &Path) -> Result<()> {
+        to_writer_pretty(
+            BufWriter::new(File::create(path)?),
+            self,
+            PrettyConfig::new().depth_limit(2),
+        )?;
+        Ok(())
+    }
+
+    /// Class of the seed
+    pub fn predict(&self, seed: &Seed) -> Result<String> {
+        ensure!(!self.samples.is_empty(), "model has no samples");
+        let mut features = features(seed, &self.features)?;
+        standardize(&mut features, &self.scales);
+        let distance = |other: &[f64]| {
+            features
+                .iter()
+                .zip(other)
+                .map(|(a, b)| (a - b).powi(2))
+                .sum::<f64>()
+        };
+        // Ordered by class for ties
+        let mut classes = BTreeMap::<&str, Vec<&Sample>>::new();
+        for sample in &self.samples {
+            classes.entry(&sample.class).or_default().push(sample);
+        }
+        let class = match self.method {
+            Method::NearestCentroid => classes
+                .iter()
+                .map(|(class, samples)| {
+                    let centroid = (0..features.len())
+                        .map(|feature| {
+                            samples
+                                .iter()
+                                .map(|sample| sample.features[feature])
+                                .sum::<f64>()
+                                / samples.len() as f64
+                        })
+                        .collect::<Vec<_>>();
+                    (*class, distance(&centroid))
+                })
+                .min_by(|a, b| a.1.total_cmp(&b.1))
+                .map(|(class, _)| class),
+            Method::Knn(k) => {
+                let mut neighbors = self
+                    .samples
+                    .iter()
+                    .map(|sample| (sample.class.as_str(), distance(&sample.features)))
+                    .collect::<Vec<_>>();
+                neighbors.sort_by(|a, b| a.1.total_cmp(&b.1));
+                // Majority vote, ties to the nearest class
+                let mut votes = BTreeMap::<&str, (usize, usize)>::new();
+                for (rank, &(class, _)) in neighbors.iter().take(k).enumerate() {
+                    votes.entry(class).or_insert((0, rank)).0 += 1;
+                }
+                votes
+                    .into_iter()
+                    .max_by(|a, b| a.1 .0.cmp(&b.1 .0).then(b.1 .1.cmp(&a.1 .1)))
+                    .map(|(class, _)| class)
+            }
+        };
+        Ok(class.unwrap_or_default().to_owned())
+    }
+}
+
+/// Features of the seed
+fn features(seed: &Seed, names: &[String]) -> Result<Vec<f64>> {
+    names
+        .iter()
+        .map(|name| match seed.variable(name) {
+            Some(value) => Ok(value),
+            None if VARIABLES.contains(&name.as_str()) => {
+                bail!("feature `{name}` is not measured")
+            }
+            None => bail!("unknown feature `{name}`, expected one of {VARIABLES:?}"),
+        })
+        .collect()
+}
+
+fn standardize(features: &mut [f64], scales: &[[f64; 2]]) {
+    for (feature, [mean, std]) in features.iter_mut().zip(scales) {
+        *feature = (*feature - mean) / std;
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn seed(area: f64, class: Option<&str>) -> Seed {
+        Seed {
+            area,
+            perimeter: 100.0,
+            class: class.map(ToOwned::to_owned),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn model() -> Result<()> {
+        let seeds = [
+            seed(100.0, Some("small")),
+            seed(120.0, Some("small")),
+            seed(900.0, Some("large")),
+            seed(1000.0, Some("large")),
+            seed(1100.0, None),
+        ];
+        for method in [Method::NearestCentroid, Method::Knn(3)] {
+            let config = Classifier {
+                method,
+                features: vec!["area".to_owned()],
+                ..Default::default()
+            };
+            let model = Model::train(&seeds, &config)?;
+            assert_eq!(model.samples.len(), 4);
+            assert_eq!(model.predict(&seed(200.0, None))?, "small");
+            assert_eq!(model.predict(&seed(800.0, None))?, "large");
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn rules() -> Result<()> {
+        let config = Classifier {
+            rules: vec![crate::config::Rule {
+                class: "damaged".to_owned(),
+                expression: "area > 500 && circularity < 0.8".parse()?,
+            }],
+            ..Default::default()
+        };
+        let classes = classify(&[seed(100.0, None), seed(600.0, None)], &config)?;
+        assert_eq!(classes, [None, Some("damaged".to_owned())]);
+        Ok(())
+    }
+}
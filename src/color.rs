@@ -0,0 +1,244 @@
+//! Color conversions
+//!
+//! Ranges:
+//! - [`Hsv`]: OpenCV scaled HSV of 8-bit images, hue in `0..180` (half
+//!   degrees), saturation and value in `0..=255`,
+//! - [`Bgr`]: OpenCV channel order, each channel in `0..=255`,
+//! - [`Srgb`] and [`LinSrgb`]: each channel in `0..=1`,
+//! - [`Lab`]: lightness in `0..=100`, a and b in about `-128..=127`,
+//! - [`Lch`]: lightness in `0..=100`, chroma from `0`, hue in degrees
+//!   `0..360`.
+//!
+//! Conversions are computed in `f64`, without the quantization of OpenCV's
+//! 8-bit `cvt_color`, so they may differ from it by one level.
+
+use egui::Color32;
+use opencv::core::{Scalar, VecN};
+use palette::{encoding, white_point::D65, FromColor, IntoColor, Lab, Lch, LinSrgb, Srgb};
+use serde::{Deserialize, Serialize};
+
+/// OpenCV scaled HSV
+#[derive(Clone, Copy, Debug, Default, Deserialize, PartialEq, Serialize)]
+pub struct Hsv {
+    /// `0..180`
+    pub hue: f64,
+    /// `0..=255`
+    pub saturation: f64,
+    /// `0..=255`
+    pub value: f64,
+}
+
+impl Hsv {
+    pub const fn new(hue: f64, saturation: f64, value: f64) -> Self {
+        Self {
+            hue,
+            saturation,
+            value,
+        }
+    }
+
+    pub fn from_srgb(srgb: Srgb<f64>) -> Self {
+        let hsv = palette::Hsv::<encoding::Srgb, f64>::from_color(srgb);
+        Self {
+            hue: hsv.hue.into_positive_degrees() / 2.0 % 180.0,
+            saturation: hsv.saturation * 255.0,
+            value: hsv.value * 255.0,
+        }
+    }
+
+    pub fn srgb(&self) -> Srgb<f64> {
+        Srgb::from_color(palette::Hsv::new_srgb(
+            self.hue * 2.0,
+            self.saturation / 255.0,
+            self.value / 255.0,
+        ))
+    }
+
+    pub fn bgr(&self) -> Bgr {
+        Bgr::from_srgb(self.srgb())
+    }
+}
+
+impl From<VecN<f64, 3>> for Hsv {
+    fn from(VecN([hue, saturation, value]): VecN<f64, 3>) -> Self {
+        Self::new(hue, saturation, value)
+    }
+}
+
+impl From<VecN<f64, 4>> for Hsv {
+    fn from(VecN([hue, saturation, value, _]): VecN<f64, 4>) -> Self {
+        Self::new(hue, saturation, value)
+    }
+}
+
+/// OpenCV ordered RGB
+#[derive(Clone, Copy, Debug, Default, Deserialize, PartialEq, Serialize)]
+pub struct Bgr {
+    /// `0..=255`
+    pub blue: f64,
+    /// `0..=255`
+    pub green: f64,
+    /// `0..=255`
+    pub red: f64,
+}
+
+impl Bgr {
+    pub const fn new(blue: f64, green: f64, red: f64) -> Self {
+        Self { blue, green, red }
+    }
+
+    pub fn from_srgb(srgb: Srgb<f64>) -> Self {
+        Self::new(srgb.blue * 255.0, srgb.green * 255.0, srgb.red * 255.0)
+    }
+
+    pub fn from_linear(linear: LinSrgb<f64>) -> Self {
+        Self::from_srgb(Srgb::from_linear(linear))
+    }
+
+    pub fn from_lab(lab: Lab<D65, f64>) -> Self {
+        Self::from_linear(lab.into_color())
+    }
+
+    pub fn from_lch(lch: Lch<D65, f64>) -> Self {
+        Self::from_lab(lch.into_color())
+    }
+
+    pub fn srgb(&self) -> Srgb<f64> {
+        Srgb::new(self.red / 255.0, self.green / 255.0, self.blue / 255.0)
+    }
+
+    pub fn linear(&self) -> LinSrgb<f64> {
+        self.srgb().into_linear()
+    }
+
+    pub fn lab(&self) -> Lab<D65, f64> {
+        self.linear().into_color()
+    }
+
+    pub fn lch(&self) -> Lch<D65, f64> {
+        self.lab().into_color()
+    }
+
+    pub fn hsv(&self) -> Hsv {
+        Hsv::from_srgb(self.srgb())
+    }
+
+    /// Rounded and clamped to 8 bits
+    pub fn round(&self) -> [u8; 3] {
+        [self.blue, self.green, self.red].map(|channel| channel.round().clamp(0.0, 255.0) as _)
+    }
+
+    pub fn scalar(&self, alpha: f64) -> Scalar {
+        Scalar::new(self.blue, self.green, self.red, alpha)
+    }
+
+    pub fn color32(&self) -> Color32 {
+        let [blue, green, red] = self.round();
+        Color32::from_rgb(red, green, blue)
+    }
+}
+
+impl From<VecN<f64, 3>> for Bgr {
+    fn from(VecN([blue, green, red]): VecN<f64, 3>) -> Self {
+        Self::new(blue, green, red)
+    }
+}
+
+impl From<VecN<f64, 4>> for Bgr {
+    fn from(VecN([blue, green, red, _]): VecN<f64, 4>) -> Self {
+        Self::new(blue, green, red)
+    }
+}
+
+impl From<[u8; 3]> for Bgr {
+    fn from([blue, green, red]: [u8; 3]) -> Self {
+        Self::new(blue as _, green as _, red as _)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use proptest::prelude::*;
+
+    const EPSILON: f64 = 1e-6;
+
+    fn bgr() -> impl Strategy<Value = Bgr> {
+        (0.0..=255.0, 0.0..=255.0, 0.0..=255.0)
+            .prop_map(|(blue, green, red)| Bgr::new(blue, green, red))
+    }
+
+    fn assert_close(left: Bgr, right: Bgr) {
+        for (left, right) in [
+            (left.blue, right.blue),
+            (left.green, right.green),
+            (left.red, right.red),
+        ] {
+            assert!((left - right).abs() < EPSILON, "{left} != {right}");
+        }
+    }
+
+    proptest! {
+        #[test]
+        fn hsv(bgr in bgr()) {
+            let hsv = bgr.hsv();
+            prop_assert!((0.0..180.0).contains(&hsv.hue));
+            prop_assert!((0.0..=255.0).contains(&hsv.saturation));
+            prop_assert!((0.0..=255.0).contains(&hsv.value));
+            assert_close(hsv.bgr(), bgr);
+        }
+
+        #[test]
+        fn hsv_u8(bgr in any::<[u8; 3]>()) {
+            prop_assert_eq!(Bgr::from(bgr).hsv().bgr().round(), bgr);
+        }
+
+        #[test]
+        fn hsv_hue(hue in 0.0..180.0, saturation in 1.0..=255.0, value in 1.0..=255.0) {
+            let hsv = Hsv::new(hue, saturation, value).bgr().hsv();
+            let difference = (hsv.hue - hue).abs();
+            prop_assert!(difference.min(180.0 - difference) < EPSILON);
+            prop_assert!((hsv.saturation - saturation).abs() < EPSILON);
+            prop_assert!((hsv.value - value).abs() < EPSILON);
+        }
+
+        #[test]
+        fn linear(bgr in bgr()) {
+            assert_close(Bgr::from_linear(bgr.linear()), bgr);
+        }
+
+        #[test]
+        fn lab(bgr in bgr()) {
+            let lab = bgr.lab();
+            prop_assert!((-EPSILON..=100.0 + EPSILON).contains(&lab.l));
+            assert_close(Bgr::from_lab(lab), bgr);
+        }
+
+        #[test]
+        fn lch(bgr in bgr()) {
+            let lch = bgr.lch();
+            prop_assert!(lch.chroma >= 0.0);
+            assert_close(Bgr::from_lch(lch), bgr);
+        }
+    }
+
+    #[test]
+    fn opencv() {
+        // Pure colors of `cvt_color` with `COLOR_BGR2HSV`
+        for (bgr, hsv) in [
+            ([0, 0, 255], [0.0, 255.0, 255.0]),
+            ([0, 255, 0], [60.0, 255.0, 255.0]),
+            ([255, 0, 0], [120.0, 255.0, 255.0]),
+            ([0, 255, 255], [30.0, 255.0, 255.0]),
+            ([128, 128, 128], [0.0, 0.0, 128.0]),
+        ] {
+            let Hsv {
+                hue,
+                saturation,
+                value,
+            } = Bgr::from(bgr).hsv();
+            assert_eq!([hue, saturation, value].map(f64::round), hsv);
+            assert_eq!(Hsv::new(hsv[0], hsv[1], hsv[2]).bgr().round(), bgr);
+        }
+    }
+}
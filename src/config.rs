@@ -1,22 +1,143 @@
+pub use self::algae::{Algae, Criterion, Hough, Scale, Segmentation, Selection};
+pub use self::calibration::{Calibration, Chart};
+pub use self::classifier::{Classifier, Method, Rule};
+pub use self::crops::Crops;
+pub use self::filter::{Column, Filter};
+pub use self::morphology::{Blur, Dilation};
+pub use self::preprocess::{Background, Clahe, Inpaint, Preprocess};
+pub use self::render::{Label, Layer, Render};
+pub use self::texture::Texture;
+
 use self::{contours::Contours, kmeans::KMeans, threshold::Threshold};
-use anyhow::Result;
+use crate::{
+    expression::{Expression, CONSTANTS},
+    pipeline::Pipeline,
+};
+use anyhow::{bail, ensure, Result};
 use ron::de::from_reader;
 use serde::{Deserialize, Serialize};
 use std::{fs::File, path::Path};
 
 /// Config
-#[derive(Clone, Copy, Debug, Default, Deserialize, Serialize)]
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
 pub struct Config {
+    #[serde(default)]
+    pub preprocess: Preprocess,
+    #[serde(default)]
+    pub calibration: Calibration,
     pub kmeans: KMeans,
+    #[serde(default)]
+    pub blur: Blur,
     pub threshold: Threshold,
+    #[serde(default)]
+    pub dilation: Dilation,
     pub contours: Contours,
+    #[serde(default)]
+    pub algae: Algae,
+    #[serde(default)]
+    pub render: Render,
+    #[serde(default)]
+    pub texture: Texture,
+    #[serde(default)]
+    pub crops: Crops,
+    #[serde(default)]
+    pub filter: Filter,
+    #[serde(default)]
+    pub classifier: Classifier,
+    /// Keeps the intermediate images
+    #[serde(default)]
+    pub debug: bool,
 }
 
 impl Config {
+    /// Loads the config, the expressions are parsed but their variables are
+    /// checked by [`Config::validate`]
     pub fn new(path: &Path) -> Result<Self> {
         let file = File::open(path)?;
         Ok(from_reader(file)?)
     }
+
+    /// Checks the names of the columns and the variables of the expressions
+    /// against the measurements of the pipeline
+    ///
+    /// A column can use the measurements and the columns before it, the
+    /// filter and the rules can use all the columns. The rules and the
+    /// features are only checked for the seeds, the only pipeline
+    /// that classifies.
+    pub fn validate(&self, pipeline: Pipeline) -> Result<()> {
+        let measurements = match pipeline {
+            Pipeline::Seeds => crate::seeds::variables(self),
+            Pipeline::Algae => crate::algae::VARIABLES.to_vec(),
+        };
+        let measurement = |name: &str| measurements.contains(&name);
+        let check = |expression: &Expression, columns: &[Column], context: &str| -> Result<()> {
+            for name in expression.variables() {
+                if measurement(name) || columns.iter().any(|column| column.name == name) {
+                    continue;
+                }
+                if crate::seeds::TEXTURE.contains(&name) {
+                    ensure!(
+                        pipeline == Pipeline::Algae,
+                        "{context}: `{name}` in `{expression}` is a texture measurement, set \
+                         `texture.enabled`",
+                    );
+                    bail!("{context}: `{name}` in `{expression}` is not measured for the algae");
+                }
+                bail!(
+                    "{context}: unknown variable `{name}` in `{expression}`, expected a column or \
+                     one of {measurements:?}",
+                );
+            }
+            Ok(())
+        };
+        let columns = &self.filter.columns;
+        for (index, column) in columns.iter().enumerate() {
+            let context = format!("column `{}`", column.name);
+            let mut characters = column.name.chars();
+            if !characters
+                .next()
+                .is_some_and(|character| character.is_alphabetic() || character == '_')
+                || !characters.all(|character| character.is_alphanumeric() || character == '_')
+            {
+                bail!("{context}: name is not an identifier");
+            }
+            ensure!(
+                !measurement(&column.name),
+                "{context}: name is a measurement"
+            );
+            ensure!(
+                column.name != "in" && CONSTANTS.iter().all(|(name, _)| *name != column.name),
+                "{context}: name is reserved",
+            );
+            ensure!(
+                !columns[..index]
+                    .iter()
+                    .any(|other| other.name == column.name),
+                "{context}: name is not unique",
+            );
+            check(&column.expression, &columns[..index], &context)?;
+        }
+        if let Some(keep) = &self.filter.keep {
+            check(keep, columns, "filter")?;
+        }
+        if pipeline == Pipeline::Seeds {
+            for rule in &self.classifier.rules {
+                check(
+                    &rule.expression,
+                    columns,
+                    &format!("rule of class `{}`", rule.class),
+                )?;
+            }
+            for name in &self.classifier.features {
+                ensure!(
+                    measurement(name.as_str()) || columns.iter().any(|column| column.name == *name),
+                    "classifier: unknown feature `{name}`, expected a column or one of \
+                     {measurements:?}",
+                );
+            }
+        }
+        Ok(())
+    }
 }
 
 mod kmeans {
@@ -43,6 +164,111 @@ mod kmeans {
     }
 }
 
+mod calibration {
+    use serde::{Deserialize, Serialize};
+
+    /// Color calibration against a 24-patch color checker
+    #[derive(Clone, Copy, Debug, Deserialize, Serialize)]
+    pub struct Calibration {
+        /// `None` turns it off
+        pub chart: Option<Chart>,
+        /// Sampled fraction of the patch side
+        pub patch: f64,
+    }
+
+    impl Default for Calibration {
+        fn default() -> Self {
+            Self {
+                chart: None,
+                patch: 0.5,
+            }
+        }
+    }
+
+    /// Location of the chart
+    #[derive(Clone, Copy, Debug, Deserialize, Serialize)]
+    pub enum Chart {
+        Detect,
+        /// `[x, y, width, height]` with the dark skin patch in the top left
+        /// corner of the long side
+        Roi([i32; 4]),
+    }
+}
+
+mod classifier {
+    use crate::expression::Expression;
+    use serde::{Deserialize, Serialize};
+    use std::path::PathBuf;
+
+    /// Classifier of the seeds
+    ///
+    /// The rules are tested in order and the first match wins, the model
+    /// classifies the seeds no rule matches.
+    #[derive(Clone, Debug, Deserialize, Serialize)]
+    pub struct Classifier {
+        pub rules: Vec<Rule>,
+        /// Trained model file
+        pub model: Option<PathBuf>,
+        /// Method of the trained model
+        pub method: Method,
+        /// Measurements used as features by the trained model
+        pub features: Vec<String>,
+    }
+
+    impl Default for Classifier {
+        fn default() -> Self {
+            Self {
+                rules: Vec::new(),
+                model: None,
+                method: Method::NearestCentroid,
+                features: ["area", "circularity", "hue", "saturation", "brightness"]
+                    .map(ToOwned::to_owned)
+                    .to_vec(),
+            }
+        }
+    }
+
+    /// Rule
+    #[derive(Clone, Debug, Deserialize, Serialize)]
+    pub struct Rule {
+        pub class: String,
+        /// Condition over the measurements, such as
+        /// `area > 500 && circularity < 0.6`
+        pub expression: Expression,
+    }
+
+    /// Method
+    #[derive(Clone, Copy, Debug, Deserialize, Eq, PartialEq, Serialize)]
+    pub enum Method {
+        NearestCentroid,
+        /// k nearest neighbors
+        Knn(usize),
+    }
+}
+
+mod filter {
+    use crate::expression::Expression;
+    use serde::{Deserialize, Serialize};
+
+    /// Filter and derived columns over the object measurements
+    #[derive(Clone, Debug, Default, Deserialize, Serialize)]
+    pub struct Filter {
+        /// Condition of the objects to keep, such as
+        /// `area in 200..5000 && solidity > 0.9`
+        pub keep: Option<Expression>,
+        /// Columns computed in order, each can use the ones before it
+        pub columns: Vec<Column>,
+    }
+
+    /// Column
+    #[derive(Clone, Debug, Deserialize, Serialize)]
+    pub struct Column {
+        pub name: String,
+        /// Value, such as `perimeter^2/(4*pi*area)`
+        pub expression: Expression,
+    }
+}
+
 mod contours {
     use opencv::imgproc::{CHAIN_APPROX_SIMPLE, RETR_EXTERNAL, RETR_TREE};
     use serde::{Deserialize, Serialize};
@@ -72,6 +298,117 @@ mod contours {
     }
 }
 
+mod crops {
+    use serde::{Deserialize, Serialize};
+
+    /// Per-object crops
+    #[derive(Clone, Copy, Debug, Deserialize, Serialize)]
+    pub struct Crops {
+        /// Pixels around the bounding rectangle
+        pub padding: i32,
+        /// Filled contour as alpha
+        pub alpha: bool,
+    }
+
+    impl Default for Crops {
+        fn default() -> Self {
+            Self {
+                padding: 8,
+                alpha: true,
+            }
+        }
+    }
+}
+
+mod morphology {
+    use serde::{Deserialize, Serialize};
+
+    /// Median blur before thresholding
+    #[derive(Clone, Copy, Debug, Default, Deserialize, Serialize)]
+    pub struct Blur {
+        /// Aperture size, odd, `0` or `1` turns it off
+        pub ksize: i32,
+    }
+
+    /// Dilation after thresholding
+    #[derive(Clone, Copy, Debug, Deserialize, Serialize)]
+    pub struct Dilation {
+        pub ksize: i32,
+        /// `0` turns it off
+        pub iterations: i32,
+    }
+
+    impl Default for Dilation {
+        fn default() -> Self {
+            Self {
+                ksize: 3,
+                iterations: 0,
+            }
+        }
+    }
+}
+
+mod preprocess {
+    use opencv::photo::INPAINT_TELEA;
+    use serde::{Deserialize, Serialize};
+
+    /// Illumination and background correction before the pipelines
+    #[derive(Clone, Copy, Debug, Default, Deserialize, Serialize)]
+    pub struct Preprocess {
+        pub background: Background,
+        /// Gray card, `[x, y, width, height]`
+        pub white_balance: Option<[i32; 4]>,
+        pub clahe: Option<Clahe>,
+        pub inpaint: Inpaint,
+    }
+
+    /// Background subtraction, `light` if the background is lighter than the
+    /// objects
+    #[derive(Clone, Copy, Debug, Default, Deserialize, Serialize)]
+    pub enum Background {
+        #[default]
+        None,
+        /// Morphological top-hat with a square kernel
+        TopHat { ksize: i32, light: bool },
+        /// Rolling ball of the radius
+        RollingBall { radius: i32, light: bool },
+    }
+
+    /// Contrast limited adaptive histogram equalization of the lightness
+    #[derive(Clone, Copy, Debug, Deserialize, Serialize)]
+    pub struct Clahe {
+        pub clip_limit: f64,
+        /// Tiles per side
+        pub tiles: i32,
+    }
+
+    impl Default for Clahe {
+        fn default() -> Self {
+            Self {
+                clip_limit: 2.0,
+                tiles: 8,
+            }
+        }
+    }
+
+    /// Inpainting of the masked regions
+    #[derive(Clone, Copy, Debug, Deserialize, Serialize)]
+    pub struct Inpaint {
+        pub radius: f64,
+        /// `INPAINT_NS` or `INPAINT_TELEA`
+        pub method: i32,
+    }
+
+    impl Default for Inpaint {
+        fn default() -> Self {
+            Self {
+                radius: 3.0,
+                method: INPAINT_TELEA,
+            }
+        }
+    }
+}
+
 mod threshold {
     use opencv::imgproc::{
         ADAPTIVE_THRESH_GAUSSIAN_C, ADAPTIVE_THRESH_MEAN_C, THRESH_BINARY_INV, THRESH_OTSU,
@@ -102,3 +439,302 @@ mod threshold {
         Mean = ADAPTIVE_THRESH_MEAN_C,
     }
 }
+
+mod algae {
+    use opencv::imgproc::HOUGH_GRADIENT_ALT;
+    use serde::{Deserialize, Serialize};
+
+    /// Algae
+    #[derive(Clone, Copy, Debug, Default, Deserialize, Serialize)]
+    pub struct Algae {
+        pub scale: Scale,
+        pub segmentation: Segmentation,
+        pub hough: Hough,
+    }
+
+    /// Scale
+    #[derive(Clone, Copy, Debug, Deserialize, Serialize)]
+    pub struct Scale {
+        /// Length of the scale bar in the template, µm
+        pub length: f64,
+        /// Minimum template match score
+        pub threshold: f64,
+    }
+
+    impl Default for Scale {
+        fn default() -> Self {
+            Self {
+                length: 10.0,
+                threshold: 0.9,
+            }
+        }
+    }
+
+    /// Segmentation
+    #[derive(Clone, Copy, Debug, Deserialize, Serialize)]
+    pub struct Segmentation {
+        /// Choice of the number of k-means clusters used for segmentation
+        pub criterion: Criterion,
+        /// Choice of the background cluster
+        pub selection: Selection,
+        /// Number of pixels sampled for the silhouette
+        pub sample: usize,
+    }
+
+    impl Default for Segmentation {
+        fn default() -> Self {
+            Self {
+                criterion: Criterion::Silhouette,
+                selection: Selection::Lightness,
+                sample: 1000,
+            }
+        }
+    }
+
+    /// Selection of the background cluster
+    #[derive(Clone, Copy, Debug, Deserialize, Eq, PartialEq, Serialize)]
+    pub enum Selection {
+        /// The lightest cluster
+        Lightness,
+        /// The cluster nearest to the sRGB color
+        Reference([u8; 3]),
+    }
+
+    /// Criterion of the number of clusters
+    #[derive(Clone, Copy, Debug, Deserialize, Eq, PartialEq, Serialize)]
+    pub enum Criterion {
+        /// Given number
+        Fixed(usize),
+        /// Knee of the inertia curve
+        Elbow,
+        /// Maximum mean silhouette
+        Silhouette,
+        /// Maximum Calinski-Harabasz index
+        CalinskiHarabasz,
+    }
+
+    /// Hough circles
+    #[derive(Clone, Copy, Debug, Deserialize, Serialize)]
+    pub struct Hough {
+        pub method: i32,
+        pub dp: f64,
+        pub min_dist: f64,
+        pub param1: f64,
+        pub param2: f64,
+        pub min_radius: i32,
+        pub max_radius: i32,
+        /// Radius bounds, µm, used instead of the pixel ones with a scale
+        pub radius: Option<(f64, f64)>,
+        /// Maximum intersection over union of kept circles
+        pub nms: f64,
+        /// Minimum intersection over union of a circle matching a contour
+        pub iou: f64,
+    }
+
+    impl Default for Hough {
+        fn default() -> Self {
+            Self {
+                method: HOUGH_GRADIENT_ALT,
+                dp: 1.5,
+                min_dist: 10.0,
+                param1: 300.0,
+                param2: 0.75,
+                min_radius: 10,
+                max_radius: 50,
+                radius: None,
+                nms: 0.3,
+                iou: 0.5,
+            }
+        }
+    }
+}
+
+mod render {
+    use serde::{Deserialize, Serialize};
+
+    const BLUE: [f64; 4] = [255.0, 0.0, 0.0, 255.0];
+    const CYAN: [f64; 4] = [255.0, 255.0, 0.0, 255.0];
+    const GREEN: [f64; 4] = [0.0, 255.0, 0.0, 255.0];
+    const MAGENTA: [f64; 4] = [255.0, 0.0, 255.0, 255.0];
+    const RED: [f64; 4] = [0.0, 0.0, 255.0, 255.0];
+    const WHITE: [f64; 4] = [255.0, 255.0, 255.0, 255.0];
+    const YELLOW: [f64; 4] = [0.0, 255.0, 255.0, 255.0];
+
+    /// Render
+    #[derive(Clone, Copy, Debug, Deserialize, Serialize)]
+    pub struct Render {
+        pub contour: Layer,
+        pub centroid: Layer,
+        pub bounding_rectangle: Layer,
+        pub rotated_rectangle: Layer,
+        pub min_circumcircle: Layer,
+        pub max_incircle: Layer,
+        pub incircle: Layer,
+        /// Mean colors of the contour, max incircle and incircle at the
+        /// corners of the bounding rectangle
+        pub swatches: Layer,
+        pub label: Label,
+    }
+
+    impl Default for Render {
+        fn default() -> Self {
+            Self {
+                contour: Layer::new(RED),
+                centroid: Layer::new(RED),
+                bounding_rectangle: Layer::new(YELLOW),
+                rotated_rectangle: Layer::new(GREEN),
+                min_circumcircle: Layer::new(BLUE),
+                max_incircle: Layer::new(CYAN),
+                incircle: Layer::new(MAGENTA),
+                swatches: Layer::new(WHITE),
+                label: Label::default(),
+            }
+        }
+    }
+
+    /// Layer
+    #[derive(Clone, Copy, Debug, Deserialize, Serialize)]
+    pub struct Layer {
+        pub visible: bool,
+        /// BGRA
+        pub color: [f64; 4],
+        pub thickness: i32,
+    }
+
+    impl Layer {
+        pub const fn new(color: [f64; 4]) -> Self {
+            Self {
+                visible: true,
+                color,
+                thickness: 1,
+            }
+        }
+    }
+
+    /// Label
+    #[derive(Clone, Copy, Debug, Deserialize, Serialize)]
+    pub struct Label {
+        pub visible: bool,
+        /// BGRA
+        pub color: [f64; 4],
+        pub thickness: i32,
+        pub font_scale: f64,
+        pub index: bool,
+        pub area: bool,
+        pub perimeter: bool,
+        pub class: bool,
+    }
+
+    impl Default for Label {
+        fn default() -> Self {
+            Self {
+                visible: true,
+                color: WHITE,
+                thickness: 1,
+                font_scale: 0.5,
+                index: true,
+                area: false,
+                perimeter: false,
+                class: false,
+            }
+        }
+    }
+}
+
+mod texture {
+    use serde::{Deserialize, Serialize};
+
+    /// Texture features of the objects
+    #[derive(Clone, Copy, Debug, Deserialize, Serialize)]
+    pub struct Texture {
+        pub enabled: bool,
+        /// Gray levels of the co-occurrence matrix
+        pub levels: usize,
+        /// Distance of the co-occurring pixels
+        pub distance: i32,
+        /// Radius of the local binary patterns
+        pub radius: i32,
+    }
+
+    impl Default for Texture {
+        fn default() -> Self {
+            Self {
+                enabled: false,
+                levels: 16,
+                distance: 1,
+                radius: 1,
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn config(columns: &[(&str, &str)], keep: &str) -> Result<Config> {
+        let mut config = Config::default();
+        for (name, expression) in columns {
+            config.filter.columns.push(Column {
+                name: name.to_string(),
+                expression: expression.parse()?,
+            });
+        }
+        config.filter.keep = Some(keep.parse()?);
+        Ok(config)
+    }
+
+    #[test]
+    fn validate() -> Result<()> {
+        let roundness = ("roundness", "perimeter^2/(4*pi*area)");
+        for pipeline in [Pipeline::Seeds, Pipeline::Algae] {
+            config(&[roundness], "area in 200..5000 && roundness < 1.2")?.validate(pipeline)?;
+            assert!(config(&[], "volume > 1")?.validate(pipeline).is_err());
+            assert!(config(&[("area", "1")], "area > 1")?
+                .validate(pipeline)
+                .is_err());
+            assert!(config(&[("pi", "1")], "area > 1")?
+                .validate(pipeline)
+                .is_err());
+            // Columns can only use the ones before them
+            let ratio = ("ratio", "roundness / solidity");
+            assert!(config(&[ratio, roundness], "ratio > 1")?
+                .validate(pipeline)
+                .is_err());
+            config(&[roundness, ratio], "ratio > 1")?.validate(pipeline)?;
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn pipelines() -> Result<()> {
+        // The label is a k-means cluster of the algae
+        config(&[], "label == 1")?.validate(Pipeline::Algae)?;
+        assert!(config(&[], "label == 1")?
+            .validate(Pipeline::Seeds)
+            .is_err());
+        // The texture is measured for the seeds only, if enabled
+        let mut textured = config(&[], "contrast < 2")?;
+        assert!(textured.validate(Pipeline::Seeds).is_err());
+        textured.texture.enabled = true;
+        textured.validate(Pipeline::Seeds)?;
+        assert!(textured.validate(Pipeline::Algae).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn rules() -> Result<()> {
+        let mut config = Config::default();
+        config.classifier.rules.push(Rule {
+            class: "damaged".to_owned(),
+            expression: "label == 1".parse()?,
+        });
+        assert!(config.validate(Pipeline::Seeds).is_err());
+        // Not classified
+        config.validate(Pipeline::Algae)?;
+        let mut config = Config::default();
+        config.classifier.features.push("circularty".to_owned());
+        assert!(config.validate(Pipeline::Seeds).is_err());
+        Ok(())
+    }
+}
@@ -0,0 +1,94 @@
+use crate::{config::Crops, object::Object};
+use anyhow::{ensure, Result};
+use opencv::{
+    core::{merge, no_array, split, Mat, Point, Rect, Scalar, Size, Vector, CV_16UC1, CV_8UC1},
+    imgproc::{cvt_color_def, draw_contours, COLOR_BGR2BGRA, FILLED, LINE_8},
+    prelude::*,
+};
+
+/// Crop of an object
+#[derive(Clone, Debug)]
+pub struct Crop {
+    /// Index of the object
+    pub index: usize,
+    /// Padded bounding rectangle, within the image
+    pub rectangle: Rect,
+    /// BGR, or BGRA with the filled contour as alpha
+    pub image: Mat,
+}
+
+/// Crops the padded bounding rectangles of the objects
+pub fn crops(source: &Mat, objects: &[Object], config: &Crops) -> Result<Vec<Crop>> {
+    let bounds = Rect::new(0, 0, source.cols(), source.rows());
+    let mut crops = Vec::with_capacity(objects.len());
+    for (index, object) in objects.iter().enumerate() {
+        let Rect {
+            x,
+            y,
+            width,
+            height,
+        } = object.bounding_rectangle;
+        let padding = config.padding;
+        let rectangle = Rect::new(
+            x - padding,
+            y - padding,
+            width + 2 * padding,
+            height + 2 * padding,
+        ) & bounds;
+        let mut image = Mat::roi(source, rectangle)?.try_clone()?;
+        if config.alpha {
+            let mut mask = Mat::zeros(rectangle.height, rectangle.width, CV_8UC1)?.to_mat()?;
+            draw_contours(
+                &mut mask,
+                &Vector::<Mat>::from_iter([object.contour.clone()]),
+                -1,
+                Scalar::all(255.0),
+                FILLED,
+                LINE_8,
+                &no_array(),
+                i32::MAX,
+                Point::new(-rectangle.x, -rectangle.y),
+            )?;
+            let mut bgra = Mat::default();
+            cvt_color_def(&image, &mut bgra, COLOR_BGR2BGRA)?;
+            let mut channels = Vector::<Mat>::new();
+            split(&bgra, &mut channels)?;
+            channels.set(3, mask)?;
+            merge(&channels, &mut image)?;
+        }
+        crops.push(Crop {
+            index,
+            rectangle,
+            image,
+        });
+    }
+    Ok(crops)
+}
+
+/// Label image, `CV_16UC1` with the background as `0` and the object of
+/// index `i` as `i + 1`
+///
+/// The offset keeps `0` for the background, the values match the annotation
+/// ids of the COCO export.
+pub fn labels(size: Size, objects: &[Object]) -> Result<Mat> {
+    ensure!(
+        objects.len() < u16::MAX as usize,
+        "{} objects do not fit in 16-bit labels",
+        objects.len(),
+    );
+    let mut labels = Mat::zeros_size(size, CV_16UC1)?.to_mat()?;
+    for (index, object) in objects.iter().enumerate() {
+        draw_contours(
+            &mut labels,
+            &Vector::<Mat>::from_iter([object.contour.clone()]),
+            -1,
+            Scalar::all((index + 1) as _),
+            FILLED,
+            LINE_8,
+            &no_array(),
+            i32::MAX,
+            Point::default(),
+        )?;
+    }
+    Ok(labels)
+}
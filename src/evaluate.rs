@@ -0,0 +1,276 @@
+use crate::{
+    annotations::{Coco, FeatureCollection},
+    object::{pairs, Object, Pair},
+};
+use anyhow::{ensure, Result};
+use cv::{Contour, ToInputArrayExt};
+use opencv::{
+    core::{compare, Mat, Scalar, CMP_EQ, CV_32S},
+    imgcodecs::{imread, IMREAD_UNCHANGED},
+    imgproc::{CHAIN_APPROX_SIMPLE, RETR_EXTERNAL},
+    prelude::*,
+};
+use serde::{Deserialize, Serialize};
+use std::{collections::BTreeSet, path::Path};
+
+/// Loads objects from a label image, COCO (`.json`) or GeoJSON (`.geojson`)
+/// annotations
+///
+/// Label images have `0` for the background and a distinct value for every
+/// object, 8 or 16 bit. COCO images are looked up by the name of the image.
+pub fn load(path: &Path, image: &str, scale: f64) -> Result<Vec<Object>> {
+    let extension = path
+        .extension()
+        .map(|extension| extension.to_string_lossy().to_lowercase())
+        .unwrap_or_default();
+    match &*extension {
+        "json" => Coco::load(path)?.objects(image, scale),
+        "geojson" => FeatureCollection::load(path)?.objects(scale),
+        _ => {
+            let labels = imread(&path.to_string_lossy(), IMREAD_UNCHANGED)?;
+            ensure!(!labels.empty(), "failed to read {}", path.display());
+            from_labels(&labels)
+        }
+    }
+}
+
+/// Objects of a label image, the largest outer contour of every label
+pub fn from_labels(labels: &Mat) -> Result<Vec<Object>> {
+    let mut converted = Mat::default();
+    labels.convert_to_def(&mut converted, CV_32S)?;
+    let values = converted
+        .data_typed::<i32>()?
+        .iter()
+        .copied()
+        .filter(|&value| value != 0)
+        .collect::<BTreeSet<_>>();
+    let mut objects = Vec::with_capacity(values.len());
+    for value in values {
+        let mut mask = Mat::default();
+        compare(&converted, &Scalar::all(value as _), &mut mask, CMP_EQ)?;
+        let contours = mask.find_contours(RETR_EXTERNAL, CHAIN_APPROX_SIMPLE)?;
+        let mut largest = None;
+        for contour in contours {
+            let area = contour.area()?;
+            if largest
+                .as_ref()
+                .map_or(true, |(largest, _)| area > *largest)
+            {
+                largest = Some((area, contour));
+            }
+        }
+        if let Some((_, contour)) = largest {
+            objects.push(Object::new(contour)?);
+        }
+    }
+    Ok(objects)
+}
+
+/// Evaluation of an image
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+pub struct Evaluation {
+    pub name: String,
+    pub metrics: Metrics,
+    /// Predicted (left) and true (right) objects
+    pub pairs: Vec<Pair>,
+}
+
+impl Evaluation {
+    /// Matches the predicted objects with the true ones by intersection over
+    /// union
+    pub fn new(name: &str, predicted: &[Object], truth: &[Object], threshold: f64) -> Result<Self> {
+        let contours = |objects: &[Object]| {
+            objects
+                .iter()
+                .map(|object| object.contour.clone())
+                .collect::<Vec<_>>()
+        };
+        let pairs = pairs(&contours(predicted), &contours(truth), threshold)?;
+        let matched = pairs
+            .iter()
+            .filter_map(|pair| Some((pair.left?, pair.right?, pair.iou)));
+        let mut counts = Counts {
+            predicted: predicted.len(),
+            truth: truth.len(),
+            ..Default::default()
+        };
+        for (left, right, iou) in matched {
+            counts.matched += 1;
+            counts.iou += iou;
+            let area = truth[right].area;
+            if area > 0.0 {
+                counts
+                    .area_errors
+                    .push((predicted[left].area - area) / area);
+            }
+        }
+        Ok(Self {
+            name: name.to_owned(),
+            metrics: Metrics::new(&counts),
+            pairs,
+        })
+    }
+}
+
+/// Aggregates the evaluations of several images
+pub fn aggregate(evaluations: &[Evaluation]) -> Metrics {
+    let mut counts = Counts::default();
+    for evaluation in evaluations {
+        let metrics = &evaluation.metrics;
+        counts.predicted += metrics.predicted;
+        counts.truth += metrics.truth;
+        counts.matched += metrics.matched;
+        counts.iou += metrics.mean_iou * metrics.matched as f64;
+        counts.area_errors.extend(&metrics.area_errors);
+    }
+    Metrics::new(&counts)
+}
+
+/// Metrics
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+pub struct Metrics {
+    pub predicted: usize,
+    pub truth: usize,
+    pub matched: usize,
+    pub precision: f64,
+    pub recall: f64,
+    pub f1: f64,
+    /// Predicted minus true count
+    pub count_error: i64,
+    /// Mean intersection over union of the matched objects
+    pub mean_iou: f64,
+    /// Relative area errors of the matched objects, `(predicted - true) / true`
+    pub area_errors: Vec<f64>,
+    pub area_error: Distribution,
+}
+
+impl Metrics {
+    fn new(counts: &Counts) -> Self {
+        let ratio = |a: usize, b: usize| if b == 0 { 0.0 } else { a as f64 / b as f64 };
+        let precision = ratio(counts.matched, counts.predicted);
+        let recall = ratio(counts.matched, counts.truth);
+        let f1 = if precision + recall > 0.0 {
+            2.0 * precision * recall / (precision + recall)
+        } else {
+            0.0
+        };
+        Self {
+            predicted: counts.predicted,
+            truth: counts.truth,
+            matched: counts.matched,
+            precision,
+            recall,
+            f1,
+            count_error: counts.predicted as i64 - counts.truth as i64,
+            mean_iou: if counts.matched == 0 {
+                0.0
+            } else {
+                counts.iou / counts.matched as f64
+            },
+            area_errors: counts.area_errors.clone(),
+            area_error: Distribution::new(&counts.area_errors),
+        }
+    }
+}
+
+/// Distribution
+#[derive(Clone, Copy, Debug, Default, Deserialize, Serialize)]
+pub struct Distribution {
+    pub mean: f64,
+    pub std: f64,
+    pub min: f64,
+    pub p5: f64,
+    pub median: f64,
+    pub p95: f64,
+    pub max: f64,
+}
+
+impl Distribution {
+    pub fn new(values: &[f64]) -> Self {
+        if values.is_empty() {
+            return Self::default();
+        }
+        let mut sorted = values.to_vec();
+        sorted.sort_by(f64::total_cmp);
+        let n = sorted.len() as f64;
+        let mean = sorted.iter().sum::<f64>() / n;
+        let variance = sorted
+            .iter()
+            .map(|value| (value - mean).powi(2))
+            .sum::<f64>()
+            / n;
+        let percentile = |p: f64| {
+            let rank = p * (sorted.len() - 1) as f64;
+            let (low, high) = (rank.floor() as usize, rank.ceil() as usize);
+            sorted[low] + (sorted[high] - sorted[low]) * (rank - low as f64)
+        };
+        Self {
+            mean,
+            std: variance.sqrt(),
+            min: sorted[0],
+            p5: percentile(0.05),
+            median: percentile(0.5),
+            p95: percentile(0.95),
+            max: sorted[sorted.len() - 1],
+        }
+    }
+}
+
+/// Counts
+#[derive(Clone, Debug, Default)]
+struct Counts {
+    predicted: usize,
+    truth: usize,
+    matched: usize,
+    /// Sum of intersections over unions
+    iou: f64,
+    area_errors: Vec<f64>,
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::object::Circle;
+    use opencv::core::Point2f;
+
+    fn objects(centers: &[(f32, f32)]) -> Result<Vec<Object>> {
+        centers
+            .iter()
+            .map(|&(x, y)| {
+                let circle = Circle {
+                    center: Point2f::new(x, y),
+                    radius: 10.0,
+                };
+                Object::new(circle.contour()?)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn evaluation() -> Result<()> {
+        let truth = objects(&[(20.0, 20.0), (60.0, 60.0)])?;
+        // Perfect match
+        let perfect = Evaluation::new("perfect", &truth, &truth, 0.5)?;
+        let metrics = &perfect.metrics;
+        assert_eq!(metrics.matched, 2);
+        assert_eq!([metrics.precision, metrics.recall, metrics.f1], [1.0; 3]);
+        assert!((metrics.mean_iou - 1.0).abs() < 1e-9);
+        assert_eq!(metrics.count_error, 0);
+        // Disjoint
+        let disjoint = Evaluation::new("disjoint", &objects(&[(100.0, 100.0)])?, &truth, 0.5)?;
+        let metrics = &disjoint.metrics;
+        assert_eq!(metrics.matched, 0);
+        assert_eq!([metrics.precision, metrics.recall, metrics.f1], [0.0; 3]);
+        assert_eq!(metrics.count_error, -1);
+        // Aggregated counts
+        let metrics = aggregate(&[perfect, disjoint]);
+        assert_eq!(
+            [metrics.predicted, metrics.truth, metrics.matched],
+            [3, 4, 2]
+        );
+        assert!((metrics.precision - 2.0 / 3.0).abs() < 1e-9);
+        assert!((metrics.recall - 0.5).abs() < 1e-9);
+        assert!((metrics.mean_iou - 1.0).abs() < 1e-9);
+        Ok(())
+    }
+}
@@ -0,0 +1,620 @@
+use serde::{de, Deserialize, Deserializer, Serialize, Serializer};
+use std::{
+    f64::consts::{E, PI},
+    fmt,
+    str::FromStr,
+};
+use thiserror::Error;
+
+pub type Result<T, E = Error> = std::result::Result<T, E>;
+
+/// Constants
+pub const CONSTANTS: [(&str, f64); 2] = [("pi", PI), ("e", E)];
+
+/// Expression error
+#[derive(Clone, Debug, Error, PartialEq)]
+pub enum Error {
+    #[error("{message} at {position} in `{expression}`")]
+    Syntax {
+        message: String,
+        position: usize,
+        expression: String,
+    },
+    #[error("unknown variable `{0}`")]
+    Variable(String),
+}
+
+/// Expression over numbers, with `0` as false and anything else as true
+///
+/// Operators by increasing precedence: `||`, `&&`, comparisons (`<`, `<=`,
+/// `>`, `>=`, `==`, `!=`) and ranges (`in a..b`, `in a..=b`), `+` and `-`,
+/// `*` and `/`, unary `-` and `!`, right associative `^`. Functions: `abs`,
+/// `sqrt`, `exp`, `ln`, `log10`, `min` and `max`. Constants: `pi` and `e`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Expression {
+    source: String,
+    node: Node,
+}
+
+impl Expression {
+    /// Evaluates the expression with the values of the variables
+    pub fn evaluate(&self, variables: &impl Fn(&str) -> Option<f64>) -> Result<f64> {
+        self.node.evaluate(variables)
+    }
+
+    /// Evaluates the expression as a condition
+    pub fn test(&self, variables: &impl Fn(&str) -> Option<f64>) -> Result<bool> {
+        Ok(self.evaluate(variables)? != 0.0)
+    }
+
+    /// Names of the variables, without the constants
+    pub fn variables(&self) -> Vec<&str> {
+        let mut variables = Vec::new();
+        self.node.visit(&mut |node| {
+            if let Node::Variable(name) = node {
+                if constant(name).is_none() && !variables.contains(&name.as_str()) {
+                    variables.push(name.as_str());
+                }
+            }
+        });
+        variables
+    }
+}
+
+impl FromStr for Expression {
+    type Err = Error;
+
+    fn from_str(source: &str) -> Result<Self> {
+        let syntax = |message, position| Error::Syntax {
+            message,
+            position,
+            expression: source.to_owned(),
+        };
+        let mut parser = Parser {
+            tokens: tokenize(source).map_err(|(message, position)| syntax(message, position))?,
+            index: 0,
+            length: source.len(),
+        };
+        let node = parser
+            .parse()
+            .map_err(|(message, position)| syntax(message, position))?;
+        Ok(Self {
+            source: source.to_owned(),
+            node,
+        })
+    }
+}
+
+impl fmt::Display for Expression {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.source)
+    }
+}
+
+impl Serialize for Expression {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&self.source)
+    }
+}
+
+impl<'de> Deserialize<'de> for Expression {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        String::deserialize(deserializer)?
+            .parse()
+            .map_err(de::Error::custom)
+    }
+}
+
+/// Syntax tree
+#[derive(Clone, Debug, PartialEq)]
+enum Node {
+    Number(f64),
+    Variable(String),
+    Unary(Unary, Box<Node>),
+    Binary(Binary, Box<Node>, Box<Node>),
+    /// Value, start and end of a range, and whether the end is included
+    In(Box<Node>, Box<Node>, Box<Node>, bool),
+    Call(Function, Vec<Node>),
+}
+
+impl Node {
+    fn evaluate(&self, variables: &impl Fn(&str) -> Option<f64>) -> Result<f64> {
+        let truth = |value: bool| if value { 1.0 } else { 0.0 };
+        Ok(match self {
+            Self::Number(value) => *value,
+            Self::Variable(name) => constant(name)
+                .or_else(|| variables(name))
+                .ok_or_else(|| Error::Variable(name.clone()))?,
+            Self::Unary(operator, operand) => {
+                let value = operand.evaluate(variables)?;
+                match operator {
+                    Unary::Negate => -value,
+                    Unary::Not => truth(value == 0.0),
+                }
+            }
+            Self::Binary(operator, left, right) => {
+                let left = left.evaluate(variables)?;
+                // Short circuit
+                match operator {
+                    Binary::Or if left != 0.0 => return Ok(1.0),
+                    Binary::And if left == 0.0 => return Ok(0.0),
+                    _ => {}
+                }
+                let right = right.evaluate(variables)?;
+                match operator {
+                    Binary::Or | Binary::And => truth(right != 0.0),
+                    Binary::Less => truth(left < right),
+                    Binary::LessEqual => truth(left <= right),
+                    Binary::Greater => truth(left > right),
+                    Binary::GreaterEqual => truth(left >= right),
+                    Binary::Equal => truth(left == right),
+                    Binary::NotEqual => truth(left != right),
+                    Binary::Add => left + right,
+                    Binary::Subtract => left - right,
+                    Binary::Multiply => left * right,
+                    Binary::Divide => left / right,
+                    Binary::Power => left.powf(right),
+                }
+            }
+            Self::In(value, start, end, inclusive) => {
+                let value = value.evaluate(variables)?;
+                let (start, end) = (start.evaluate(variables)?, end.evaluate(variables)?);
+                truth(
+                    start <= value
+                        && if *inclusive {
+                            value <= end
+                        } else {
+                            value < end
+                        },
+                )
+            }
+            Self::Call(function, arguments) => {
+                let arguments = arguments
+                    .iter()
+                    .map(|argument| argument.evaluate(variables))
+                    .collect::<Result<Vec<_>>>()?;
+                match (function, &arguments[..]) {
+                    (Function::Abs, &[value]) => value.abs(),
+                    (Function::Sqrt, &[value]) => value.sqrt(),
+                    (Function::Exp, &[value]) => value.exp(),
+                    (Function::Ln, &[value]) => value.ln(),
+                    (Function::Log10, &[value]) => value.log10(),
+                    (Function::Min, &[a, b]) => a.min(b),
+                    (Function::Max, &[a, b]) => a.max(b),
+                    // Checked by the parser
+                    _ => unreachable!(),
+                }
+            }
+        })
+    }
+
+    fn visit<'a>(&'a self, visitor: &mut impl FnMut(&'a Self)) {
+        visitor(self);
+        match self {
+            Self::Number(_) | Self::Variable(_) => {}
+            Self::Unary(_, operand) => operand.visit(visitor),
+            Self::Binary(_, left, right) => {
+                left.visit(visitor);
+                right.visit(visitor);
+            }
+            Self::In(value, start, end, _) => {
+                value.visit(visitor);
+                start.visit(visitor);
+                end.visit(visitor);
+            }
+            Self::Call(_, arguments) => {
+                for argument in arguments {
+                    argument.visit(visitor);
+                }
+            }
+        }
+    }
+}
+
+/// Unary operator
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+enum Unary {
+    Negate,
+    Not,
+}
+
+/// Binary operator
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+enum Binary {
+    Or,
+    And,
+    Less,
+    LessEqual,
+    Greater,
+    GreaterEqual,
+    Equal,
+    NotEqual,
+    Add,
+    Subtract,
+    Multiply,
+    Divide,
+    Power,
+}
+
+/// Function
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+enum Function {
+    Abs,
+    Sqrt,
+    Exp,
+    Ln,
+    Log10,
+    Min,
+    Max,
+}
+
+impl Function {
+    fn new(name: &str) -> Option<Self> {
+        Some(match name {
+            "abs" => Self::Abs,
+            "sqrt" => Self::Sqrt,
+            "exp" => Self::Exp,
+            "ln" => Self::Ln,
+            "log10" => Self::Log10,
+            "min" => Self::Min,
+            "max" => Self::Max,
+            _ => return None,
+        })
+    }
+
+    fn arity(&self) -> usize {
+        match self {
+            Self::Min | Self::Max => 2,
+            _ => 1,
+        }
+    }
+}
+
+fn constant(name: &str) -> Option<f64> {
+    CONSTANTS
+        .iter()
+        .find(|(constant, _)| *constant == name)
+        .map(|&(_, value)| value)
+}
+
+/// Token
+#[derive(Clone, Debug, PartialEq)]
+enum Token {
+    Number(f64),
+    Identifier(String),
+    Symbol(&'static str),
+}
+
+impl fmt::Display for Token {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Number(value) => write!(f, "{value}"),
+            Self::Identifier(name) => write!(f, "{name}"),
+            Self::Symbol(symbol) => write!(f, "{symbol}"),
+        }
+    }
+}
+
+/// Symbols, longest first
+const SYMBOLS: [&str; 19] = [
+    "..=", "||", "&&", "<=", ">=", "==", "!=", "..", "<", ">", "+", "-", "*", "/", "^", "!", "(",
+    ")", ",",
+];
+
+/// Syntax error message and byte position
+type Syntax = (String, usize);
+
+fn tokenize(source: &str) -> Result<Vec<(usize, Token)>, Syntax> {
+    let mut tokens = Vec::new();
+    let mut position = 0;
+    while let Some(character) = source[position..].chars().next() {
+        let rest = &source[position..];
+        if character.is_whitespace() {
+            position += character.len_utf8();
+        } else if character.is_ascii_digit() {
+            let length = number(rest);
+            let value = rest[..length]
+                .parse()
+                .map_err(|_| (format!("invalid number `{}`", &rest[..length]), position))?;
+            tokens.push((position, Token::Number(value)));
+            position += length;
+        } else if character.is_alphabetic() || character == '_' {
+            let length = rest
+                .find(|character: char| !character.is_alphanumeric() && character != '_')
+                .unwrap_or(rest.len());
+            tokens.push((position, Token::Identifier(rest[..length].to_owned())));
+            position += length;
+        } else if let Some(symbol) = SYMBOLS.iter().find(|symbol| rest.starts_with(**symbol)) {
+            tokens.push((position, Token::Symbol(symbol)));
+            position += symbol.len();
+        } else {
+            return Err((format!("unexpected character `{character}`"), position));
+        }
+    }
+    Ok(tokens)
+}
+
+/// Length of the number at the start: digits, a fraction and an exponent
+///
+/// A dot is a fraction only if a digit follows, so that `200..5000` is a
+/// range.
+fn number(text: &str) -> usize {
+    let bytes = text.as_bytes();
+    let digits = |start: usize| {
+        start
+            + bytes[start..]
+                .iter()
+                .take_while(|byte| byte.is_ascii_digit())
+                .count()
+    };
+    let mut length = digits(0);
+    if bytes.get(length) == Some(&b'.') && bytes.get(length + 1).is_some_and(u8::is_ascii_digit) {
+        length = digits(length + 1);
+    }
+    if matches!(bytes.get(length), Some(b'e' | b'E')) {
+        let sign = usize::from(matches!(bytes.get(length + 1), Some(b'+' | b'-')));
+        if bytes.get(length + 1 + sign).is_some_and(u8::is_ascii_digit) {
+            length = digits(length + 1 + sign);
+        }
+    }
+    length
+}
+
+/// Recursive descent parser
+struct Parser {
+    tokens: Vec<(usize, Token)>,
+    index: usize,
+    length: usize,
+}
+
+impl Parser {
+    fn parse(&mut self) -> Result<Node, Syntax> {
+        let node = self.or()?;
+        match self.tokens.get(self.index) {
+            Some((position, token)) => Err((format!("unexpected `{token}`"), *position)),
+            None => Ok(node),
+        }
+    }
+
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.index).map(|(_, token)| token)
+    }
+
+    fn position(&self) -> usize {
+        self.tokens
+            .get(self.index)
+            .map_or(self.length, |&(position, _)| position)
+    }
+
+    /// Consumes the symbol or the keyword if it is next
+    fn eat(&mut self, expected: &str) -> bool {
+        let found = match self.peek() {
+            Some(Token::Symbol(symbol)) => *symbol == expected,
+            Some(Token::Identifier(name)) => name == expected,
+            _ => false,
+        };
+        if found {
+            self.index += 1;
+        }
+        found
+    }
+
+    fn expect(&mut self, expected: &str) -> Result<(), Syntax> {
+        if self.eat(expected) {
+            return Ok(());
+        }
+        Err((format!("expected `{expected}`"), self.position()))
+    }
+
+    fn binary(
+        &mut self,
+        operators: &[(&str, Binary)],
+        operand: fn(&mut Self) -> Result<Node, Syntax>,
+    ) -> Result<Node, Syntax> {
+        let mut left = operand(self)?;
+        'outer: loop {
+            for &(symbol, operator) in operators {
+                if self.eat(symbol) {
+                    let right = operand(self)?;
+                    left = Node::Binary(operator, Box::new(left), Box::new(right));
+                    continue 'outer;
+                }
+            }
+            return Ok(left);
+        }
+    }
+
+    fn or(&mut self) -> Result<Node, Syntax> {
+        self.binary(&[("||", Binary::Or)], Self::and)
+    }
+
+    fn and(&mut self) -> Result<Node, Syntax> {
+        self.binary(&[("&&", Binary::And)], Self::comparison)
+    }
+
+    fn comparison(&mut self) -> Result<Node, Syntax> {
+        let left = self.sum()?;
+        if self.eat("in") {
+            let start = self.sum()?;
+            let inclusive = if self.eat("..=") {
+                true
+            } else {
+                self.expect("..")?;
+                false
+            };
+            let end = self.sum()?;
+            return Ok(Node::In(
+                Box::new(left),
+                Box::new(start),
+                Box::new(end),
+                inclusive,
+            ));
+        }
+        for (symbol, operator) in [
+            ("<=", Binary::LessEqual),
+            (">=", Binary::GreaterEqual),
+            ("==", Binary::Equal),
+            ("!=", Binary::NotEqual),
+            ("<", Binary::Less),
+            (">", Binary::Greater),
+        ] {
+            if self.eat(symbol) {
+                let right = self.sum()?;
+                return Ok(Node::Binary(operator, Box::new(left), Box::new(right)));
+            }
+        }
+        Ok(left)
+    }
+
+    fn sum(&mut self) -> Result<Node, Syntax> {
+        self.binary(
+            &[("+", Binary::Add), ("-", Binary::Subtract)],
+            Self::product,
+        )
+    }
+
+    fn product(&mut self) -> Result<Node, Syntax> {
+        self.binary(
+            &[("*", Binary::Multiply), ("/", Binary::Divide)],
+            Self::unary,
+        )
+    }
+
+    fn unary(&mut self) -> Result<Node, Syntax> {
+        if self.eat("-") {
+            return Ok(Node::Unary(Unary::Negate, Box::new(self.unary()?)));
+        }
+        if self.eat("!") {
+            return Ok(Node::Unary(Unary::Not, Box::new(self.unary()?)));
+        }
+        self.power()
+    }
+
+    fn power(&mut self) -> Result<Node, Syntax> {
+        let base = self.primary()?;
+        if self.eat("^") {
+            // Right associative, `2^-1` is a half
+            let exponent = self.unary()?;
+            return Ok(Node::Binary(
+                Binary::Power,
+                Box::new(base),
+                Box::new(exponent),
+            ));
+        }
+        Ok(base)
+    }
+
+    fn primary(&mut self) -> Result<Node, Syntax> {
+        let position = self.position();
+        let Some(token) = self.peek().cloned() else {
+            return Err(("unexpected end".to_owned(), position));
+        };
+        self.index += 1;
+        match token {
+            Token::Number(value) => Ok(Node::Number(value)),
+            Token::Identifier(name) if self.eat("(") => {
+                let function = Function::new(&name)
+                    .ok_or_else(|| (format!("unknown function `{name}`"), position))?;
+                let mut arguments = Vec::new();
+                if !self.eat(")") {
+                    loop {
+                        arguments.push(self.or()?);
+                        if self.eat(")") {
+                            break;
+                        }
+                        self.expect(",")?;
+                    }
+                }
+                if arguments.len() != function.arity() {
+                    return Err((
+                        format!(
+                            "`{name}` takes {} arguments, not {}",
+                            function.arity(),
+                            arguments.len(),
+                        ),
+                        position,
+                    ));
+                }
+                Ok(Node::Call(function, arguments))
+            }
+            Token::Identifier(name) => Ok(Node::Variable(name)),
+            Token::Symbol("(") => {
+                let node = self.or()?;
+                self.expect(")")?;
+                Ok(node)
+            }
+            token => Err((format!("unexpected `{token}`"), position)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn evaluate(source: &str) -> Result<f64> {
+        let variables = |name: &str| match name {
+            "area" => Some(600.0),
+            "perimeter" => Some(100.0),
+            "circularity" => Some(0.5),
+            _ => None,
+        };
+        source.parse::<Expression>()?.evaluate(&variables)
+    }
+
+    fn position(source: &str) -> Option<usize> {
+        match source.parse::<Expression>() {
+            Err(Error::Syntax { position, .. }) => Some(position),
+            _ => None,
+        }
+    }
+
+    #[test]
+    fn precedence() {
+        assert_eq!(evaluate("1 + 2 * 3"), Ok(7.0));
+        assert_eq!(evaluate("(1 + 2) * 3"), Ok(9.0));
+        assert_eq!(evaluate("-2 - -3"), Ok(1.0));
+        assert_eq!(evaluate("-2^2"), Ok(-4.0));
+        assert_eq!(evaluate("2^3^2"), Ok(512.0));
+        assert_eq!(evaluate("2^-1"), Ok(0.5));
+        assert_eq!(evaluate("area > 500 && circularity < 0.6"), Ok(1.0));
+        assert_eq!(evaluate("area > 700 || !(circularity < 0.6)"), Ok(0.0));
+    }
+
+    #[test]
+    fn ranges() {
+        assert_eq!(evaluate("area in 200..5000 && circularity > 0.4"), Ok(1.0));
+        assert_eq!(evaluate("area in 200..600"), Ok(0.0));
+        assert_eq!(evaluate("area in 200..=600"), Ok(1.0));
+        assert_eq!(evaluate("1.5e2 in 1e2..2e2"), Ok(1.0));
+    }
+
+    #[test]
+    fn functions() {
+        let circularity = evaluate("4*pi*area/perimeter^2").unwrap();
+        assert!((circularity - 0.24 * PI).abs() < 1e-12);
+        assert_eq!(evaluate("max(abs(-2), sqrt(9))"), Ok(3.0));
+        assert_eq!(evaluate("ln(1) + e"), Ok(E));
+    }
+
+    #[test]
+    fn errors() {
+        assert_eq!(
+            evaluate("volume > 1"),
+            Err(Error::Variable("volume".to_owned()))
+        );
+        assert_eq!(position("area >"), Some(6));
+        assert_eq!(position("area # 1"), Some(5));
+        assert_eq!(position("area in 1 2"), Some(10));
+        assert_eq!(position("(area"), Some(5));
+        assert_eq!(position("sin(area)"), Some(0));
+        assert_eq!(position("min(area)"), Some(0));
+        assert_eq!(
+            "perimeter^2/(4*pi*area)"
+                .parse::<Expression>()
+                .unwrap()
+                .variables(),
+            ["perimeter", "area"]
+        );
+    }
+}
@@ -0,0 +1,29 @@
+use crate::config::Filter;
+use anyhow::{Context, Result};
+use std::collections::BTreeMap;
+
+/// Object with measurements usable in expressions
+pub trait Measured {
+    /// Value of the measurement or of the computed column
+    fn variable(&self, name: &str) -> Option<f64>;
+
+    fn columns_mut(&mut self) -> &mut BTreeMap<String, f64>;
+}
+
+/// Computes the columns of the object, in order, and tests whether the
+/// filter keeps it
+pub fn filter(object: &mut impl Measured, config: &Filter) -> Result<bool> {
+    for column in &config.columns {
+        let value = column
+            .expression
+            .evaluate(&|name: &str| object.variable(name))
+            .with_context(|| format!("column `{}`", column.name))?;
+        object.columns_mut().insert(column.name.clone(), value);
+    }
+    match &config.keep {
+        Some(keep) => Ok(keep
+            .test(&|name: &str| object.variable(name))
+            .context("filter")?),
+        None => Ok(true),
+    }
+}
@@ -1,12 +1,8 @@
 pub use self::config::Config;
 
-use anyhow::Result;
-use opencv::{
-    core::{DataType, Mat, MatTraitConst, Scalar, VecN},
-    imgproc::{cvt_color_def, COLOR_HSV2BGR},
-};
+use self::color::Bgr;
+use opencv::core::{Scalar, VecN};
 use serde::{Deserialize, Serialize};
-use std::iter::once;
 
 pub const BLACK: Scalar = Scalar::all(0.0);
 pub const WHITE: Scalar = Scalar::all(255.0);
@@ -26,28 +22,22 @@ pub struct Hsb {
 }
 
 impl Hsb {
-    pub fn bgr(&self) -> Result<VecN<u8, 3>> {
-        let src = Mat::from_exact_iter(once(VecN([
-            self.hue as u8,
-            self.saturation as u8,
-            self.brightness as u8,
-        ])))?;
-        let mut dst = Mat::default();
-        cvt_color_def(&src, &mut dst, COLOR_HSV2BGR)?;
-        Ok(*dst.at(0)?)
+    /// BGR, rounded to 8 bits
+    pub fn bgr(&self) -> VecN<u8, 3> {
+        VecN(color::Hsv::from(*self).bgr().round())
     }
 }
 
-pub fn hsva_to_bgra(hsva: VecN<f64, 4>) -> Result<VecN<f64, 4>> {
-    let src = Mat::from_exact_iter(once(VecN([
-        hsva[0].round() as u8,
-        hsva[1].round() as _,
-        hsva[2].round() as _,
-    ])))?;
-    let mut dst = Mat::default();
-    cvt_color_def(&src, &mut dst, COLOR_HSV2BGR)?;
-    let bgr = dst.at::<VecN<u8, 3>>(0)?;
-    Ok(VecN([bgr[0] as _, bgr[1] as _, bgr[2] as _, hsva[3]]))
+impl From<Hsb> for color::Hsv {
+    fn from(value: Hsb) -> Self {
+        Self::new(value.hue, value.saturation, value.brightness)
+    }
+}
+
+/// OpenCV scaled HSV with alpha to BGR with alpha, not rounded
+pub fn hsva_to_bgra(hsva: VecN<f64, 4>) -> VecN<f64, 4> {
+    let Bgr { blue, green, red } = color::Hsv::from(hsva).bgr();
+    VecN([blue, green, red, hsva[3]])
 }
 
 impl From<VecN<f64, 3>> for Hsb {
@@ -70,8 +60,32 @@ impl From<VecN<f64, 4>> for Hsb {
     }
 }
 
+pub mod algae;
+pub mod annotations;
+pub mod app;
+mod cache;
+pub mod calibration;
+pub mod classify;
+pub mod color;
 pub mod config;
+pub mod crops;
+pub mod evaluate;
+pub mod expression;
+pub mod filter;
+pub mod node;
+pub mod object;
+pub mod optimize;
+pub mod pipeline;
+pub mod preprocess;
+pub mod read;
+pub mod render;
+pub mod seeds;
+pub mod statistics;
+pub mod svg;
+mod sweep;
+pub mod texture;
 pub mod utils;
+mod view;
 
 #[cfg(test)]
 mod test {
@@ -1,5 +1,5 @@
-use self::app::App;
 use clap::crate_name;
+use finder::app::App;
 
 // When compiling natively
 #[cfg(not(target_arch = "wasm32"))]
@@ -32,9 +32,3 @@ fn main() {
             .expect("failed to start eframe");
     });
 }
-
-mod app;
-mod cache;
-mod node;
-mod utils;
-mod view;
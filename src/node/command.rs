@@ -0,0 +1,408 @@
+use super::{matrix, Error, Kind, NodeType, Pin, Result, Table, Value};
+use crate::utils::npy;
+use egui::{ComboBox, DragValue, Ui};
+use opencv::{
+    core::{Mat, Vector},
+    imgcodecs::{imdecode, imencode_def, imread, imwrite_def, IMREAD_UNCHANGED},
+};
+use serde::{Deserialize, Serialize};
+use std::{
+    borrow::Cow,
+    collections::{hash_map::DefaultHasher, BTreeMap},
+    env::temp_dir,
+    fs::{self, create_dir_all, read_to_string, remove_dir_all, File},
+    hash::{Hash, Hasher},
+    io::{BufReader, BufWriter, Read, Write},
+    path::{Path, PathBuf},
+    process::{self, Stdio},
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Mutex, MutexGuard,
+    },
+    thread::{sleep, spawn, JoinHandle},
+    time::{Duration, Instant},
+};
+
+/// Stdout of a finished run, written last
+const STDOUT: &str = ".stdout";
+
+/// Runs by working directory, `None` while running
+static RUNS: Mutex<BTreeMap<PathBuf, Option<Result<()>>>> = Mutex::new(BTreeMap::new());
+
+/// Number of finished runs
+static FINISHED: AtomicUsize = AtomicUsize::new(0);
+
+/// External command
+///
+/// Runs a child process on a worker thread in a working directory keyed by the
+/// hash of the parameters and inputs, the outputs of a finished run are reused.
+/// Inputs are written as `input<N>.<format>`, outputs are read back from
+/// `output<N>.<format>` and an optional `table.csv`. The placeholders `{dir}`,
+/// `{input<N>}` and `{output<N>}` are substituted in the arguments, the working
+/// directory is also passed as `FINDER_DIR`.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct Command {
+    pub program: String,
+    pub arguments: String,
+    pub inputs: usize,
+    pub outputs: usize,
+    pub format: Format,
+    /// Pass the first input through stdin and read the first output from
+    /// stdout
+    pub stdin: bool,
+    /// Timeout in seconds
+    pub timeout: u64,
+}
+
+impl Command {
+    fn hash_parameters(&self, state: &mut impl Hasher) {
+        self.program.hash(state);
+        self.arguments.hash(state);
+        self.inputs.hash(state);
+        self.outputs.hash(state);
+        self.format.hash(state);
+        self.stdin.hash(state);
+        self.timeout.hash(state);
+    }
+
+    fn directory(&self, inputs: &[Value]) -> PathBuf {
+        let mut hasher = DefaultHasher::new();
+        self.hash_parameters(&mut hasher);
+        inputs.hash(&mut hasher);
+        temp_dir().join(format!("finder-{:016x}", hasher.finish()))
+    }
+
+    fn input(&self, directory: &Path, index: usize) -> PathBuf {
+        directory.join(format!("input{index}.{}", self.format.extension()))
+    }
+
+    fn output(&self, directory: &Path, index: usize) -> PathBuf {
+        directory.join(format!("output{index}.{}", self.format.extension()))
+    }
+
+    fn arguments(&self, directory: &Path) -> Vec<String> {
+        self.arguments
+            .split_whitespace()
+            .map(|argument| {
+                let mut argument = argument.replace("{dir}", &directory.to_string_lossy());
+                for index in 0..self.inputs {
+                    argument = argument.replace(
+                        &format!("{{input{index}}}"),
+                        &self.input(directory, index).to_string_lossy(),
+                    );
+                }
+                for index in 0..self.outputs {
+                    argument = argument.replace(
+                        &format!("{{output{index}}}"),
+                        &self.output(directory, index).to_string_lossy(),
+                    );
+                }
+                argument
+            })
+            .collect()
+    }
+
+    /// Runs the program, returns its stdout
+    fn run(&self, directory: &Path, stdin: Vec<u8>) -> Result<Vec<u8>> {
+        let mut child = process::Command::new(&self.program)
+            .args(self.arguments(directory))
+            .current_dir(directory)
+            .env("FINDER_DIR", directory)
+            .stdin(if self.stdin {
+                Stdio::piped()
+            } else {
+                Stdio::null()
+            })
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()?;
+        // Pipes are served by threads, so that a full pipe can't block the
+        // child
+        if let Some(mut pipe) = child.stdin.take() {
+            spawn(move || pipe.write_all(&stdin));
+        }
+        let stdout = child.stdout.take().map(drain);
+        let stderr = child.stderr.take().map(drain);
+        let timeout = Duration::from_secs(self.timeout);
+        let deadline = Instant::now() + timeout;
+        let status = loop {
+            if let Some(status) = child.try_wait()? {
+                break Some(status);
+            }
+            if Instant::now() >= deadline {
+                child.kill()?;
+                child.wait()?;
+                break None;
+            }
+            sleep(Duration::from_millis(10));
+        };
+        // A grandchild may keep the pipes open, so the readers are only
+        // waited for until the deadline
+        while [&stdout, &stderr]
+            .into_iter()
+            .flatten()
+            .any(|handle| !handle.is_finished())
+            && Instant::now() < deadline
+        {
+            sleep(Duration::from_millis(10));
+        }
+        let stdout = join(stdout)?;
+        let stderr = String::from_utf8_lossy(&join(stderr)?).trim().to_owned();
+        match status {
+            None => Err(Error::Timeout {
+                program: self.program.clone(),
+                timeout,
+                stderr,
+            }),
+            Some(status) if !status.success() => Err(Error::Status {
+                program: self.program.clone(),
+                status: status.to_string(),
+                stderr,
+            }),
+            Some(_) => Ok(stdout),
+        }
+    }
+
+    /// Starts a run on a worker thread
+    fn start(&self, directory: &Path, inputs: &[Value]) -> Result<()> {
+        if directory.exists() {
+            remove_dir_all(directory)?;
+        }
+        create_dir_all(directory)?;
+        let mut stdin = Vec::new();
+        for index in 0..self.inputs {
+            let src = matrix(inputs, index)?;
+            if self.stdin && index == 0 {
+                stdin = self.format.encode(&src.0)?;
+            } else {
+                self.format.write(&src.0, &self.input(directory, index))?;
+            }
+        }
+        let command = self.clone();
+        let directory = directory.to_owned();
+        spawn(move || {
+            let result = command.run(&directory, stdin).and_then(|stdout| {
+                fs::write(directory.join(STDOUT), stdout)?;
+                Ok(())
+            });
+            runs().insert(directory, Some(result));
+            FINISHED.fetch_add(1, Ordering::Relaxed);
+        });
+        Ok(())
+    }
+}
+
+impl Hash for Command {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.hash_parameters(state);
+        // Finished runs change the key, so that pending nodes are computed
+        // again
+        FINISHED.load(Ordering::Relaxed).hash(state);
+    }
+}
+
+impl NodeType for Command {
+    const NAME: &'static str = "Command";
+    const TITLE: &'static str = "External command";
+    const CATEGORY: &'static str = "External";
+    const INPUTS: &'static [Pin] = &[];
+    const OUTPUTS: &'static [Pin] = &[];
+
+    fn inputs(&self) -> Cow<'static, [Pin]> {
+        (0..self.inputs)
+            .map(|index| Pin {
+                name: format!("input{index}").into(),
+                kind: Kind::Matrix,
+            })
+            .collect()
+    }
+
+    fn outputs(&self) -> Cow<'static, [Pin]> {
+        (0..self.outputs)
+            .map(|index| Pin {
+                name: format!("output{index}").into(),
+                kind: Kind::Matrix,
+            })
+            .chain([Pin::new("table", Kind::Table)])
+            .collect()
+    }
+
+    fn compute(&self, inputs: &[Value]) -> Result<Vec<Value>> {
+        let directory = self.directory(inputs);
+        let path = directory.join(STDOUT);
+        if !path.exists() {
+            let mut runs = runs();
+            match runs.get(&directory) {
+                Some(None) => return Err(Error::Pending),
+                Some(Some(Err(error))) => return Err(error.clone()),
+                _ => {}
+            }
+            self.start(&directory, inputs)?;
+            runs.insert(directory, None);
+            return Err(Error::Pending);
+        }
+        let stdout = fs::read(path)?;
+        let mut outputs = Vec::with_capacity(self.outputs + 1);
+        for index in 0..self.outputs {
+            let dst = if self.stdin && index == 0 {
+                self.format.decode(&stdout)?
+            } else {
+                self.format.read(&self.output(&directory, index))?
+            };
+            outputs.push(Value::matrix(dst));
+        }
+        // A table is read from `table.csv` or from stdout, if it doesn't carry
+        // an output
+        let table = directory.join("table.csv");
+        let table = if table.exists() {
+            Table::from_csv(&read_to_string(table)?)
+        } else if !self.stdin || self.outputs == 0 {
+            Table::from_csv(&String::from_utf8_lossy(&stdout))
+        } else {
+            Table::default()
+        };
+        outputs.push(Value::Table(table.into()));
+        Ok(outputs)
+    }
+
+    fn show_body(&mut self, ui: &mut Ui, _inputs: &[Value]) {
+        ui.vertical(|ui| {
+            // Program
+            ui.horizontal(|ui| {
+                ui.label("Program:");
+                ui.text_edit_singleline(&mut self.program);
+            });
+            // Arguments
+            ui.horizontal(|ui| {
+                ui.label("Arguments:");
+                ui.text_edit_singleline(&mut self.arguments)
+                    .on_hover_text("Placeholders: {dir}, {input<N>}, {output<N>}");
+            });
+            // Pins
+            ui.horizontal(|ui| {
+                ui.label("Inputs:");
+                ui.add(DragValue::new(&mut self.inputs).clamp_range(0..=8))
+                    .on_hover_text("Inputs");
+                ui.label("Outputs:");
+                ui.add(DragValue::new(&mut self.outputs).clamp_range(0..=8))
+                    .on_hover_text("Outputs");
+            });
+            // Format
+            ui.horizontal(|ui| {
+                ui.label("Format:");
+                ComboBox::from_id_source("format")
+                    .selected_text(self.format.extension())
+                    .show_ui(ui, |ui| {
+                        ui.selectable_value(&mut self.format, Format::Png, "png");
+                        ui.selectable_value(&mut self.format, Format::Npy, "npy");
+                    })
+                    .response
+                    .on_hover_text("Format");
+                ui.checkbox(&mut self.stdin, "Stdin/stdout");
+            });
+            // Timeout
+            ui.horizontal(|ui| {
+                ui.label("Timeout:");
+                ui.add(
+                    DragValue::new(&mut self.timeout)
+                        .clamp_range(1..=3600)
+                        .suffix(" s"),
+                )
+                .on_hover_text("Timeout");
+            });
+        });
+    }
+}
+
+impl Default for Command {
+    fn default() -> Self {
+        Self {
+            program: Default::default(),
+            arguments: Default::default(),
+            inputs: 1,
+            outputs: 1,
+            format: Format::Png,
+            stdin: false,
+            timeout: 10,
+        }
+    }
+}
+
+/// Format
+#[derive(Clone, Copy, Debug, Default, Deserialize, Eq, Hash, PartialEq, Serialize)]
+pub enum Format {
+    #[default]
+    Png,
+    Npy,
+}
+
+impl Format {
+    pub fn extension(&self) -> &'static str {
+        match self {
+            Self::Png => "png",
+            Self::Npy => "npy",
+        }
+    }
+
+    fn read(&self, path: &Path) -> Result<Mat> {
+        match self {
+            Self::Png => Ok(imread(&path.to_string_lossy(), IMREAD_UNCHANGED)?),
+            Self::Npy => Ok(npy::read(BufReader::new(File::open(path)?))?),
+        }
+    }
+
+    fn write(&self, mat: &Mat, path: &Path) -> Result<()> {
+        match self {
+            Self::Png => {
+                imwrite_def(&path.to_string_lossy(), mat)?;
+            }
+            Self::Npy => npy::write(mat, BufWriter::new(File::create(path)?))?,
+        }
+        Ok(())
+    }
+
+    fn decode(&self, bytes: &[u8]) -> Result<Mat> {
+        match self {
+            Self::Png => Ok(imdecode(
+                &Vector::<u8>::from_slice(bytes),
+                IMREAD_UNCHANGED,
+            )?),
+            Self::Npy => Ok(npy::read(bytes)?),
+        }
+    }
+
+    fn encode(&self, mat: &Mat) -> Result<Vec<u8>> {
+        let mut bytes = Vec::new();
+        match self {
+            Self::Png => {
+                let mut buffer = Vector::new();
+                imencode_def(".png", mat, &mut buffer)?;
+                bytes.extend(buffer);
+            }
+            Self::Npy => npy::write(mat, &mut bytes)?,
+        }
+        Ok(bytes)
+    }
+}
+
+fn runs() -> MutexGuard<'static, BTreeMap<PathBuf, Option<Result<()>>>> {
+    RUNS.lock().unwrap_or_else(|error| error.into_inner())
+}
+
+fn drain(mut pipe: impl Read + Send + 'static) -> JoinHandle<std::io::Result<Vec<u8>>> {
+    spawn(move || {
+        let mut bytes = Vec::new();
+        pipe.read_to_end(&mut bytes)?;
+        Ok(bytes)
+    })
+}
+
+fn join(handle: Option<JoinHandle<std::io::Result<Vec<u8>>>>) -> Result<Vec<u8>> {
+    match handle {
+        Some(handle) if handle.is_finished() => handle
+            .join()
+            .map_err(|_| Error::Io("pipe reader panicked".to_owned()))?
+            .map_err(Into::into),
+        _ => Ok(Vec::new()),
+    }
+}
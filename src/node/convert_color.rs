@@ -1,34 +1,31 @@
-use crate::{
-    utils::SyncMat,
-    view::{View, RED, UNTYPED_COLOR},
-};
+use super::{matrix, Kind, NodeType, Pin, Result, Value};
 use egui::{ComboBox, Ui};
-use egui_snarl::{ui::PinInfo, InPin};
-use opencv::imgproc::ColorConversionCodes::*;
+use opencv::{
+    core::Mat,
+    imgproc::{cvt_color_def, ColorConversionCodes::*},
+};
 use serde::{Deserialize, Serialize};
-use std::sync::Arc;
 
 /// Convert color
 #[derive(Clone, Debug, Default, Deserialize, Hash, Serialize)]
 pub struct ConvertColor {
-    #[serde(skip)]
-    pub src: Arc<SyncMat>,
     pub code: i32,
 }
 
-impl View for ConvertColor {
-    fn show_input(&mut self, ui: &mut Ui, pin: &InPin) -> PinInfo {
-        match pin.id.input {
-            0 if pin.remotes.is_empty() => PinInfo::square().with_fill(UNTYPED_COLOR),
-            0 => {
-                ui.label(self.src.to_string());
-                PinInfo::square().with_fill(RED)
-            }
-            _ => unreachable!("ConvertColor node has only 1 input"),
-        }
+impl NodeType for ConvertColor {
+    const NAME: &'static str = "ConvertColor";
+    const TITLE: &'static str = "Convert color";
+    const CATEGORY: &'static str = "Proc";
+    const INPUTS: &'static [Pin] = &[Pin::new("src", Kind::Matrix)];
+    const OUTPUTS: &'static [Pin] = &[Pin::new("dst", Kind::Matrix)];
+
+    fn compute(&self, inputs: &[Value]) -> Result<Vec<Value>> {
+        let mut dst = Mat::default();
+        cvt_color_def(matrix(inputs, 0)?, &mut dst, self.code)?;
+        Ok(vec![Value::matrix(dst)])
     }
 
-    fn show_body(&mut self, ui: &mut Ui) {
+    fn show_body(&mut self, ui: &mut Ui, _inputs: &[Value]) {
         // Code
         ui.horizontal(|ui| {
             ui.label("Code:");
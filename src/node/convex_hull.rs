@@ -1,20 +1,40 @@
-use crate::utils::SyncMat;
+use super::{contours, Kind, NodeType, Pin, Result, Value};
+use egui::Ui;
+use opencv::{core::Mat, imgproc::convex_hull};
 use serde::{Deserialize, Serialize};
-use std::sync::Arc;
 
 /// Convex hull
 #[derive(Clone, Debug, Deserialize, Hash, Serialize)]
 pub struct ConvexHull {
-    #[serde(skip)]
-    pub points: Arc<SyncMat>,
     pub clockwise: bool,
     pub return_points: bool,
 }
 
+impl NodeType for ConvexHull {
+    const NAME: &'static str = "ConvexHull";
+    const TITLE: &'static str = "Convex hull";
+    const CATEGORY: &'static str = "Proc";
+    const INPUTS: &'static [Pin] = &[Pin::new("points", Kind::Contours)];
+    const OUTPUTS: &'static [Pin] = &[Pin::new("hull", Kind::Contours)];
+
+    fn compute(&self, inputs: &[Value]) -> Result<Vec<Value>> {
+        let mut hulls = Vec::new();
+        for points in contours(inputs, 0)? {
+            let mut hull = Mat::default();
+            convex_hull(points, &mut hull, self.clockwise, self.return_points)?;
+            hulls.push(hull);
+        }
+        Ok(vec![Value::contours(hulls)])
+    }
+
+    fn show_body(&mut self, ui: &mut Ui, _inputs: &[Value]) {
+        ui.checkbox(&mut self.clockwise, "Clockwise");
+    }
+}
+
 impl Default for ConvexHull {
     fn default() -> Self {
         Self {
-            points: Default::default(),
             clockwise: false,
             return_points: true,
         }
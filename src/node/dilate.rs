@@ -1,39 +1,59 @@
-use super::Point;
-use crate::{
-    utils::SyncMat,
-    view::{View, RED, UNTYPED_COLOR},
-};
+use super::{matrix, Kind, NodeType, Parameter, Pin, Point, Result, Value};
 use egui::{DragValue, Ui};
-use egui_snarl::{ui::PinInfo, InPin};
-use serde::{Deserialize, Serialize};
-use std::{
-    hash::{Hash, Hasher},
-    sync::Arc,
+use opencv::{
+    core::{self, Mat, CV_8U},
+    imgproc::dilate,
 };
+use serde::{Deserialize, Serialize};
+use std::borrow::Cow;
 
 /// Dilate
-#[derive(Clone, Debug, Deserialize, Serialize)]
+#[derive(Clone, Debug, Deserialize, Hash, Serialize)]
 pub struct Dilate {
-    #[serde(skip)]
-    pub src: Arc<SyncMat>,
     pub kernel: Kernel,
     pub anchor: Point,
     pub iterations: i32,
 }
 
-impl View for Dilate {
-    fn show_input(&mut self, ui: &mut Ui, pin: &InPin) -> PinInfo {
-        match pin.id.input {
-            0 if pin.remotes.is_empty() => PinInfo::square().with_fill(UNTYPED_COLOR),
-            0 => {
-                ui.label(self.src.to_string());
-                PinInfo::square().with_fill(RED)
-            }
-            _ => unreachable!("Dilate node has 1 input"),
-        }
+impl NodeType for Dilate {
+    const NAME: &'static str = "Dilate";
+    const TITLE: &'static str = "Dilate";
+    const CATEGORY: &'static str = "Proc";
+    const INPUTS: &'static [Pin] = &[Pin::new("src", Kind::Matrix)];
+    const OUTPUTS: &'static [Pin] = &[Pin::new("dst", Kind::Matrix)];
+
+    fn parameters(&mut self) -> Vec<(Cow<'static, str>, Parameter<'_>)> {
+        vec![
+            (
+                "kernel.rows".into(),
+                Parameter::Integer(&mut self.kernel.rows),
+            ),
+            (
+                "kernel.cols".into(),
+                Parameter::Integer(&mut self.kernel.cols),
+            ),
+            (
+                "iterations".into(),
+                Parameter::Integer(&mut self.iterations),
+            ),
+        ]
     }
 
-    fn show_body(&mut self, ui: &mut Ui) {
+    fn compute(&self, inputs: &[Value]) -> Result<Vec<Value>> {
+        let mut dst = Mat::default();
+        dilate(
+            matrix(inputs, 0)?,
+            &mut dst,
+            &Mat::ones(self.kernel.rows, self.kernel.cols, CV_8U)?,
+            core::Point::new(self.anchor.x, self.anchor.y),
+            self.iterations,
+            Default::default(),
+            Default::default(),
+        )?;
+        Ok(vec![Value::matrix(dst)])
+    }
+
+    fn show_body(&mut self, ui: &mut Ui, _inputs: &[Value]) {
         ui.vertical(|ui| {
             // Kernel
             ui.horizontal(|ui| {
@@ -76,7 +96,6 @@ impl View for Dilate {
 impl Default for Dilate {
     fn default() -> Self {
         Self {
-            src: Default::default(),
             kernel: Default::default(),
             anchor: Point { x: -1, y: -1 },
             iterations: Default::default(),
@@ -84,15 +103,6 @@ impl Default for Dilate {
     }
 }
 
-impl Hash for Dilate {
-    fn hash<H: Hasher>(&self, state: &mut H) {
-        self.src.hash(state);
-        self.kernel.hash(state);
-        self.anchor.hash(state);
-        self.iterations.hash(state);
-    }
-}
-
 /// Kernel
 #[derive(Clone, Debug, Default, Deserialize, Hash, Serialize)]
 pub struct Kernel {
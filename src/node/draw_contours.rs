@@ -1,95 +1,95 @@
-use super::Point;
-use crate::{
-    utils::SyncMat,
-    view::{View, RED, UNTYPED_COLOR},
+use super::{contours, matrix, Kind, NodeType, Parameter, Pin, Result, Value};
+use egui::{epaint::util::FloatOrd, DragValue, Ui};
+use opencv::{
+    core::{no_array, Mat, Point, Scalar, Vector},
+    imgproc::{draw_contours, LINE_8},
 };
-use egui::{DragValue, Ui};
-use egui_snarl::{ui::PinInfo, InPin};
-use opencv::core::Mat;
 use serde::{Deserialize, Serialize};
 use std::{
+    borrow::Cow,
     hash::{Hash, Hasher},
-    sync::Arc,
 };
 
 /// Draw contours
 #[derive(Clone, Debug, Deserialize, Serialize)]
 pub struct DrawContours {
-    #[serde(skip)]
-    pub contours: Vec<Mat>,
     pub color: [f64; 4],
+    pub thickness: i32,
 }
 
-// impl View for DrawContours {
-//     fn show_input(&mut self, ui: &mut Ui, pin: &InPin) -> PinInfo {
-//         match pin.id.input {
-//             0 if pin.remotes.is_empty() => PinInfo::square().with_fill(UNTYPED_COLOR),
-//             0 => {
-//                 ui.label(self.contours.to_string());
-//                 PinInfo::square().with_fill(RED)
-//             }
-//             _ => unreachable!("DrawContours node has 1 input"),
-//         }
-//     }
+impl NodeType for DrawContours {
+    const NAME: &'static str = "DrawContours";
+    const TITLE: &'static str = "Draw contours";
+    const CATEGORY: &'static str = "Proc";
+    const INPUTS: &'static [Pin] = &[
+        Pin::new("image", Kind::Matrix),
+        Pin::new("contours", Kind::Contours),
+    ];
+    const OUTPUTS: &'static [Pin] = &[Pin::new("image", Kind::Matrix)];
 
-//     fn show_body(&mut self, ui: &mut Ui) {
-//         ui.vertical(|ui| {
-//             // Kernel
-//             ui.horizontal(|ui| {
-//                 ui.label("Kernel:");
-//                 ui.add(
-//                     DragValue::new(&mut self.kernel.rows)
-//                         .speed(1)
-//                         .clamp_range(0..=i32::MAX),
-//                 )
-//                 .on_hover_text("Kernel rows");
-//                 ui.add(
-//                     DragValue::new(&mut self.kernel.cols)
-//                         .speed(1)
-//                         .clamp_range(0..=i32::MAX),
-//                 )
-//                 .on_hover_text("Kernel columns");
-//             });
-//             // Anchor
-//             ui.horizontal(|ui| {
-//                 ui.label("Anchor:");
-//                 ui.add(DragValue::new(&mut self.anchor.x))
-//                     .on_hover_text("Anchor x");
-//                 ui.add(DragValue::new(&mut self.anchor.y))
-//                     .on_hover_text("Anchor y");
-//             });
-//             // Iterations
-//             ui.horizontal(|ui| {
-//                 ui.label("Iterations:");
-//                 ui.add(
-//                     DragValue::new(&mut self.iterations)
-//                         .speed(1)
-//                         .clamp_range(0..=i32::MAX),
-//                 )
-//                 .on_hover_text("Iterations");
-//             });
-//         });
-//     }
-// }
+    fn parameters(&mut self) -> Vec<(Cow<'static, str>, Parameter<'_>)> {
+        vec![("thickness".into(), Parameter::Integer(&mut self.thickness))]
+    }
+
+    fn compute(&self, inputs: &[Value]) -> Result<Vec<Value>> {
+        let mut image = matrix(inputs, 0)?.0.clone();
+        let contours = contours(inputs, 1)?
+            .iter()
+            .map(|contour| contour.0.clone())
+            .collect::<Vector<Mat>>();
+        let [b, g, r, a] = self.color;
+        draw_contours(
+            &mut image,
+            &contours,
+            -1,
+            Scalar::new(b, g, r, a),
+            self.thickness,
+            LINE_8,
+            &no_array(),
+            i32::MAX,
+            Point::default(),
+        )?;
+        Ok(vec![Value::matrix(image)])
+    }
+
+    fn show_body(&mut self, ui: &mut Ui, _inputs: &[Value]) {
+        ui.vertical(|ui| {
+            // Color
+            ui.horizontal(|ui| {
+                ui.label("Color:");
+                for (value, text) in self.color.iter_mut().zip(["Blue", "Green", "Red"]) {
+                    ui.add(DragValue::new(value).speed(1.0).clamp_range(0.0..=255.0))
+                        .on_hover_text(text);
+                }
+            });
+            // Thickness
+            ui.horizontal(|ui| {
+                ui.label("Thickness:");
+                ui.add(
+                    DragValue::new(&mut self.thickness)
+                        .speed(1)
+                        .clamp_range(-1..=i32::MAX),
+                )
+                .on_hover_text("Thickness (-1 to fill)");
+            });
+        });
+    }
+}
 
 impl Default for DrawContours {
     fn default() -> Self {
         Self {
-            contours: Default::default(),
-            color: Default::default(),
+            color: [0.0, 0.0, 255.0, 255.0],
+            thickness: 1,
         }
     }
 }
 
-// impl Hash for DrawContours {
-//     fn hash<H: Hasher>(&self, state: &mut H) {
-//         self.contours.hash(state);
-//         self.color.hash(state);
-//     }
-// }
-
-// thickness: 1
-// line_type: LINE_8
-// hierarchy: noArray()
-// max_level: INT_MAX
-// offset: Point()
+impl Hash for DrawContours {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        for value in self.color {
+            value.ord().hash(state);
+        }
+        self.thickness.hash(state);
+    }
+}
@@ -0,0 +1,55 @@
+use std::{io, time::Duration};
+use thiserror::Error;
+
+pub type Result<T, E = Error> = std::result::Result<T, E>;
+
+#[derive(Clone, Debug, Error)]
+pub enum Error {
+    #[error("the graph has a cycle")]
+    Cycle,
+    #[error("input {index} is not connected or has a wrong type")]
+    Input { index: usize },
+    #[error("{0}")]
+    Io(String),
+    #[error("node {index} does not exist")]
+    Node { index: usize },
+    #[error("running")]
+    Pending,
+    #[error("output {index} is not computed")]
+    Output { index: usize },
+    #[error("{message}")]
+    OpenCV { code: i32, message: String },
+    #[error("`{program}` exited with {status}: {stderr}")]
+    Status {
+        program: String,
+        status: String,
+        stderr: String,
+    },
+    #[error("`{program}` timed out after {timeout:?}: {stderr}")]
+    Timeout {
+        program: String,
+        timeout: Duration,
+        stderr: String,
+    },
+}
+
+impl From<anyhow::Error> for Error {
+    fn from(value: anyhow::Error) -> Self {
+        Self::Io(format!("{value:#}"))
+    }
+}
+
+impl From<io::Error> for Error {
+    fn from(value: io::Error) -> Self {
+        Self::Io(value.to_string())
+    }
+}
+
+impl From<opencv::Error> for Error {
+    fn from(value: opencv::Error) -> Self {
+        Self::OpenCV {
+            code: value.code,
+            message: value.message,
+        }
+    }
+}
@@ -1,37 +1,39 @@
-use super::Point;
-use crate::{
-    utils::SyncMat,
-    view::{View, RED, UNTYPED_COLOR},
-};
+use super::{matrix, Kind, NodeType, Pin, Point, Result, Value};
 use egui::{ComboBox, DragValue, Ui};
-use egui_snarl::{ui::PinInfo, InPin};
-use opencv::imgproc::{ContourApproximationModes::*, RetrievalModes::*};
+use opencv::{
+    core::{self, Mat, Vector},
+    imgproc::{find_contours, ContourApproximationModes::*, RetrievalModes::*},
+};
 use serde::{Deserialize, Serialize};
-use std::sync::Arc;
 
 /// Find contours
 #[derive(Clone, Debug, Deserialize, Hash, Serialize)]
 pub struct FindContours {
-    #[serde(skip)]
-    pub image: Arc<SyncMat>,
     pub mode: i32,
     pub method: i32,
     pub offset: Point,
 }
 
-impl View for FindContours {
-    fn show_input(&mut self, ui: &mut Ui, pin: &InPin) -> PinInfo {
-        match pin.id.input {
-            0 if pin.remotes.is_empty() => PinInfo::square().with_fill(UNTYPED_COLOR),
-            0 => {
-                ui.label(self.image.to_string());
-                PinInfo::square().with_fill(RED)
-            }
-            _ => unreachable!("FindContours node has 1 input"),
-        }
+impl NodeType for FindContours {
+    const NAME: &'static str = "FindContours";
+    const TITLE: &'static str = "Find contours";
+    const CATEGORY: &'static str = "Proc";
+    const INPUTS: &'static [Pin] = &[Pin::new("image", Kind::Matrix)];
+    const OUTPUTS: &'static [Pin] = &[Pin::new("contours", Kind::Contours)];
+
+    fn compute(&self, inputs: &[Value]) -> Result<Vec<Value>> {
+        let mut contours = Vector::<Mat>::new();
+        find_contours(
+            matrix(inputs, 0)?,
+            &mut contours,
+            self.mode,
+            self.method,
+            core::Point::new(self.offset.x, self.offset.y),
+        )?;
+        Ok(vec![Value::contours(contours)])
     }
 
-    fn show_body(&mut self, ui: &mut Ui) {
+    fn show_body(&mut self, ui: &mut Ui, _inputs: &[Value]) {
         ui.vertical(|ui| {
             // Mode
             ui.horizontal(|ui| {
@@ -81,7 +83,6 @@ impl View for FindContours {
 impl Default for FindContours {
     fn default() -> Self {
         Self {
-            image: Default::default(),
             mode: Default::default(),
             method: CHAIN_APPROX_NONE as _,
             offset: Default::default(),
@@ -1,36 +1,36 @@
-use crate::{
-    utils::SyncMat,
-    view::{View, RED, UNTYPED_COLOR},
-};
+use super::{matrix, Kind, NodeType, Parameter, Pin, Result, Value};
 use egui::{epaint::util::FloatOrd, DragValue, Ui};
-use egui_snarl::{ui::PinInfo, InPin};
+use opencv::core::{greater_than_mat_f64, MatExprTraitConst};
 use serde::{Deserialize, Serialize};
 use std::{
+    borrow::Cow,
     hash::{Hash, Hasher},
-    sync::Arc,
 };
 
 /// Greater than
 #[derive(Clone, Debug, Default, Deserialize, Serialize)]
 pub struct GreaterThan {
-    #[serde(skip)]
-    pub a: Arc<SyncMat>,
     pub s: f64,
 }
 
-impl View for GreaterThan {
-    fn show_input(&mut self, ui: &mut Ui, pin: &InPin) -> PinInfo {
-        match pin.id.input {
-            0 if pin.remotes.is_empty() => PinInfo::square().with_fill(UNTYPED_COLOR),
-            0 => {
-                ui.label(self.a.to_string());
-                PinInfo::square().with_fill(RED)
-            }
-            _ => unreachable!("GreaterThan node has 1 input"),
-        }
+impl NodeType for GreaterThan {
+    const NAME: &'static str = "GreaterThan";
+    const TITLE: &'static str = "Greater than";
+    const CATEGORY: &'static str = "Proc";
+    const INPUTS: &'static [Pin] = &[Pin::new("a", Kind::Matrix)];
+    const OUTPUTS: &'static [Pin] = &[Pin::new("dst", Kind::Matrix)];
+
+    fn parameters(&mut self) -> Vec<(Cow<'static, str>, Parameter<'_>)> {
+        vec![("s".into(), Parameter::Float(&mut self.s))]
+    }
+
+    fn compute(&self, inputs: &[Value]) -> Result<Vec<Value>> {
+        Ok(vec![Value::matrix(
+            greater_than_mat_f64(matrix(inputs, 0)?, self.s)?.to_mat()?,
+        )])
     }
 
-    fn show_body(&mut self, ui: &mut Ui) {
+    fn show_body(&mut self, ui: &mut Ui, _inputs: &[Value]) {
         ui.horizontal(|ui| {
             ui.label("S:");
             ui.add(DragValue::new(&mut self.s).speed(2).clamp_range(3..=999))
@@ -41,7 +41,6 @@ impl View for GreaterThan {
 
 impl Hash for GreaterThan {
     fn hash<H: Hasher>(&self, state: &mut H) {
-        self.a.hash(state);
         self.s.ord().hash(state);
     }
 }
@@ -1,33 +1,33 @@
-use crate::{
-    utils::SyncMat,
-    view::{View, RED, UNTYPED_COLOR},
-};
+use super::{matrix, Kind, NodeType, Parameter, Pin, Result, Value};
 use egui::{DragValue, Ui};
-use egui_snarl::{ui::PinInfo, InPin};
+use opencv::{core::Mat, imgproc::median_blur};
 use serde::{Deserialize, Serialize};
-use std::sync::Arc;
+use std::borrow::Cow;
 
 /// Median blur
 #[derive(Clone, Debug, Deserialize, Hash, Serialize)]
 pub struct MedianBlur {
-    #[serde(skip)]
-    pub src: Arc<SyncMat>,
     pub ksize: i32,
 }
 
-impl View for MedianBlur {
-    fn show_input(&mut self, ui: &mut Ui, pin: &InPin) -> PinInfo {
-        match pin.id.input {
-            0 if pin.remotes.is_empty() => PinInfo::square().with_fill(UNTYPED_COLOR),
-            0 => {
-                ui.label(self.src.to_string());
-                PinInfo::square().with_fill(RED)
-            }
-            _ => unreachable!("MedianBlur node has 1 input"),
-        }
+impl NodeType for MedianBlur {
+    const NAME: &'static str = "MedianBlur";
+    const TITLE: &'static str = "Median blur";
+    const CATEGORY: &'static str = "Proc";
+    const INPUTS: &'static [Pin] = &[Pin::new("src", Kind::Matrix)];
+    const OUTPUTS: &'static [Pin] = &[Pin::new("dst", Kind::Matrix)];
+
+    fn parameters(&mut self) -> Vec<(Cow<'static, str>, Parameter<'_>)> {
+        vec![("ksize".into(), Parameter::Odd(&mut self.ksize))]
+    }
+
+    fn compute(&self, inputs: &[Value]) -> Result<Vec<Value>> {
+        let mut dst = Mat::default();
+        median_blur(matrix(inputs, 0)?, &mut dst, self.ksize)?;
+        Ok(vec![Value::matrix(dst)])
     }
 
-    fn show_body(&mut self, ui: &mut Ui) {
+    fn show_body(&mut self, ui: &mut Ui, _inputs: &[Value]) {
         ui.horizontal(|ui| {
             ui.label("K size:");
             ui.add(
@@ -42,9 +42,6 @@ impl View for MedianBlur {
 
 impl Default for MedianBlur {
     fn default() -> Self {
-        Self {
-            src: Default::default(),
-            ksize: 1,
-        }
+        Self { ksize: 1 }
     }
 }
@@ -1,136 +1,433 @@
 pub(crate) use self::{
-    convert_color::ConvertColor, convex_hull::ConvexHull, dilate::Dilate,
+    command::Command, convert_color::ConvertColor, convex_hull::ConvexHull, dilate::Dilate,
     draw_contours::DrawContours, find_contours::FindContours, greater_than::GreaterThan,
     median_blur::MedianBlur, read::Read, subtract::Subtract, threshold::Threshold, write::Write,
 };
+pub use self::{
+    error::{Error, Result},
+    registry::{register, registry, Entry, Registry},
+    subgraph::{Library, Subgraph},
+};
 
 use crate::utils::SyncMat;
-use opencv::{
-    boxed_ref::BoxedRef,
-    core::{type_to_string, Mat, MatTraitConstManual, ToInputArray, _InputArray},
-    prelude::MatTraitConst,
-    Result,
+use egui::{DragValue, Response, Ui};
+use opencv::core::Mat;
+use serde::{
+    de::{DeserializeOwned, Error as _},
+    ser::Error as _,
+    Deserialize, Deserializer, Serialize, Serializer,
 };
-use serde::{Deserialize, Serialize};
 use std::{
-    ffi::c_void,
-    fmt::{self, Display, Formatter},
+    any::Any,
+    borrow::Cow,
+    fmt::{self, Debug, Display, Formatter},
     hash::{Hash, Hasher},
     sync::Arc,
 };
 
+/// Node type
+///
+/// Everything the editor knows about a node is declared here: its name,
+/// category, pins, parameters (the serialized fields of the implementor), how
+/// its outputs are computed and how its body is shown. Register it with
+/// [`register`] to make it available in the graph menu.
+pub trait NodeType:
+    Clone + Debug + Default + DeserializeOwned + Hash + Serialize + Send + Sync + 'static
+{
+    /// Unique name, used for serialization
+    const NAME: &'static str;
+    /// Title, shown in the node header and the graph menu
+    const TITLE: &'static str;
+    /// Graph menu category
+    const CATEGORY: &'static str;
+    /// Whether the node can be added from the graph menu
+    const MENU: bool = true;
+    const INPUTS: &'static [Pin];
+    const OUTPUTS: &'static [Pin];
+
+    /// Title, override for nodes whose title depends on parameters
+    fn title(&self) -> Cow<'static, str> {
+        Cow::Borrowed(Self::TITLE)
+    }
+
+    /// Input pins, override for nodes whose pins depend on parameters
+    fn inputs(&self) -> Cow<'static, [Pin]> {
+        Cow::Borrowed(Self::INPUTS)
+    }
+
+    /// Output pins, override for nodes whose pins depend on parameters
+    fn outputs(&self) -> Cow<'static, [Pin]> {
+        Cow::Borrowed(Self::OUTPUTS)
+    }
+
+    /// Numeric parameters, which can be promoted or swept
+    fn parameters(&mut self) -> Vec<(Cow<'static, str>, Parameter<'_>)> {
+        Vec::new()
+    }
+
+    fn compute(&self, inputs: &[Value]) -> Result<Vec<Value>>;
+
+    fn show_body(&mut self, _ui: &mut Ui, _inputs: &[Value]) {}
+}
+
+/// Object safe counterpart of [`NodeType`]
+pub(crate) trait Operator: Send + Sync {
+    fn name(&self) -> &'static str;
+
+    fn title(&self) -> Cow<'static, str>;
+
+    fn inputs(&self) -> Cow<'static, [Pin]>;
+
+    fn outputs(&self) -> Cow<'static, [Pin]>;
+
+    fn parameters(&mut self) -> Vec<(Cow<'static, str>, Parameter<'_>)>;
+
+    fn compute(&self, inputs: &[Value]) -> Result<Vec<Value>>;
+
+    fn show_body(&mut self, ui: &mut Ui, inputs: &[Value]);
+
+    fn save(&self) -> serde_json::Result<serde_json::Value>;
+
+    fn as_any(&self) -> &dyn Any;
+
+    fn dyn_clone(&self) -> Box<dyn Operator>;
+
+    fn dyn_debug(&self, f: &mut Formatter) -> fmt::Result;
+
+    fn dyn_hash(&self, state: &mut dyn Hasher);
+}
+
+impl<T: NodeType> Operator for T {
+    fn name(&self) -> &'static str {
+        T::NAME
+    }
+
+    fn title(&self) -> Cow<'static, str> {
+        NodeType::title(self)
+    }
+
+    fn inputs(&self) -> Cow<'static, [Pin]> {
+        NodeType::inputs(self)
+    }
+
+    fn outputs(&self) -> Cow<'static, [Pin]> {
+        NodeType::outputs(self)
+    }
+
+    fn parameters(&mut self) -> Vec<(Cow<'static, str>, Parameter<'_>)> {
+        NodeType::parameters(self)
+    }
+
+    fn compute(&self, inputs: &[Value]) -> Result<Vec<Value>> {
+        NodeType::compute(self, inputs)
+    }
+
+    fn show_body(&mut self, ui: &mut Ui, inputs: &[Value]) {
+        NodeType::show_body(self, ui, inputs)
+    }
+
+    fn save(&self) -> serde_json::Result<serde_json::Value> {
+        serde_json::to_value(self)
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn dyn_clone(&self) -> Box<dyn Operator> {
+        Box::new(self.clone())
+    }
+
+    fn dyn_debug(&self, f: &mut Formatter) -> fmt::Result {
+        Debug::fmt(self, f)
+    }
+
+    fn dyn_hash(&self, mut state: &mut dyn Hasher) {
+        Hash::hash(self, &mut state)
+    }
+}
+
 /// Node
-#[derive(Clone, Debug, Deserialize, Hash, Serialize)]
-pub enum Node {
-    // codecs
-    Read(Read),
-    Write(Write),
-    // proc
-    ConvertColor(ConvertColor),
-    // ConvexHull(ConvexHull),
-    Dilate(Dilate),
-    // DrawContours(DrawContours),
-    FindContours(FindContours),
-    GreaterThan(GreaterThan),
-    MedianBlur(MedianBlur),
-    Subtract(Subtract),
-    Threshold(Threshold),
+pub struct Node {
+    pub(crate) operator: Box<dyn Operator>,
+    pub(crate) values: Vec<Value>,
 }
 
 impl Node {
-    pub fn as_mat_mut(&mut self, index: usize) -> &mut Arc<SyncMat> {
-        match self {
-            Self::Write(Write { img, .. }) => img,
-            Self::ConvertColor(ConvertColor { src, .. }) => src,
-            // Self::ConvexHull(ConvexHull { src, .. }) => src,
-            Self::Dilate(Dilate { src, .. }) => src,
-            Self::FindContours(FindContours { image, .. }) => image,
-            Self::GreaterThan(GreaterThan { a, .. }) => a,
-            Self::MedianBlur(MedianBlur { src, .. }) => src,
-            Self::Subtract(Subtract { src1, .. }) if index == 0 => src1,
-            Self::Subtract(Subtract { src2, .. }) if index == 1 => src2,
-            Self::Threshold(Threshold { src, .. }) => src,
-            _ => unreachable!(),
+    pub fn new<T: NodeType>(node: T) -> Self {
+        Self::from_operator(Box::new(node))
+    }
+
+    fn from_operator(operator: Box<dyn Operator>) -> Self {
+        let values = vec![Value::None; operator.inputs().len()];
+        Self { operator, values }
+    }
+
+    pub fn name(&self) -> &'static str {
+        self.operator.name()
+    }
+
+    pub fn title(&self) -> Cow<'static, str> {
+        self.operator.title()
+    }
+
+    pub fn inputs(&self) -> Cow<'static, [Pin]> {
+        self.operator.inputs()
+    }
+
+    pub fn outputs(&self) -> Cow<'static, [Pin]> {
+        self.operator.outputs()
+    }
+
+    pub fn parameters(&mut self) -> Vec<(Cow<'static, str>, Parameter<'_>)> {
+        self.operator.parameters()
+    }
+
+    /// Sets the value of an input, following changes of the input count
+    pub(crate) fn set_value(&mut self, index: usize, value: Value) {
+        self.values.resize(self.inputs().len(), Value::None);
+        if let Some(target) = self.values.get_mut(index) {
+            *target = value;
         }
     }
-}
 
-/// Value
-#[derive(Clone, Debug)]
-pub enum Value {
-    Matrix(Matrix),
-    Vector(Vec<Mat>),
+    pub fn compute(&self) -> Result<Vec<Value>> {
+        self.operator.compute(&self.values)
+    }
+
+    pub fn downcast_ref<T: NodeType>(&self) -> Option<&T> {
+        self.operator.as_any().downcast_ref()
+    }
 }
-impl Default for Value {
-    fn default() -> Self {
-        Self::Matrix(Default::default())
+
+impl Clone for Node {
+    fn clone(&self) -> Self {
+        Self {
+            operator: self.operator.dyn_clone(),
+            values: self.values.clone(),
+        }
     }
 }
 
-impl Display for Value {
+impl Debug for Node {
     fn fmt(&self, f: &mut Formatter) -> fmt::Result {
-        match self {
-            Value::Matrix(matrix) => Display::fmt(matrix, f),
-            Value::Vector(vector) => f.debug_list().entries(vector.iter().map(Matrix)).finish(),
-        }
+        self.operator.dyn_debug(f)
     }
 }
 
-impl Hash for Value {
+impl Hash for Node {
     fn hash<H: Hasher>(&self, state: &mut H) {
+        self.name().hash(state);
+        self.operator.dyn_hash(state);
+        self.values.hash(state);
+    }
+}
+
+impl Serialize for Node {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        Stored {
+            r#type: self.name().to_owned(),
+            parameters: self.operator.save().map_err(S::Error::custom)?,
+        }
+        .serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for Node {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let stored = Stored::deserialize(deserializer)?;
+        // The lock is released before loading, nodes may contain nodes
+        let entry = *registry()
+            .read()
+            .map_err(D::Error::custom)?
+            .get(&stored.r#type)
+            .ok_or_else(|| D::Error::custom(format!("unknown node type `{}`", stored.r#type)))?;
+        entry.load(stored.parameters).map_err(D::Error::custom)
+    }
+}
+
+/// Stored node
+///
+/// The parameters are serialized in place, a string holds the RON of older
+/// versions.
+#[derive(Deserialize, Serialize)]
+struct Stored {
+    r#type: String,
+    parameters: serde_json::Value,
+}
+
+/// Parameter
+#[derive(Debug)]
+pub enum Parameter<'a> {
+    Integer(&'a mut i32),
+    /// Odd integer, such as a kernel size
+    Odd(&'a mut i32),
+    Float(&'a mut f64),
+}
+
+impl Parameter<'_> {
+    pub fn get(&self) -> f64 {
+        match self {
+            Self::Integer(value) | Self::Odd(value) => **value as _,
+            Self::Float(value) => **value,
+        }
+    }
+
+    pub fn set(&mut self, value: f64) {
+        match self {
+            Self::Integer(target) => **target = value.round() as _,
+            Self::Odd(target) => **target = ((value - 1.0) / 2.0).round() as i32 * 2 + 1,
+            Self::Float(target) => **target = value,
+        }
+    }
+
+    pub fn show(self, ui: &mut Ui) -> Response {
         match self {
-            Value::Matrix(matrix) => matrix.hash(state),
-            Value::Vector(vector) => {
-                for mat in vector {
-                    Matrix(mat).hash(state)
-                }
-            }
+            Self::Integer(value) => ui.add(DragValue::new(value).speed(1)),
+            Self::Odd(value) => ui.add(DragValue::new(value).speed(2)),
+            Self::Float(value) => ui.add(DragValue::new(value).speed(1.0)),
         }
     }
 }
 
-/// Sync [`Mat`]
+/// Pin
+#[derive(Clone, Debug, Deserialize, Eq, Hash, PartialEq, Serialize)]
+pub struct Pin {
+    pub name: Cow<'static, str>,
+    pub kind: Kind,
+}
+
+impl Pin {
+    pub const fn new(name: &'static str, kind: Kind) -> Self {
+        Self {
+            name: Cow::Borrowed(name),
+            kind,
+        }
+    }
+}
+
+/// Pin kind
+#[derive(Clone, Copy, Debug, Deserialize, Eq, Hash, PartialEq, Serialize)]
+pub enum Kind {
+    Matrix,
+    Contours,
+    Table,
+}
+
+/// Value
 #[derive(Clone, Debug, Default)]
-#[repr(transparent)]
-pub struct Matrix<T = Mat>(pub T);
+pub enum Value {
+    #[default]
+    None,
+    Matrix(Arc<SyncMat>),
+    Contours(Arc<Vec<SyncMat>>),
+    Table(Arc<Table>),
+}
 
-impl<T: MatTraitConst> Display for Matrix<T> {
-    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
-        f.debug_struct("Matrix")
-            .field(
-                "type",
-                &type_to_string(self.0.typ()).map_err(|_| fmt::Error)?,
-            )
-            .field("rows", &self.rows())
-            .field("columns", &self.cols())
-            .finish()
+impl Value {
+    pub fn matrix(matrix: Mat) -> Self {
+        Self::Matrix(Arc::new(SyncMat(matrix)))
+    }
+
+    pub fn contours(contours: impl IntoIterator<Item = Mat>) -> Self {
+        Self::Contours(Arc::new(contours.into_iter().map(SyncMat).collect()))
+    }
+
+    pub fn kind(&self) -> Option<Kind> {
+        match self {
+            Self::None => None,
+            Self::Matrix(_) => Some(Kind::Matrix),
+            Self::Contours(_) => Some(Kind::Contours),
+            Self::Table(_) => Some(Kind::Table),
+        }
+    }
+
+    pub fn as_matrix(&self) -> Option<&SyncMat> {
+        match self {
+            Self::Matrix(matrix) => Some(matrix),
+            _ => None,
+        }
+    }
+
+    pub fn as_contours(&self) -> Option<&[SyncMat]> {
+        match self {
+            Self::Contours(contours) => Some(contours),
+            _ => None,
+        }
+    }
+
+    pub fn as_table(&self) -> Option<&Table> {
+        match self {
+            Self::Table(table) => Some(table),
+            _ => None,
+        }
     }
 }
 
-impl Hash for Matrix<Mat> {
-    fn hash<H: Hasher>(&self, state: &mut H) {
-        self.0.data_bytes().ok().hash(state)
+impl Display for Value {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        match self {
+            Self::None => f.write_str("None"),
+            Self::Matrix(matrix) => Display::fmt(matrix, f),
+            Self::Contours(contours) => f
+                .debug_struct("Contours")
+                .field("count", &contours.len())
+                .finish(),
+            Self::Table(table) => f
+                .debug_struct("Table")
+                .field("rows", &table.rows.len())
+                .field("columns", &table.header.len())
+                .finish(),
+        }
     }
 }
 
-impl Hash for Matrix<&Mat> {
+impl Hash for Value {
     fn hash<H: Hasher>(&self, state: &mut H) {
-        self.0.data_bytes().ok().hash(state)
+        match self {
+            Self::None => {}
+            Self::Matrix(matrix) => matrix.hash(state),
+            Self::Contours(contours) => contours.hash(state),
+            Self::Table(table) => table.hash(state),
+        }
     }
 }
 
-unsafe impl<T> Sync for Matrix<T> {}
+/// Table
+#[derive(Clone, Debug, Default, Hash)]
+pub struct Table {
+    pub header: Vec<String>,
+    pub rows: Vec<Vec<String>>,
+}
 
-impl<T: MatTraitConst> MatTraitConst for Matrix<T> {
-    fn as_raw_Mat(&self) -> *const c_void {
-        self.0.as_raw_Mat()
+impl Table {
+    /// Parses comma separated values, the first line is the header
+    pub fn from_csv(text: &str) -> Self {
+        let mut lines = text
+            .lines()
+            .filter(|line| !line.trim().is_empty())
+            .map(|line| line.split(',').map(|cell| cell.trim().to_owned()).collect());
+        Self {
+            header: lines.next().unwrap_or_default(),
+            rows: lines.collect(),
+        }
     }
 }
 
-impl<T: ToInputArray> ToInputArray for Matrix<T> {
-    fn input_array(&self) -> Result<BoxedRef<_InputArray>> {
-        self.0.input_array()
-    }
+/// Returns the matrix connected to the input with the given index
+pub fn matrix(inputs: &[Value], index: usize) -> Result<&SyncMat> {
+    inputs
+        .get(index)
+        .and_then(Value::as_matrix)
+        .ok_or(Error::Input { index })
+}
+
+/// Returns the contours connected to the input with the given index
+pub fn contours(inputs: &[Value], index: usize) -> Result<&[SyncMat]> {
+    inputs
+        .get(index)
+        .and_then(Value::as_contours)
+        .ok_or(Error::Input { index })
 }
 
 /// Point
@@ -140,14 +437,18 @@ pub struct Point {
     pub y: i32,
 }
 
+mod command;
 mod convert_color;
 mod convex_hull;
 mod dilate;
 mod draw_contours;
+mod error;
 mod find_contours;
 mod greater_than;
 mod median_blur;
 mod read;
+mod registry;
+mod subgraph;
 mod subtract;
 mod threshold;
 mod write;
@@ -1,8 +1,91 @@
+use super::{Kind, NodeType, Parameter, Pin, Result, Table, Value};
+use crate::read::{Mode, Sequence};
+use egui::{ComboBox, Ui};
 use serde::{Deserialize, Serialize};
-use std::path::PathBuf;
+use std::{borrow::Cow, path::PathBuf};
 
 /// Read
+///
+/// Reads a frame of a file, a multi-page TIFF, a directory or a pattern such
+/// as `images/*.tif`. The frame parameter can be swept to go through all of
+/// them, the frames output lists them.
 #[derive(Clone, Debug, Default, Deserialize, Hash, Serialize)]
+#[serde(default)]
 pub struct Read {
     pub path: PathBuf,
+    pub mode: Mode,
+    pub frame: i32,
+}
+
+impl NodeType for Read {
+    const NAME: &'static str = "Read";
+    const TITLE: &'static str = "Read";
+    const CATEGORY: &'static str = "Codecs";
+    const INPUTS: &'static [Pin] = &[];
+    const OUTPUTS: &'static [Pin] = &[
+        Pin::new("image", Kind::Matrix),
+        Pin::new("frames", Kind::Table),
+    ];
+
+    fn parameters(&mut self) -> Vec<(Cow<'static, str>, Parameter<'_>)> {
+        vec![("frame".into(), Parameter::Integer(&mut self.frame))]
+    }
+
+    fn compute(&self, _inputs: &[Value]) -> Result<Vec<Value>> {
+        let sequence = Sequence::new(&self.path, self.mode)?;
+        let image = sequence.read(self.frame.max(0) as _)?;
+        let frames = Table {
+            header: vec!["frame".to_owned(), "path".to_owned(), "page".to_owned()],
+            rows: (0..sequence.len())
+                .filter_map(|index| {
+                    let (path, page) = sequence.frame(index)?;
+                    Some(vec![
+                        index.to_string(),
+                        path.display().to_string(),
+                        page.to_string(),
+                    ])
+                })
+                .collect(),
+        };
+        Ok(vec![Value::matrix(image), Value::Table(frames.into())])
+    }
+
+    fn show_body(&mut self, ui: &mut Ui, _inputs: &[Value]) {
+        ui.vertical(|ui| {
+            // Path
+            ui.horizontal(|ui| {
+                ui.label("Path:");
+                let mut text = self.path.to_string_lossy();
+                if ui
+                    .text_edit_singleline(&mut text)
+                    .on_hover_text("File, directory or pattern with `*` and `?`")
+                    .changed()
+                {
+                    self.path = PathBuf::from(&*text);
+                }
+            });
+            // Mode
+            ui.horizontal(|ui| {
+                ui.label("Mode:");
+                ComboBox::from_id_source("mode")
+                    .selected_text(format!("{:?}", self.mode))
+                    .show_ui(ui, |ui| {
+                        for mode in [
+                            Mode::Color,
+                            Mode::Grayscale,
+                            Mode::AnyDepth,
+                            Mode::Unchanged,
+                        ] {
+                            ui.selectable_value(&mut self.mode, mode, format!("{mode:?}"));
+                        }
+                    });
+            });
+            // Frame
+            ui.horizontal(|ui| {
+                ui.label("Frame:");
+                Parameter::Integer(&mut self.frame).show(ui);
+                self.frame = self.frame.max(0);
+            });
+        });
+    }
 }
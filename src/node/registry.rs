@@ -0,0 +1,117 @@
+use super::{
+    Command, ConvertColor, ConvexHull, Dilate, DrawContours, FindContours, GreaterThan, MedianBlur,
+    Node, NodeType, Read, Subtract, Threshold, Write,
+};
+use anyhow::Result;
+use itertools::Itertools;
+use serde_json::Value;
+use std::sync::{OnceLock, RwLock};
+
+/// Returns the global registry, initialized with the builtin nodes
+pub fn registry() -> &'static RwLock<Registry> {
+    static REGISTRY: OnceLock<RwLock<Registry>> = OnceLock::new();
+    REGISTRY.get_or_init(|| RwLock::new(Registry::builtin()))
+}
+
+/// Registers a node type in the global registry
+pub fn register<T: NodeType>() {
+    registry()
+        .write()
+        .unwrap_or_else(|error| error.into_inner())
+        .register::<T>();
+}
+
+/// Registry
+#[derive(Clone, Debug, Default)]
+pub struct Registry {
+    entries: Vec<Entry>,
+}
+
+impl Registry {
+    pub fn builtin() -> Self {
+        let mut registry = Self::default();
+        // codecs
+        registry.register::<Read>();
+        registry.register::<Write>();
+        // proc
+        registry.register::<ConvertColor>();
+        registry.register::<ConvexHull>();
+        registry.register::<Dilate>();
+        registry.register::<DrawContours>();
+        registry.register::<FindContours>();
+        registry.register::<GreaterThan>();
+        registry.register::<MedianBlur>();
+        registry.register::<Subtract>();
+        registry.register::<Threshold>();
+        // external
+        registry.register::<Command>();
+        // library
+        registry.register::<Subgraph>();
+        registry
+    }
+
+    /// Registers a node type, replacing a previous one with the same name
+    pub fn register<T: NodeType>(&mut self) {
+        let entry = Entry::new::<T>();
+        match self.entries.iter_mut().find(|other| other.name == T::NAME) {
+            Some(other) => *other = entry,
+            None => self.entries.push(entry),
+        }
+    }
+
+    pub fn get(&self, name: &str) -> Option<&Entry> {
+        self.entries.iter().find(|entry| entry.name == name)
+    }
+
+    pub fn entries(&self) -> &[Entry] {
+        &self.entries
+    }
+
+    /// Categories of the graph menu in registration order
+    pub fn categories(&self) -> impl Iterator<Item = &'static str> + '_ {
+        self.entries
+            .iter()
+            .filter(|entry| entry.menu)
+            .map(|entry| entry.category)
+            .unique()
+    }
+}
+
+/// Registry entry
+#[derive(Clone, Copy, Debug)]
+pub struct Entry {
+    pub name: &'static str,
+    pub title: &'static str,
+    pub category: &'static str,
+    pub menu: bool,
+    new: fn() -> Node,
+    load: fn(Value) -> Result<Node>,
+}
+
+impl Entry {
+    fn new<T: NodeType>() -> Self {
+        Self {
+            name: T::NAME,
+            title: T::TITLE,
+            category: T::CATEGORY,
+            menu: T::MENU,
+            new: || Node::new(T::default()),
+            load: |parameters| {
+                Ok(Node::new(match parameters {
+                    Value::String(parameters) => ron::from_str::<T>(&parameters)?,
+                    parameters => serde_json::from_value::<T>(parameters)?,
+                }))
+            },
+        }
+    }
+
+    /// Creates a node with default parameters
+    pub fn node(&self) -> Node {
+        (self.new)()
+    }
+
+    /// Creates a node from serialized parameters
+    pub fn load(&self, parameters: Value) -> Result<Node> {
+        (self.load)(parameters)
+    }
+}
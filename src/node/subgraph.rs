@@ -0,0 +1,357 @@
+use super::{Error, Node, NodeType, Parameter, Pin, Result, Value};
+use anyhow::Result as AnyResult;
+use egui::{CollapsingHeader, Pos2, Ui};
+use egui_snarl::{InPinId, NodeId, OutPinId, Snarl};
+use ron::{
+    de::from_reader,
+    ser::{to_writer_pretty, PrettyConfig},
+};
+use serde::{Deserialize, Serialize};
+use std::{
+    borrow::Cow,
+    collections::{HashMap, HashSet},
+    fs::File,
+    path::Path,
+};
+
+/// Subgraph
+///
+/// A group of nodes collapsed into one. Nodes are stored in topological order,
+/// so they are computed one after another.
+#[derive(Clone, Debug, Default, Deserialize, Hash, Serialize)]
+pub struct Subgraph {
+    pub name: String,
+    pub nodes: Vec<Node>,
+    pub wires: Vec<(Endpoint, Endpoint)>,
+    pub inputs: Vec<Exposed>,
+    pub outputs: Vec<Exposed>,
+    /// Parameters shown in the body
+    pub parameters: Vec<Promoted>,
+}
+
+impl Subgraph {
+    /// Collapses the nodes into a subgraph node inserted at the position,
+    /// wires to the rest of the graph are preserved
+    pub(crate) fn collapse(
+        snarl: &mut Snarl<Node>,
+        ids: &HashSet<NodeId>,
+        pos: Pos2,
+    ) -> Option<NodeId> {
+        let wires = snarl.wires().collect::<Vec<_>>();
+        // Topological order
+        let mut order = Vec::with_capacity(ids.len());
+        let mut pending = ids.clone();
+        while !pending.is_empty() {
+            let ready = pending
+                .iter()
+                .copied()
+                .filter(|&id| {
+                    !wires
+                        .iter()
+                        .any(|(from, to)| to.node == id && pending.contains(&from.node))
+                })
+                .collect::<Vec<_>>();
+            if ready.is_empty() {
+                return None;
+            }
+            for id in ready {
+                pending.remove(&id);
+                order.push(id);
+            }
+        }
+        let indices = order
+            .iter()
+            .enumerate()
+            .map(|(index, &id)| (id, index))
+            .collect::<HashMap<_, _>>();
+        let mut subgraph = Self {
+            name: "Subgraph".to_owned(),
+            ..Default::default()
+        };
+        let mut external = (Vec::new(), Vec::new());
+        for &id in &order {
+            let node = snarl.get_node(id)?;
+            let title = node.title();
+            // Inputs which are not fed from inside are exposed
+            for (input, pin) in node.inputs().iter().enumerate() {
+                let from = wires
+                    .iter()
+                    .find(|(_, to)| to.node == id && to.input == input)
+                    .map(|&(from, _)| from);
+                if from.is_some_and(|from| ids.contains(&from.node)) {
+                    continue;
+                }
+                if let Some(from) = from {
+                    external.0.push((from, subgraph.inputs.len()));
+                }
+                subgraph.inputs.push(Exposed {
+                    endpoint: Endpoint {
+                        node: indices[&id],
+                        pin: input,
+                    },
+                    pin: Pin {
+                        name: format!("{title}.{}", pin.name).into(),
+                        kind: pin.kind,
+                    },
+                });
+            }
+            // Outputs which are used outside or not used at all are exposed
+            for (output, pin) in node.outputs().iter().enumerate() {
+                let to = wires
+                    .iter()
+                    .filter(|(from, _)| from.node == id && from.output == output)
+                    .map(|&(_, to)| to)
+                    .collect::<Vec<_>>();
+                for to in &to {
+                    if let Some(&node) = indices.get(&to.node) {
+                        subgraph.wires.push((
+                            Endpoint {
+                                node: indices[&id],
+                                pin: output,
+                            },
+                            Endpoint {
+                                node,
+                                pin: to.input,
+                            },
+                        ));
+                    }
+                }
+                let outside = to
+                    .iter()
+                    .filter(|to| !ids.contains(&to.node))
+                    .copied()
+                    .collect::<Vec<_>>();
+                if to.is_empty() || !outside.is_empty() {
+                    for to in outside {
+                        external.1.push((subgraph.outputs.len(), to));
+                    }
+                    subgraph.outputs.push(Exposed {
+                        endpoint: Endpoint {
+                            node: indices[&id],
+                            pin: output,
+                        },
+                        pin: Pin {
+                            name: format!("{title}.{}", pin.name).into(),
+                            kind: pin.kind,
+                        },
+                    });
+                }
+            }
+            subgraph.nodes.push(node.clone());
+        }
+        for &id in &order {
+            snarl.remove_node(id);
+        }
+        let id = snarl.insert_node(pos, Node::new(subgraph));
+        for (from, input) in external.0 {
+            snarl.connect(from, InPinId { node: id, input });
+        }
+        for (output, to) in external.1 {
+            snarl.connect(OutPinId { node: id, output }, to);
+        }
+        Some(id)
+    }
+
+    fn parameter(&mut self, promoted: &Promoted) -> Option<Parameter<'_>> {
+        self.nodes
+            .get_mut(promoted.node)?
+            .parameters()
+            .into_iter()
+            .find(|(name, _)| *name == promoted.name)
+            .map(|(_, parameter)| parameter)
+    }
+}
+
+impl NodeType for Subgraph {
+    const NAME: &'static str = "Subgraph";
+    const TITLE: &'static str = "Subgraph";
+    const CATEGORY: &'static str = "Library";
+    const MENU: bool = false;
+    const INPUTS: &'static [Pin] = &[];
+    const OUTPUTS: &'static [Pin] = &[];
+
+    fn title(&self) -> Cow<'static, str> {
+        Cow::Owned(self.name.clone())
+    }
+
+    fn inputs(&self) -> Cow<'static, [Pin]> {
+        self.inputs
+            .iter()
+            .map(|exposed| exposed.pin.clone())
+            .collect()
+    }
+
+    fn outputs(&self) -> Cow<'static, [Pin]> {
+        self.outputs
+            .iter()
+            .map(|exposed| exposed.pin.clone())
+            .collect()
+    }
+
+    fn parameters(&mut self) -> Vec<(Cow<'static, str>, Parameter<'_>)> {
+        // Prefixed by the inner node, whose parameters may share names
+        self.nodes
+            .iter_mut()
+            .enumerate()
+            .flat_map(|(index, node)| {
+                let title = node.title();
+                node.parameters().into_iter().map(move |(name, parameter)| {
+                    (Cow::Owned(format!("{title}#{index}.{name}")), parameter)
+                })
+            })
+            .collect()
+    }
+
+    fn compute(&self, inputs: &[Value]) -> Result<Vec<Value>> {
+        // Loaded from a library, so the references may be broken
+        if let Some(promoted) = self
+            .parameters
+            .iter()
+            .find(|promoted| promoted.node >= self.nodes.len())
+        {
+            return Err(Error::Node {
+                index: promoted.node,
+            });
+        }
+        let mut results = Vec::<Vec<Value>>::with_capacity(self.nodes.len());
+        for (index, node) in self.nodes.iter().enumerate() {
+            let mut node = node.clone();
+            for (input, exposed) in self.inputs.iter().enumerate() {
+                if exposed.endpoint.node == index {
+                    let value = inputs.get(input).cloned().unwrap_or_default();
+                    node.set_value(exposed.endpoint.pin, value);
+                }
+            }
+            for (from, to) in &self.wires {
+                if to.node == index {
+                    let value = results
+                        .get(from.node)
+                        .and_then(|outputs| outputs.get(from.pin))
+                        .cloned()
+                        .unwrap_or_default();
+                    node.set_value(to.pin, value);
+                }
+            }
+            results.push(node.compute()?);
+        }
+        self.outputs
+            .iter()
+            .enumerate()
+            .map(|(index, exposed)| {
+                results
+                    .get(exposed.endpoint.node)
+                    .and_then(|outputs| outputs.get(exposed.endpoint.pin))
+                    .cloned()
+                    .ok_or(Error::Output { index })
+            })
+            .collect()
+    }
+
+    fn show_body(&mut self, ui: &mut Ui, _inputs: &[Value]) {
+        ui.vertical(|ui| {
+            // Name
+            ui.horizontal(|ui| {
+                ui.label("Name:");
+                ui.text_edit_singleline(&mut self.name);
+            });
+            // Promoted parameters
+            // Broken references are reported by `compute`
+            for promoted in self.parameters.clone() {
+                let Some(title) = self.nodes.get(promoted.node).map(Node::title) else {
+                    continue;
+                };
+                if let Some(parameter) = self.parameter(&promoted) {
+                    ui.horizontal(|ui| {
+                        ui.label(format!("{title}.{}:", promoted.name));
+                        parameter.show(ui);
+                    });
+                }
+            }
+            // Promotion
+            CollapsingHeader::new("Promote").show(ui, |ui| {
+                for node in 0..self.nodes.len() {
+                    let title = self.nodes[node].title();
+                    let names = self.nodes[node]
+                        .parameters()
+                        .into_iter()
+                        .map(|(name, _)| name)
+                        .collect::<Vec<_>>();
+                    for name in names {
+                        let position = self
+                            .parameters
+                            .iter()
+                            .position(|promoted| promoted.node == node && promoted.name == name);
+                        let mut checked = position.is_some();
+                        if ui
+                            .checkbox(&mut checked, format!("{title}.{name}"))
+                            .changed()
+                        {
+                            match position {
+                                Some(position) => {
+                                    self.parameters.remove(position);
+                                }
+                                None => self.parameters.push(Promoted {
+                                    node,
+                                    name: name.into_owned(),
+                                }),
+                            }
+                        }
+                    }
+                }
+            });
+        });
+    }
+}
+
+/// Endpoint of a wire inside a subgraph
+#[derive(Clone, Copy, Debug, Default, Deserialize, Hash, Serialize)]
+pub struct Endpoint {
+    pub node: usize,
+    pub pin: usize,
+}
+
+/// Exposed pin
+#[derive(Clone, Debug, Deserialize, Hash, Serialize)]
+pub struct Exposed {
+    pub endpoint: Endpoint,
+    pub pin: Pin,
+}
+
+/// Promoted parameter
+#[derive(Clone, Debug, Default, Deserialize, Hash, Serialize)]
+pub struct Promoted {
+    pub node: usize,
+    pub name: String,
+}
+
+/// Library of subgraphs
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+pub struct Library {
+    pub subgraphs: Vec<Subgraph>,
+}
+
+impl Library {
+    pub fn load(path: &Path) -> AnyResult<Self> {
+        if !path.exists() {
+            return Ok(Default::default());
+        }
+        Ok(from_reader(File::open(path)?)?)
+    }
+
+    pub fn save(&self, path: &Path) -> AnyResult<()> {
+        to_writer_pretty(File::create(path)?, self, PrettyConfig::new())?;
+        Ok(())
+    }
+
+    /// Adds the subgraph, replacing one with the same name
+    pub fn add(&mut self, subgraph: Subgraph) {
+        match self
+            .subgraphs
+            .iter_mut()
+            .find(|other| other.name == subgraph.name)
+        {
+            Some(other) => *other = subgraph,
+            None => self.subgraphs.push(subgraph),
+        }
+    }
+}
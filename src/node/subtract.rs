@@ -1,34 +1,24 @@
-use crate::{
-    utils::SyncMat,
-    view::{View, RED, UNTYPED_COLOR},
-};
-use egui::Ui;
-use egui_snarl::{ui::PinInfo, InPin};
+use super::{matrix, Kind, NodeType, Pin, Result, Value};
+use opencv::core::{subtract_def, Mat};
 use serde::{Deserialize, Serialize};
-use std::sync::Arc;
 
 /// Subtract
 #[derive(Clone, Debug, Default, Deserialize, Hash, Serialize)]
-pub struct Subtract {
-    #[serde(skip)]
-    pub src1: Arc<SyncMat>,
-    #[serde(skip)]
-    pub src2: Arc<SyncMat>,
-}
+pub struct Subtract {}
+
+impl NodeType for Subtract {
+    const NAME: &'static str = "Subtract";
+    const TITLE: &'static str = "Subtract";
+    const CATEGORY: &'static str = "Proc";
+    const INPUTS: &'static [Pin] = &[
+        Pin::new("src1", Kind::Matrix),
+        Pin::new("src2", Kind::Matrix),
+    ];
+    const OUTPUTS: &'static [Pin] = &[Pin::new("dst", Kind::Matrix)];
 
-impl View for Subtract {
-    fn show_input(&mut self, ui: &mut Ui, pin: &InPin) -> PinInfo {
-        match pin.id.input {
-            0 | 1 if pin.remotes.is_empty() => PinInfo::square().with_fill(UNTYPED_COLOR),
-            0 => {
-                ui.label(self.src1.to_string());
-                PinInfo::square().with_fill(RED)
-            }
-            1 => {
-                ui.label(self.src2.to_string());
-                PinInfo::square().with_fill(RED)
-            }
-            _ => unreachable!("Subtract node has 2 inputs"),
-        }
+    fn compute(&self, inputs: &[Value]) -> Result<Vec<Value>> {
+        let mut dst = Mat::default();
+        subtract_def(matrix(inputs, 0)?, matrix(inputs, 1)?, &mut dst)?;
+        Ok(vec![Value::matrix(dst)])
     }
 }
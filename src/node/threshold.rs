@@ -1,37 +1,49 @@
-use crate::{
-    utils::SyncMat,
-    view::{View, RED, UNTYPED_COLOR},
-};
+use super::{matrix, Kind, NodeType, Parameter, Pin, Result, Value};
 use egui::{epaint::util::FloatOrd, DragValue, Ui};
-use egui_snarl::{ui::PinInfo, InPin};
+use opencv::{
+    core::Mat,
+    imgproc::{threshold, THRESH_BINARY_INV, THRESH_OTSU},
+};
 use serde::{Deserialize, Serialize};
 use std::{
+    borrow::Cow,
     hash::{Hash, Hasher},
-    sync::Arc,
 };
 
 /// Threshold
 #[derive(Clone, Debug, Deserialize, Serialize)]
 pub struct Threshold {
-    #[serde(skip)]
-    pub src: Arc<SyncMat>,
     pub thresh: f64,
     pub maxval: f64,
 }
 
-impl View for Threshold {
-    fn show_input(&mut self, ui: &mut Ui, pin: &InPin) -> PinInfo {
-        match pin.id.input {
-            0 if pin.remotes.is_empty() => PinInfo::square().with_fill(UNTYPED_COLOR),
-            0 => {
-                ui.label(self.src.to_string());
-                PinInfo::square().with_fill(RED)
-            }
-            _ => unreachable!("Threshold node has 1 input"),
-        }
+impl NodeType for Threshold {
+    const NAME: &'static str = "Threshold";
+    const TITLE: &'static str = "Threshold";
+    const CATEGORY: &'static str = "Proc";
+    const INPUTS: &'static [Pin] = &[Pin::new("src", Kind::Matrix)];
+    const OUTPUTS: &'static [Pin] = &[Pin::new("dst", Kind::Matrix)];
+
+    fn parameters(&mut self) -> Vec<(Cow<'static, str>, Parameter<'_>)> {
+        vec![
+            ("thresh".into(), Parameter::Float(&mut self.thresh)),
+            ("maxval".into(), Parameter::Float(&mut self.maxval)),
+        ]
+    }
+
+    fn compute(&self, inputs: &[Value]) -> Result<Vec<Value>> {
+        let mut dst = Mat::default();
+        threshold(
+            matrix(inputs, 0)?,
+            &mut dst,
+            self.thresh,
+            self.maxval,
+            THRESH_BINARY_INV | THRESH_OTSU,
+        )?;
+        Ok(vec![Value::matrix(dst)])
     }
 
-    fn show_body(&mut self, ui: &mut Ui) {
+    fn show_body(&mut self, ui: &mut Ui, _inputs: &[Value]) {
         // Thresh
         ui.add(
             DragValue::new(&mut self.thresh)
@@ -52,7 +64,6 @@ impl View for Threshold {
 impl Default for Threshold {
     fn default() -> Self {
         Self {
-            src: Default::default(),
             thresh: 0.0,
             maxval: 255.0,
         }
@@ -61,7 +72,6 @@ impl Default for Threshold {
 
 impl Hash for Threshold {
     fn hash<H: Hasher>(&self, state: &mut H) {
-        self.src.hash(state);
         self.thresh.ord().hash(state);
         self.maxval.ord().hash(state);
     }
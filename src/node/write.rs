@@ -1,37 +1,40 @@
-use super::Value;
-use crate::cache::WriteCache;
-use crate::{
-    utils::SyncMat,
-    view::{View, RED, UNTYPED_COLOR},
-};
-use egui::{Id, Ui};
-use egui_snarl::{ui::PinInfo, InPin};
+use super::{matrix, Kind, NodeType, Pin, Result, Value};
+use egui::Ui;
+use opencv::imgcodecs::imwrite_def;
 use serde::{Deserialize, Serialize};
 use std::path::PathBuf;
-use std::sync::Arc;
 use tracing::error;
 
 /// Write
 #[derive(Clone, Debug, Default, Deserialize, Hash, Serialize)]
 pub struct Write {
-    #[serde(skip)]
-    pub img: Arc<SyncMat>,
     pub path: PathBuf,
+    pub auto_save: bool,
 }
 
-impl View for Write {
-    fn show_input(&mut self, ui: &mut Ui, pin: &InPin) -> PinInfo {
-        match pin.id.input {
-            0 if pin.remotes.is_empty() => PinInfo::square().with_fill(UNTYPED_COLOR),
-            0 => {
-                ui.label(self.img.to_string());
-                PinInfo::square().with_fill(RED)
-            }
-            _ => unreachable!("Write node has 1 input"),
+impl Write {
+    fn write(&self, inputs: &[Value]) -> Result<()> {
+        let filename = &*self.path.to_string_lossy();
+        imwrite_def(filename, matrix(inputs, 0)?)?;
+        Ok(())
+    }
+}
+
+impl NodeType for Write {
+    const NAME: &'static str = "Write";
+    const TITLE: &'static str = "Write";
+    const CATEGORY: &'static str = "Codecs";
+    const INPUTS: &'static [Pin] = &[Pin::new("img", Kind::Matrix)];
+    const OUTPUTS: &'static [Pin] = &[];
+
+    fn compute(&self, inputs: &[Value]) -> Result<Vec<Value>> {
+        if self.auto_save {
+            self.write(inputs)?;
         }
+        Ok(Vec::new())
     }
 
-    fn show_body(&mut self, ui: &mut Ui) {
+    fn show_body(&mut self, ui: &mut Ui, inputs: &[Value]) {
         // Path
         ui.horizontal(|ui| {
             ui.label("Path:");
@@ -42,21 +45,12 @@ impl View for Write {
         });
         // Save
         ui.horizontal(|ui| {
-            let clicked = ui.button("Save").clicked();
-            let id = Id::new("auto_save");
-            let mut checked = ui
-                .data_mut(|data| data.get_persisted(id))
-                .unwrap_or_default();
-            if ui.checkbox(&mut checked, "Auto save").changed() {
-                ui.data_mut(|data| data.insert_persisted(id, checked))
-            }
-            if clicked || checked {
-                if let Err(error) =
-                    ui.memory_mut(|memory| memory.caches.cache::<WriteCache>().get(self))
-                {
+            if ui.button("Save").clicked() {
+                if let Err(error) = self.write(inputs) {
                     error!(%error);
                 }
             }
+            ui.checkbox(&mut self.auto_save, "Auto save");
         });
     }
 }
@@ -0,0 +1,225 @@
+use anyhow::Result;
+use cv::{Contour, MomentsExt};
+use opencv::{
+    core::{
+        bitwise_and_def, bitwise_or_def, count_non_zero, no_array, Mat, Point, Point2f, Rect,
+        RotatedRect, Scalar, Size, Vector, CV_8UC1,
+    },
+    imgproc::{bounding_rect, draw_contours, ellipse_2_poly, FILLED, LINE_8},
+    prelude::*,
+};
+use serde::{Deserialize, Serialize};
+use std::f64::consts::PI;
+
+/// Object
+///
+/// A contour with its fitted shapes.
+#[derive(Clone, Debug)]
+pub struct Object {
+    pub contour: Mat,
+    pub area: f64,
+    pub perimeter: f64,
+    pub centroid: Point2f,
+    pub bounding_rectangle: Rect,
+    pub rotated_rectangle: RotatedRect,
+    pub min_circumcircle: Circle,
+    pub max_incircle: Circle,
+    pub incircle: Circle,
+    /// Class assigned by a classifier
+    pub class: Option<String>,
+}
+
+impl Object {
+    pub fn new(contour: Mat) -> Result<Self> {
+        let moments = contour.moments(false)?;
+        let centroid = moments.centroid();
+        let min_circumcircle = contour.min_circumcircle()?;
+        let max_incircle = contour.max_incircle()?;
+        let incircle = contour.incircle(centroid)?;
+        Ok(Self {
+            area: contour.area()?,
+            perimeter: contour.perimeter(true)?,
+            centroid: Point2f::new(centroid.x as _, centroid.y as _),
+            bounding_rectangle: contour.bounding_rectangle()?,
+            rotated_rectangle: contour.rotated_rectangle()?,
+            min_circumcircle: Circle {
+                center: Point2f::new(
+                    min_circumcircle.center.x as _,
+                    min_circumcircle.center.y as _,
+                ),
+                radius: min_circumcircle.radius as _,
+            },
+            max_incircle: Circle {
+                center: Point2f::new(max_incircle.center.x as _, max_incircle.center.y as _),
+                radius: max_incircle.radius as _,
+            },
+            incircle: Circle {
+                center: Point2f::new(incircle.center.x as _, incircle.center.y as _),
+                radius: incircle.radius as _,
+            },
+            class: None,
+            contour,
+        })
+    }
+
+    /// Objects of the contours
+    pub fn from_contours(contours: &Vector<Mat>) -> Result<Vec<Self>> {
+        contours.iter().map(Self::new).collect()
+    }
+}
+
+/// Ratio of the area of the contour to the area of its convex hull
+pub fn solidity(contour: &Mat) -> Result<f64> {
+    let hull = contour.convex_hull()?.area()?;
+    Ok(if hull > 0.0 {
+        contour.area()? / hull
+    } else {
+        0.0
+    })
+}
+
+/// Circle
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Circle {
+    pub center: Point2f,
+    pub radius: f32,
+}
+
+impl Circle {
+    /// Polygon approximating the circle, in the contour format
+    pub fn contour(&self) -> Result<Mat> {
+        let mut points = Vector::<Point>::new();
+        ellipse_2_poly(
+            Point::new(self.center.x.round() as _, self.center.y.round() as _),
+            Size::new(self.radius.round() as _, self.radius.round() as _),
+            0,
+            0,
+            360,
+            5,
+            &mut points,
+        )?;
+        Ok(Mat::from_exact_iter(points.into_iter())?)
+    }
+
+    /// Intersection over union of two circles
+    pub fn iou(&self, other: &Self) -> f64 {
+        let (r1, r2) = (self.radius as f64, other.radius as f64);
+        let d = ((self.center.x - other.center.x) as f64)
+            .hypot((self.center.y - other.center.y) as f64);
+        let intersection = if d >= r1 + r2 {
+            0.0
+        } else if d <= (r1 - r2).abs() {
+            PI * r1.min(r2).powi(2)
+        } else {
+            let a1 = ((d * d + r1 * r1 - r2 * r2) / (2.0 * d * r1))
+                .clamp(-1.0, 1.0)
+                .acos();
+            let a2 = ((d * d + r2 * r2 - r1 * r1) / (2.0 * d * r2))
+                .clamp(-1.0, 1.0)
+                .acos();
+            let triangle = 0.5
+                * ((-d + r1 + r2) * (d + r1 - r2) * (d - r1 + r2) * (d + r1 + r2))
+                    .max(0.0)
+                    .sqrt();
+            r1 * r1 * a1 + r2 * r2 * a2 - triangle
+        };
+        let union = PI * (r1 * r1 + r2 * r2) - intersection;
+        if union > 0.0 {
+            intersection / union
+        } else {
+            0.0
+        }
+    }
+}
+
+/// Pair of matched contours
+#[derive(Clone, Copy, Debug, Default, Deserialize, Serialize)]
+pub struct Pair {
+    pub left: Option<usize>,
+    pub right: Option<usize>,
+    pub iou: f64,
+}
+
+/// Matches contours one to one, greedily by intersection over union, the
+/// unmatched ones are paired with `None`
+pub fn pairs(left: &[Mat], right: &[Mat], threshold: f64) -> Result<Vec<Pair>> {
+    let mut candidates = Vec::new();
+    for (i, a) in left.iter().enumerate() {
+        for (j, b) in right.iter().enumerate() {
+            let iou = iou(a, b)?;
+            if iou >= threshold && iou > 0.0 {
+                candidates.push((i, j, iou));
+            }
+        }
+    }
+    candidates.sort_by(|a, b| b.2.total_cmp(&a.2));
+    let mut matched = (vec![false; left.len()], vec![false; right.len()]);
+    let mut pairs = Vec::new();
+    for (i, j, iou) in candidates {
+        if !matched.0[i] && !matched.1[j] {
+            matched.0[i] = true;
+            matched.1[j] = true;
+            pairs.push(Pair {
+                left: Some(i),
+                right: Some(j),
+                iou,
+            });
+        }
+    }
+    for (i, _) in matched
+        .0
+        .iter()
+        .enumerate()
+        .filter(|(_, &matched)| !matched)
+    {
+        pairs.push(Pair {
+            left: Some(i),
+            ..Default::default()
+        });
+    }
+    for (j, _) in matched
+        .1
+        .iter()
+        .enumerate()
+        .filter(|(_, &matched)| !matched)
+    {
+        pairs.push(Pair {
+            right: Some(j),
+            ..Default::default()
+        });
+    }
+    Ok(pairs)
+}
+
+/// Intersection over union of two contours
+pub fn iou(a: &Mat, b: &Mat) -> Result<f64> {
+    let (ra, rb) = (bounding_rect(a)?, bounding_rect(b)?);
+    if (ra & rb).empty() {
+        return Ok(0.0);
+    }
+    let roi = ra | rb;
+    let fill = |contour: &Mat| -> Result<Mat> {
+        let mut mask = Mat::zeros(roi.height, roi.width, CV_8UC1)?.to_mat()?;
+        draw_contours(
+            &mut mask,
+            &Vector::<Mat>::from_iter([contour.clone()]),
+            -1,
+            Scalar::all(255.0),
+            FILLED,
+            LINE_8,
+            &no_array(),
+            i32::MAX,
+            Point::new(-roi.x, -roi.y),
+        )?;
+        Ok(mask)
+    };
+    let (a, b) = (fill(a)?, fill(b)?);
+    let (mut intersection, mut union) = (Mat::default(), Mat::default());
+    bitwise_and_def(&a, &b, &mut intersection)?;
+    bitwise_or_def(&a, &b, &mut union)?;
+    let union = count_non_zero(&union)?;
+    if union == 0 {
+        return Ok(0.0);
+    }
+    Ok(count_non_zero(&intersection)? as f64 / union as f64)
+}
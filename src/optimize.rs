@@ -0,0 +1,392 @@
+use crate::{
+    config::Criterion,
+    evaluate::{aggregate, Evaluation, Metrics},
+    object::Object,
+    pipeline::Pipeline,
+    Config,
+};
+use anyhow::Result;
+use opencv::{
+    core::Mat,
+    imgproc::{THRESH_OTSU, THRESH_TRIANGLE},
+};
+use serde::{Deserialize, Serialize};
+use std::{
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Mutex,
+    },
+    thread::{available_parallelism, scope},
+};
+
+/// Parameter
+#[derive(Clone, Copy, Debug, Deserialize, Eq, PartialEq, Serialize)]
+pub enum Parameter {
+    /// Fixed threshold, replaces Otsu's one
+    Threshold,
+    MinArea,
+    /// Median blur aperture size
+    Blur,
+    /// Dilation iterations
+    Dilation,
+    /// Number of k-means clusters, fixed
+    K,
+}
+
+impl Parameter {
+    fn is_integer(&self) -> bool {
+        !matches!(self, Self::Threshold | Self::MinArea)
+    }
+
+    /// Whether the pipeline reads the parameter
+    pub fn applies(&self, pipeline: Pipeline) -> bool {
+        match pipeline {
+            Pipeline::Seeds => !matches!(self, Self::K),
+            Pipeline::Algae => matches!(self, Self::MinArea | Self::K),
+        }
+    }
+
+    pub fn apply(&self, config: &mut Config, value: f64) {
+        match self {
+            Self::Threshold => {
+                config.threshold.thresh = value;
+                // A fixed threshold, keeping the other flags
+                config.threshold.r#type &= !(THRESH_OTSU | THRESH_TRIANGLE);
+            }
+            Self::MinArea => config.contours.min_area = value,
+            Self::Blur => config.blur.ksize = value.round() as i32 | 1,
+            Self::Dilation => config.dilation.iterations = value.round() as _,
+            Self::K => {
+                let k = (value.round() as usize).max(1);
+                config.kmeans.k = k;
+                config.algae.segmentation.criterion = Criterion::Fixed(k);
+            }
+        }
+    }
+}
+
+/// Dimension of the search space
+#[derive(Clone, Copy, Debug, Deserialize, Serialize)]
+pub struct Dimension {
+    pub parameter: Parameter,
+    pub min: f64,
+    pub max: f64,
+    /// Number of grid values
+    pub steps: usize,
+}
+
+impl Dimension {
+    fn value(&self, unit: f64) -> f64 {
+        let value = self.min + (self.max - self.min) * unit.clamp(0.0, 1.0);
+        if self.parameter.is_integer() {
+            value.round()
+        } else {
+            value
+        }
+    }
+
+    fn grid(&self) -> Vec<f64> {
+        let steps = self.steps.max(1);
+        let mut values = (0..steps)
+            .map(|step| self.value(step as f64 / (steps - 1).max(1) as f64))
+            .collect::<Vec<_>>();
+        values.dedup();
+        values
+    }
+}
+
+/// Search space
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct Space {
+    pub dimensions: Vec<Dimension>,
+}
+
+impl Space {
+    /// Default space of the pipeline
+    pub fn new(pipeline: Pipeline) -> Self {
+        let dimension = |parameter, min, max, steps| Dimension {
+            parameter,
+            min,
+            max,
+            steps,
+        };
+        let dimensions = match pipeline {
+            Pipeline::Seeds => vec![
+                dimension(Parameter::Threshold, 32.0, 224.0, 7),
+                dimension(Parameter::MinArea, 0.0, 1000.0, 5),
+                dimension(Parameter::Blur, 1.0, 9.0, 5),
+                dimension(Parameter::Dilation, 0.0, 3.0, 4),
+            ],
+            Pipeline::Algae => vec![
+                dimension(Parameter::MinArea, 0.0, 1000.0, 5),
+                dimension(Parameter::K, 2.0, 6.0, 5),
+            ],
+        };
+        Self { dimensions }
+    }
+}
+
+/// Search strategy
+#[derive(Clone, Copy, Debug, Default, Deserialize, Eq, PartialEq, Serialize)]
+pub enum Search {
+    /// All combinations of the grid values
+    #[default]
+    Grid,
+    /// Uniformly random points
+    Random,
+    /// Tree-structured Parzen estimator: random points first, then the
+    /// candidates most likely to be among the best trials
+    Bayesian,
+}
+
+/// Sample, an image with its ground truth
+#[derive(Clone, Debug)]
+pub struct Sample {
+    pub name: String,
+    pub image: Mat,
+    pub truth: Vec<Object>,
+}
+
+// Only read, so the workers share the samples like a `SyncMat`
+unsafe impl Sync for Sample {}
+
+/// Trial
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct Trial {
+    pub values: Vec<(Parameter, f64)>,
+    /// Aggregated F1 of the samples, `0` on error
+    pub score: f64,
+    pub metrics: Metrics,
+    pub error: Option<String>,
+}
+
+/// Optimizer
+#[derive(Clone, Debug)]
+pub struct Optimizer {
+    pub pipeline: Pipeline,
+    pub space: Space,
+    pub search: Search,
+    /// Number of trials of the random and bayesian searches
+    pub trials: usize,
+    /// Minimum intersection over union of matched objects
+    pub iou: f64,
+    pub seed: u64,
+}
+
+impl Optimizer {
+    /// Runs the trials in parallel, returns them ranked by score
+    pub fn run(&self, base: &Config, samples: &[Sample]) -> Vec<Trial> {
+        let mut random = Random::new(self.seed);
+        let mut trials = Vec::new();
+        match self.search {
+            Search::Grid => {
+                let points = self.grid();
+                trials.extend(self.evaluate(base, samples, &points));
+            }
+            Search::Random => {
+                let points = (0..self.trials)
+                    .map(|_| self.random(&mut random))
+                    .collect::<Vec<_>>();
+                trials.extend(self.evaluate(base, samples, &points));
+            }
+            Search::Bayesian => {
+                let threads = threads();
+                let startup = (self.trials / 4).max(threads).min(self.trials);
+                let points = (0..startup)
+                    .map(|_| self.random(&mut random))
+                    .collect::<Vec<_>>();
+                trials.extend(self.evaluate(base, samples, &points));
+                while trials.len() < self.trials {
+                    let batch = threads.min(self.trials - trials.len());
+                    let points = (0..batch)
+                        .map(|_| self.suggest(&trials, &mut random))
+                        .collect::<Vec<_>>();
+                    trials.extend(self.evaluate(base, samples, &points));
+                }
+            }
+        }
+        trials.sort_by(|a, b| b.score.total_cmp(&a.score));
+        trials
+    }
+
+    /// Config of the trial
+    pub fn config(&self, base: &Config, values: &[(Parameter, f64)]) -> Config {
+        let mut config = base.clone();
+        for &(parameter, value) in values {
+            parameter.apply(&mut config, value);
+        }
+        config
+    }
+
+    fn grid(&self) -> Vec<Vec<(Parameter, f64)>> {
+        let mut points = vec![Vec::new()];
+        for dimension in &self.space.dimensions {
+            points = points
+                .into_iter()
+                .flat_map(|point: Vec<_>| {
+                    dimension.grid().into_iter().map(move |value| {
+                        let mut point = point.clone();
+                        point.push((dimension.parameter, value));
+                        point
+                    })
+                })
+                .collect();
+        }
+        points
+    }
+
+    fn random(&self, random: &mut Random) -> Vec<(Parameter, f64)> {
+        self.space
+            .dimensions
+            .iter()
+            .map(|dimension| (dimension.parameter, dimension.value(random.next())))
+            .collect()
+    }
+
+    /// Suggests the candidate with the highest ratio of the densities of the
+    /// good and the bad trials
+    fn suggest(&self, trials: &[Trial], random: &mut Random) -> Vec<(Parameter, f64)> {
+        const CANDIDATES: usize = 24;
+        let mut ranked = trials.iter().collect::<Vec<_>>();
+        ranked.sort_by(|a, b| b.score.total_cmp(&a.score));
+        let split = (ranked.len() / 4).max(1);
+        let (good, bad) = ranked.split_at(split);
+        // Unit coordinates
+        let unit = |trial: &Trial| -> Vec<f64> {
+            self.space
+                .dimensions
+                .iter()
+                .zip(&trial.values)
+                .map(|(dimension, &(_, value))| {
+                    let range = dimension.max - dimension.min;
+                    if range == 0.0 {
+                        0.0
+                    } else {
+                        (value - dimension.min) / range
+                    }
+                })
+                .collect()
+        };
+        let good = good.iter().map(|trial| unit(trial)).collect::<Vec<_>>();
+        let bad = bad.iter().map(|trial| unit(trial)).collect::<Vec<_>>();
+        let bandwidth = 0.2 / (1.0 + trials.len() as f64).ln().max(1.0);
+        let density = |points: &[Vec<f64>], x: &[f64]| -> f64 {
+            points
+                .iter()
+                .map(|point| {
+                    let distance = point
+                        .iter()
+                        .zip(x)
+                        .map(|(a, b)| (a - b).powi(2))
+                        .sum::<f64>();
+                    (-distance / (2.0 * bandwidth * bandwidth)).exp()
+                })
+                .sum::<f64>()
+                / points.len().max(1) as f64
+                + f64::EPSILON
+        };
+        let mut best = (f64::NEG_INFINITY, Vec::new());
+        for _ in 0..CANDIDATES {
+            // Around a good trial
+            let center = &good[(random.next() * good.len() as f64) as usize % good.len()];
+            let x = center
+                .iter()
+                .map(|&value| (value + random.normal() * bandwidth).clamp(0.0, 1.0))
+                .collect::<Vec<_>>();
+            let ratio = density(&good, &x) / density(&bad, &x);
+            if ratio > best.0 {
+                best = (ratio, x);
+            }
+        }
+        self.space
+            .dimensions
+            .iter()
+            .zip(best.1)
+            .map(|(dimension, unit)| (dimension.parameter, dimension.value(unit)))
+            .collect()
+    }
+
+    /// Evaluates the points in parallel, the workers share the samples
+    fn evaluate(
+        &self,
+        base: &Config,
+        samples: &[Sample],
+        points: &[Vec<(Parameter, f64)>],
+    ) -> Vec<Trial> {
+        let next = AtomicUsize::new(0);
+        let trials = Mutex::new(Vec::with_capacity(points.len()));
+        scope(|scope| {
+            for _ in 0..threads().min(points.len()) {
+                let (next, trials) = (&next, &trials);
+                scope.spawn(move || loop {
+                    let index = next.fetch_add(1, Ordering::Relaxed);
+                    let Some(values) = points.get(index) else {
+                        break;
+                    };
+                    let trial = self.trial(base, samples, values);
+                    trials.lock().unwrap().push((index, trial));
+                });
+            }
+        });
+        let mut trials = trials.into_inner().unwrap();
+        trials.sort_by_key(|&(index, _)| index);
+        trials.into_iter().map(|(_, trial)| trial).collect()
+    }
+
+    fn trial(&self, base: &Config, samples: &[Sample], values: &[(Parameter, f64)]) -> Trial {
+        let config = self.config(base, values);
+        let evaluations = samples
+            .iter()
+            .map(|sample| {
+                let predicted = self.pipeline.detect(&sample.image, &config)?;
+                Evaluation::new(&sample.name, &predicted, &sample.truth, self.iou)
+            })
+            .collect::<Result<Vec<_>>>();
+        match evaluations {
+            Ok(evaluations) => {
+                let metrics = aggregate(&evaluations);
+                Trial {
+                    values: values.to_vec(),
+                    score: metrics.f1,
+                    metrics,
+                    error: None,
+                }
+            }
+            Err(error) => Trial {
+                values: values.to_vec(),
+                score: 0.0,
+                metrics: Metrics::default(),
+                error: Some(format!("{error:#}")),
+            },
+        }
+    }
+}
+
+fn threads() -> usize {
+    available_parallelism().map_or(1, |threads| threads.get())
+}
+
+/// SplitMix64 generator
+struct Random(u64);
+
+impl Random {
+    fn new(seed: u64) -> Self {
+        Self(seed)
+    }
+
+    /// Uniform in `[0, 1)`
+    fn next(&mut self) -> f64 {
+        self.0 = self.0.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        z ^= z >> 31;
+        (z >> 11) as f64 / (1u64 << 53) as f64
+    }
+
+    /// Standard normal, Box-Muller
+    fn normal(&mut self) -> f64 {
+        let (u, v) = (self.next().max(f64::MIN_POSITIVE), self.next());
+        (-2.0 * u.ln()).sqrt() * (2.0 * std::f64::consts::PI * v).cos()
+    }
+}
@@ -0,0 +1,31 @@
+use crate::{algae, object::Object, seeds, Config};
+use anyhow::Result;
+use opencv::core::Mat;
+use serde::{Deserialize, Serialize};
+
+/// Pipeline
+#[derive(Clone, Copy, Debug, Default, Deserialize, Eq, PartialEq, Serialize)]
+pub enum Pipeline {
+    #[default]
+    Seeds,
+    Algae,
+}
+
+impl Pipeline {
+    /// Detects the objects of the image
+    pub fn detect(&self, source: &Mat, config: &Config) -> Result<Vec<Object>> {
+        match self {
+            Self::Seeds => Ok(seeds::detect(source, config)?.objects),
+            Self::Algae => {
+                let clusterings = algae::kmeans(source, config)?;
+                let Some(clustering) =
+                    algae::choose(&clusterings, config.algae.segmentation.criterion)
+                else {
+                    return Ok(Vec::new());
+                };
+                let segmentation = algae::segment(clustering, config)?;
+                Object::from_contours(&segmentation.contours)
+            }
+        }
+    }
+}
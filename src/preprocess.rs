@@ -0,0 +1,209 @@
+use crate::config::{Background, Clahe, Inpaint, Preprocess};
+use anyhow::{ensure, Result};
+use opencv::{
+    core::{
+        add, divide2, mean, merge, multiply, no_array, split, subtract, Mat, Point, Rect, Scalar,
+        Size, Vector, BORDER_REPLICATE, CV_32F, CV_8UC1,
+    },
+    imgproc::{
+        create_clahe, cvt_color_def, gaussian_blur, get_structuring_element, morphology_ex, resize,
+        COLOR_BGR2Lab, COLOR_Lab2BGR, INTER_AREA, INTER_LINEAR, MORPH_CLOSE, MORPH_ELLIPSE,
+        MORPH_OPEN, MORPH_RECT,
+    },
+    photo,
+    prelude::*,
+};
+
+/// Largest rolling ball radius processed at full resolution
+const RADIUS: i32 = 10;
+
+/// Corrects the illumination and the background of the source image
+///
+/// Stages, in order: inpainting of the masked regions, flat-field correction
+/// against a blank reference image, background subtraction, white balance
+/// against a gray card and CLAHE. Each stage is skipped when it is not
+/// configured or given.
+pub fn preprocess(
+    source: &Mat,
+    config: &Preprocess,
+    reference: Option<&Mat>,
+    mask: Option<&Mat>,
+) -> Result<Mat> {
+    let mut image = source.try_clone()?;
+    if let Some(mask) = mask {
+        image = inpaint(&image, mask, &config.inpaint)?;
+    }
+    if let Some(reference) = reference {
+        image = flat_field(&image, reference)?;
+    }
+    image = background(&image, config.background)?;
+    if let Some([x, y, width, height]) = config.white_balance {
+        image = white_balance(&image, Rect::new(x, y, width, height))?;
+    }
+    if let Some(config) = &config.clahe {
+        image = clahe(&image, config)?;
+    }
+    Ok(image)
+}
+
+/// Fills the non-zero pixels of the mask from their surroundings
+pub fn inpaint(source: &Mat, mask: &Mat, config: &Inpaint) -> Result<Mat> {
+    ensure!(mask.typ() == CV_8UC1, "mask type is not CV_8UC1");
+    let mut target = Mat::default();
+    photo::inpaint(source, mask, &mut target, config.radius, config.method)?;
+    Ok(target)
+}
+
+/// Divides the image by a blank reference image of the same setup, keeping
+/// the mean brightness of the reference
+///
+/// One is added to the reference to avoid division by zero.
+pub fn flat_field(source: &Mat, reference: &Mat) -> Result<Mat> {
+    ensure!(
+        source.channels() == reference.channels(),
+        "reference has {} channels, not {}",
+        reference.channels(),
+        source.channels(),
+    );
+    let mut resized = Mat::default();
+    let reference = if reference.size()? != source.size()? {
+        resize(
+            reference,
+            &mut resized,
+            source.size()?,
+            0.0,
+            0.0,
+            INTER_LINEAR,
+        )?;
+        &resized
+    } else {
+        reference
+    };
+    let (mut image, mut flat) = (Mat::default(), Mat::default());
+    source.convert_to(&mut image, CV_32F, 1.0, 0.0)?;
+    reference.convert_to(&mut flat, CV_32F, 1.0, 1.0)?;
+    let mut ratio = Mat::default();
+    divide2(&image, &flat, &mut ratio, 1.0, -1)?;
+    let mut corrected = Mat::default();
+    multiply(&ratio, &mean(&flat, &no_array())?, &mut corrected, 1.0, -1)?;
+    let mut target = Mat::default();
+    corrected.convert_to(&mut target, source.depth(), 1.0, 0.0)?;
+    Ok(target)
+}
+
+/// Subtracts the background estimated by a morphological opening (dark
+/// background) or closing (light background), keeping its mean brightness
+///
+/// The rolling ball is approximated by a disk on an image downscaled so that
+/// its radius is at most 10 pixels, the estimate is blurred and upscaled
+/// back.
+pub fn background(source: &Mat, config: Background) -> Result<Mat> {
+    let (shape, radius, light) = match config {
+        Background::None => return Ok(source.try_clone()?),
+        Background::TopHat { ksize, light } => (MORPH_RECT, ksize / 2, light),
+        Background::RollingBall { radius, light } => (MORPH_ELLIPSE, radius, light),
+    };
+    ensure!(
+        radius > 0,
+        "background kernel radius {radius} is not positive"
+    );
+    let operation = if light { MORPH_CLOSE } else { MORPH_OPEN };
+    let size = source.size()?;
+    // Rolling ball shrink factor
+    let shrink = if shape == MORPH_ELLIPSE {
+        (radius + RADIUS - 1) / RADIUS
+    } else {
+        1
+    };
+    let mut small = Mat::default();
+    if shrink > 1 {
+        resize(
+            source,
+            &mut small,
+            Size::new((size.width / shrink).max(1), (size.height / shrink).max(1)),
+            0.0,
+            0.0,
+            INTER_AREA,
+        )?;
+    } else {
+        small = source.try_clone()?;
+    }
+    let side = 2 * (radius / shrink).max(1) + 1;
+    let kernel = get_structuring_element(shape, Size::new(side, side), Point::new(-1, -1))?;
+    let mut estimate = Mat::default();
+    morphology_ex(
+        &small,
+        &mut estimate,
+        operation,
+        &kernel,
+        Point::new(-1, -1),
+        1,
+        BORDER_REPLICATE,
+        Scalar::default(),
+    )?;
+    if shrink > 1 {
+        let mut blurred = Mat::default();
+        gaussian_blur(
+            &estimate,
+            &mut blurred,
+            Size::new(side, side),
+            0.0,
+            0.0,
+            BORDER_REPLICATE,
+        )?;
+        resize(&blurred, &mut estimate, size, 0.0, 0.0, INTER_LINEAR)?;
+    }
+    // Source - background + mean of the background
+    let (mut image, mut background) = (Mat::default(), Mat::default());
+    source.convert_to(&mut image, CV_32F, 1.0, 0.0)?;
+    estimate.convert_to(&mut background, CV_32F, 1.0, 0.0)?;
+    let offset = mean(&background, &no_array())?;
+    let mut corrected = Mat::default();
+    subtract(&image, &background, &mut corrected, &no_array(), -1)?;
+    let mut shifted = Mat::default();
+    add(&corrected, &offset, &mut shifted, &no_array(), -1)?;
+    let mut target = Mat::default();
+    shifted.convert_to(&mut target, source.depth(), 1.0, 0.0)?;
+    Ok(target)
+}
+
+/// Scales the channels so that the gray card in the rectangle becomes neutral
+pub fn white_balance(source: &Mat, card: Rect) -> Result<Mat> {
+    ensure!(source.channels() == 3, "white balance needs 3 channels");
+    let bounds = Rect::new(0, 0, source.cols(), source.rows());
+    ensure!(
+        (card & bounds) == card && !card.empty(),
+        "gray card {card:?} is out of the image {bounds:?}",
+    );
+    let means = mean(&Mat::roi(source, card)?, &no_array())?;
+    let gray = (means[0] + means[1] + means[2]) / 3.0;
+    let gain = |channel: f64| if channel > 0.0 { gray / channel } else { 1.0 };
+    let gains = Scalar::new(gain(means[0]), gain(means[1]), gain(means[2]), 1.0);
+    let mut image = Mat::default();
+    source.convert_to(&mut image, CV_32F, 1.0, 0.0)?;
+    let mut balanced = Mat::default();
+    multiply(&image, &gains, &mut balanced, 1.0, -1)?;
+    let mut target = Mat::default();
+    balanced.convert_to(&mut target, source.depth(), 1.0, 0.0)?;
+    Ok(target)
+}
+
+/// Equalizes the lightness (CIELAB L* of color images) with CLAHE
+pub fn clahe(source: &Mat, config: &Clahe) -> Result<Mat> {
+    let mut clahe = create_clahe(config.clip_limit, Size::new(config.tiles, config.tiles))?;
+    let mut target = Mat::default();
+    if source.channels() == 1 {
+        clahe.apply(source, &mut target)?;
+        return Ok(target);
+    }
+    let mut lab = Mat::default();
+    cvt_color_def(source, &mut lab, COLOR_BGR2Lab)?;
+    let mut channels = Vector::<Mat>::new();
+    split(&lab, &mut channels)?;
+    let mut lightness = Mat::default();
+    clahe.apply(&channels.get(0)?, &mut lightness)?;
+    channels.set(0, lightness)?;
+    merge(&channels, &mut lab)?;
+    cvt_color_def(&lab, &mut target, COLOR_Lab2BGR)?;
+    Ok(target)
+}
@@ -0,0 +1,157 @@
+use anyhow::{bail, ensure, Result};
+use opencv::{
+    core::{Mat, Vector},
+    imgcodecs::{
+        imcount, imreadmulti_range, IMREAD_ANYCOLOR, IMREAD_ANYDEPTH, IMREAD_COLOR,
+        IMREAD_GRAYSCALE, IMREAD_UNCHANGED,
+    },
+};
+use serde::{Deserialize, Serialize};
+use std::{
+    fs::read_dir,
+    path::{Path, PathBuf},
+};
+
+const EXTENSIONS: [&str; 7] = ["bmp", "jpeg", "jpg", "png", "tif", "tiff", "webp"];
+
+/// Read mode
+#[derive(Clone, Copy, Debug, Default, Deserialize, Eq, Hash, PartialEq, Serialize)]
+pub enum Mode {
+    /// 8-bit, 3 channels
+    #[default]
+    Color,
+    /// 8-bit, 1 channel
+    Grayscale,
+    /// Original depth and channels
+    AnyDepth,
+    /// Original depth and channels, with alpha
+    Unchanged,
+}
+
+impl Mode {
+    pub fn flags(&self) -> i32 {
+        match self {
+            Self::Color => IMREAD_COLOR,
+            Self::Grayscale => IMREAD_GRAYSCALE,
+            Self::AnyDepth => IMREAD_ANYDEPTH | IMREAD_ANYCOLOR,
+            Self::Unchanged => IMREAD_UNCHANGED,
+        }
+    }
+}
+
+/// Sequence of frames
+///
+/// Frames are the pages of a (multi-page) file, of the files of a directory or
+/// of the files matching a pattern with `*` and `?` in the file name, in
+/// lexicographic order.
+#[derive(Clone, Debug, Default)]
+pub struct Sequence {
+    frames: Vec<(PathBuf, usize)>,
+    mode: Mode,
+}
+
+impl Sequence {
+    pub fn new(path: &Path, mode: Mode) -> Result<Self> {
+        let mut frames = Vec::new();
+        for path in paths(path)? {
+            let count = imcount(&path.to_string_lossy(), mode.flags())?;
+            frames.extend((0..count).map(|page| (path.clone(), page)));
+        }
+        Ok(Self { frames, mode })
+    }
+
+    pub fn len(&self) -> usize {
+        self.frames.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.frames.is_empty()
+    }
+
+    /// File and page of the frame
+    pub fn frame(&self, index: usize) -> Option<(&Path, usize)> {
+        let (path, page) = self.frames.get(index)?;
+        Some((path, *page))
+    }
+
+    pub fn read(&self, index: usize) -> Result<Mat> {
+        let Some((path, page)) = self.frame(index) else {
+            bail!("frame {index} is out of range 0..{}", self.len());
+        };
+        read_page(path, page, self.mode)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = Result<Mat>> + '_ {
+        (0..self.len()).map(|index| self.read(index))
+    }
+}
+
+/// Reads a page of a (multi-page) file
+pub fn read_page(path: &Path, page: usize, mode: Mode) -> Result<Mat> {
+    let mut mats = Vector::<Mat>::new();
+    let filename = path.to_string_lossy();
+    imreadmulti_range(&filename, &mut mats, page as _, 1, mode.flags())?;
+    ensure!(!mats.is_empty(), "failed to read page {page} of {filename}");
+    Ok(mats.get(0)?)
+}
+
+/// Reads all frames
+pub fn read(path: &Path, mode: Mode) -> Result<Vec<Mat>> {
+    Sequence::new(path, mode)?.iter().collect()
+}
+
+/// Resolves a file, a directory or a pattern into files
+pub fn paths(path: &Path) -> Result<Vec<PathBuf>> {
+    if path.is_file() {
+        return Ok(vec![path.to_owned()]);
+    }
+    let (directory, pattern) = if path.is_dir() {
+        (path, None)
+    } else {
+        let Some(pattern) = path.file_name().map(|name| name.to_string_lossy()) else {
+            bail!("{} is not a file, a directory or a pattern", path.display());
+        };
+        let directory = path
+            .parent()
+            .filter(|parent| !parent.as_os_str().is_empty())
+            .unwrap_or(Path::new("."));
+        (directory, Some(pattern))
+    };
+    let mut paths = Vec::new();
+    for entry in read_dir(directory)? {
+        let path = entry?.path();
+        if !path.is_file() {
+            continue;
+        }
+        let name = path
+            .file_name()
+            .map(|name| name.to_string_lossy())
+            .unwrap_or_default();
+        let matches = match &pattern {
+            Some(pattern) => wildcard(pattern.as_bytes(), name.as_bytes()),
+            None => path
+                .extension()
+                .map(|extension| extension.to_string_lossy().to_lowercase())
+                .is_some_and(|extension| EXTENSIONS.contains(&&*extension)),
+        };
+        if matches {
+            paths.push(path);
+        }
+    }
+    ensure!(!paths.is_empty(), "no images found at {}", path.display());
+    paths.sort();
+    Ok(paths)
+}
+
+/// Matches `*` (any sequence) and `?` (any character) wildcards
+fn wildcard(pattern: &[u8], text: &[u8]) -> bool {
+    match (pattern.first(), text.first()) {
+        (None, None) => true,
+        (Some(b'*'), _) => {
+            wildcard(&pattern[1..], text) || (!text.is_empty() && wildcard(pattern, &text[1..]))
+        }
+        (Some(b'?'), Some(_)) => wildcard(&pattern[1..], &text[1..]),
+        (Some(a), Some(b)) if a == b => wildcard(&pattern[1..], &text[1..]),
+        _ => false,
+    }
+}
@@ -0,0 +1,265 @@
+use crate::{
+    color::Hsv,
+    config::{Layer, Render},
+    object::Object,
+    statistics::{statistics, Region},
+};
+use anyhow::Result;
+use opencv::{
+    core::{no_array, Mat, Point, Point2f, Rect, Scalar, Vector, CV_8UC4},
+    imgproc::{
+        circle, draw_contours, polylines, put_text, rectangle, FILLED, FONT_HERSHEY_SIMPLEX,
+        LINE_8, LINE_AA,
+    },
+    prelude::*,
+};
+use std::iter::once;
+
+/// Names of the layers, in drawing order
+pub const LAYERS: [&str; 9] = [
+    "contour",
+    "centroid",
+    "bounding_rectangle",
+    "rotated_rectangle",
+    "min_circumcircle",
+    "max_incircle",
+    "incircle",
+    "swatches",
+    "label",
+];
+
+/// Draws the visible layers over the source image
+pub fn render(source: &Mat, objects: &[Object], config: &Render) -> Result<Mat> {
+    let mut target = source.try_clone()?;
+    for name in LAYERS {
+        draw(&mut target, name, source, objects, config)?;
+    }
+    Ok(target)
+}
+
+/// Draws each visible layer on its own transparent BGRA image
+pub fn layers(
+    source: &Mat,
+    objects: &[Object],
+    config: &Render,
+) -> Result<Vec<(&'static str, Mat)>> {
+    let mut layers = Vec::new();
+    for name in LAYERS {
+        if !visible(name, config) {
+            continue;
+        }
+        let mut target = Mat::zeros_size(source.size()?, CV_8UC4)?.to_mat()?;
+        draw(&mut target, name, source, objects, config)?;
+        layers.push((name, target));
+    }
+    Ok(layers)
+}
+
+/// Text of the label of the object
+pub fn label(index: usize, object: &Object, config: &Render) -> String {
+    let label = &config.label;
+    let mut fields = Vec::new();
+    if label.index {
+        fields.push(index.to_string());
+    }
+    if label.area {
+        fields.push(format!("{:.0}", object.area));
+    }
+    if label.perimeter {
+        fields.push(format!("{:.0}", object.perimeter));
+    }
+    if label.class {
+        if let Some(class) = &object.class {
+            fields.push(class.clone());
+        }
+    }
+    fields.join(" ")
+}
+
+/// Color of the class, from a hash of its name, with the alpha of the layer
+pub fn class_color(class: &str, layer: &Layer) -> [f64; 4] {
+    // FNV-1a
+    let hash = class.bytes().fold(0xcbf29ce484222325u64, |hash, byte| {
+        (hash ^ byte as u64).wrapping_mul(0x100000001b3)
+    });
+    let bgr = Hsv::new((hash % 180) as _, 255.0, 255.0).bgr();
+    [bgr.blue, bgr.green, bgr.red, layer.color[3]]
+}
+
+fn visible(name: &str, config: &Render) -> bool {
+    match name {
+        "label" => config.label.visible,
+        name => layer(name, config).is_some_and(|layer| layer.visible),
+    }
+}
+
+fn layer<'a>(name: &str, config: &'a Render) -> Option<&'a Layer> {
+    Some(match name {
+        "contour" => &config.contour,
+        "centroid" => &config.centroid,
+        "bounding_rectangle" => &config.bounding_rectangle,
+        "rotated_rectangle" => &config.rotated_rectangle,
+        "min_circumcircle" => &config.min_circumcircle,
+        "max_incircle" => &config.max_incircle,
+        "incircle" => &config.incircle,
+        "swatches" => &config.swatches,
+        _ => return None,
+    })
+}
+
+fn draw(
+    target: &mut Mat,
+    name: &str,
+    source: &Mat,
+    objects: &[Object],
+    config: &Render,
+) -> Result<()> {
+    if !visible(name, config) {
+        return Ok(());
+    }
+    if name == "label" {
+        let label = &config.label;
+        for (index, object) in objects.iter().enumerate() {
+            put_text(
+                target,
+                &self::label(index, object, config),
+                point(object.centroid),
+                FONT_HERSHEY_SIMPLEX,
+                label.font_scale,
+                scalar(label.color),
+                label.thickness,
+                LINE_AA,
+                false,
+            )?;
+        }
+        return Ok(());
+    }
+    let Some(layer) = layer(name, config) else {
+        return Ok(());
+    };
+    let color = scalar(layer.color);
+    let thickness = layer.thickness;
+    for object in objects {
+        match name {
+            "contour" => draw_contours(
+                target,
+                &Vector::<Mat>::from_iter(once(object.contour.clone())),
+                -1,
+                match &object.class {
+                    Some(class) => scalar(class_color(class, layer)),
+                    None => color,
+                },
+                thickness,
+                LINE_8,
+                &no_array(),
+                i32::MAX,
+                Point::default(),
+            )?,
+            "centroid" => circle(
+                target,
+                point(object.centroid),
+                1,
+                color,
+                thickness,
+                LINE_8,
+                0,
+            )?,
+            "bounding_rectangle" => {
+                rectangle(
+                    target,
+                    object.bounding_rectangle,
+                    color,
+                    thickness,
+                    LINE_8,
+                    0,
+                )?;
+                circle(
+                    target,
+                    center(object.bounding_rectangle),
+                    1,
+                    color,
+                    thickness,
+                    LINE_8,
+                    0,
+                )?;
+            }
+            "rotated_rectangle" => {
+                let mut points = [Point2f::default(); 4];
+                object.rotated_rectangle.points(&mut points)?;
+                let points = Vector::<Point>::from_iter(points.map(point));
+                polylines(target, &points, true, color, thickness, LINE_8, 0)?;
+                circle(
+                    target,
+                    point(object.rotated_rectangle.center),
+                    1,
+                    color,
+                    thickness,
+                    LINE_8,
+                    0,
+                )?;
+            }
+            "min_circumcircle" | "max_incircle" | "incircle" => {
+                let shape = match name {
+                    "min_circumcircle" => object.min_circumcircle,
+                    "max_incircle" => object.max_incircle,
+                    _ => object.incircle,
+                };
+                let center = point(shape.center);
+                circle(
+                    target,
+                    center,
+                    shape.radius.round() as _,
+                    color,
+                    thickness,
+                    LINE_AA,
+                    0,
+                )?;
+                circle(target, center, 1, color, thickness, LINE_8, 0)?;
+            }
+            "swatches" => {
+                let rect = object.bounding_rectangle;
+                let radius = (object.incircle.radius / 2.0).round().max(1.0) as i32;
+                let corners = [
+                    (rect.tl(), Region::Contour(&object.contour)),
+                    (
+                        Point::new(rect.x + rect.width, rect.y),
+                        Region::Circle(object.max_incircle),
+                    ),
+                    (rect.br(), Region::Circle(object.incircle)),
+                ];
+                for (corner, region) in corners {
+                    let fill = swatch(source, region)?;
+                    circle(target, corner, radius, fill, FILLED, LINE_AA, 0)?;
+                    circle(target, corner, radius, color, thickness, LINE_AA, 0)?;
+                }
+            }
+            _ => {}
+        }
+    }
+    Ok(())
+}
+
+/// Mean color of the region, opaque
+///
+/// Only the bounding rectangle of the region is visited.
+fn swatch(source: &Mat, region: Region) -> Result<Scalar> {
+    let channels = statistics(source, region, &[0, 1, 2])?;
+    Ok(Scalar::new(
+        channels[0].mean,
+        channels[1].mean,
+        channels[2].mean,
+        255.0,
+    ))
+}
+
+fn scalar([b, g, r, a]: [f64; 4]) -> Scalar {
+    Scalar::new(b, g, r, a)
+}
+
+fn point(point: Point2f) -> Point {
+    Point::new(point.x.round() as _, point.y.round() as _)
+}
+
+fn center(rect: Rect) -> Point {
+    Point::new(rect.x + rect.width / 2, rect.y + rect.height / 2)
+}
@@ -0,0 +1,308 @@
+use crate::{
+    filter::{self, Measured},
+    object::{solidity, Object},
+    statistics::{statistics, Region},
+    texture::{texture, Texture},
+    Config, Hsb, GREEN, RED, WHITE,
+};
+use anyhow::Result;
+use cv::{Contour, Draw, ToInputArrayExt};
+use opencv::{
+    core::{Mat, Point, VecN, Vector, BORDER_CONSTANT, CV_8U, CV_8UC1},
+    imgproc::{
+        dilate, median_blur, morphology_default_border_value, COLOR_BGR2GRAY, COLOR_BGR2HSV,
+        DIST_L2, DIST_MASK_5, FILLED,
+    },
+    prelude::*,
+};
+use serde::{Deserialize, Serialize};
+use std::{collections::BTreeMap, f64::consts::PI};
+
+/// Analysis
+#[derive(Clone, Debug, Default)]
+pub struct Analysis {
+    /// Objects of at least the minimum area
+    pub objects: Vec<Object>,
+    /// Seeds of the objects, in the same order
+    pub seeds: Vec<Seed>,
+    /// Objects smaller than the minimum area or rejected by the filter
+    pub rejected: Vec<Object>,
+    /// Intermediate images, if `config.debug` is set
+    pub debug: Option<Images>,
+}
+
+/// Intermediate images
+#[derive(Clone, Debug)]
+pub struct Images {
+    /// Thresholded and dilated image
+    pub binary: Mat,
+    /// Accepted objects in green, rejected ones in red
+    pub filter: Mat,
+    /// Distance transform of the filled objects
+    pub distance_transform: Mat,
+}
+
+/// Names of the seed measurements usable in expressions
+pub const VARIABLES: [&str; 14] = [
+    "area",
+    "perimeter",
+    "circumcircle_radius",
+    "incircle_radius",
+    "circularity",
+    "solidity",
+    "elongation",
+    "hue",
+    "saturation",
+    "brightness",
+    "contrast",
+    "homogeneity",
+    "energy",
+    "correlation",
+];
+
+/// Names of the texture measurements, measured if `config.texture.enabled`
+/// is set
+pub const TEXTURE: [&str; 4] = ["contrast", "homogeneity", "energy", "correlation"];
+
+/// Names of the seed measurements with the config
+pub fn variables(config: &Config) -> Vec<&'static str> {
+    VARIABLES
+        .into_iter()
+        .filter(|name| config.texture.enabled || !TEXTURE.contains(name))
+        .collect()
+}
+
+/// Seed
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+pub struct Seed {
+    pub area: f64,
+    pub circumcircle_radius: f64,
+    pub incircle_radius: f64,
+    pub perimeter: f64,
+    /// Ratio of the area to the area of the convex hull
+    #[serde(default)]
+    pub solidity: f64,
+    /// Ratio of the long side of the rotated rectangle to the short one
+    #[serde(default)]
+    pub elongation: f64,
+    pub colors: Colors,
+    /// Texture, if `config.texture.enabled` is set
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub texture: Option<Texture>,
+    /// Class, if classified or annotated
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub class: Option<String>,
+    /// Columns computed by `config.filter`
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub columns: BTreeMap<String, f64>,
+}
+
+impl Seed {
+    /// `4π·area/perimeter²`, one for a circle
+    pub fn circularity(&self) -> f64 {
+        if self.perimeter > 0.0 {
+            4.0 * PI * self.area / self.perimeter.powi(2)
+        } else {
+            0.0
+        }
+    }
+}
+
+impl Measured for Seed {
+    /// Value of the measurement, the mean contour color for `hue`,
+    /// `saturation` and `brightness`, or of the computed column
+    ///
+    /// The texture measurements are `None` unless the texture is measured.
+    fn variable(&self, name: &str) -> Option<f64> {
+        let texture = || self.texture.map(|texture| texture.glcm);
+        Some(match name {
+            "area" => self.area,
+            "perimeter" => self.perimeter,
+            "circumcircle_radius" => self.circumcircle_radius,
+            "incircle_radius" => self.incircle_radius,
+            "circularity" => self.circularity(),
+            "solidity" => self.solidity,
+            "elongation" => self.elongation,
+            "hue" => self.colors.contour.hue,
+            "saturation" => self.colors.contour.saturation,
+            "brightness" => self.colors.contour.brightness,
+            "contrast" => texture()?.contrast,
+            "homogeneity" => texture()?.homogeneity,
+            "energy" => texture()?.energy,
+            "correlation" => texture()?.correlation,
+            name => return self.columns.get(name).copied(),
+        })
+    }
+
+    fn columns_mut(&mut self) -> &mut BTreeMap<String, f64> {
+        &mut self.columns
+    }
+}
+
+/// Mean HSV colors of the object regions
+#[derive(Clone, Copy, Debug, Default, Deserialize, Serialize)]
+pub struct Colors {
+    pub contour: Hsb,
+    pub max_incircle: Hsb,
+    pub incircle: Hsb,
+}
+
+/// Detection
+#[derive(Clone, Debug, Default)]
+pub struct Detection {
+    /// Objects of at least the minimum area
+    pub objects: Vec<Object>,
+    /// Objects smaller than the minimum area
+    pub rejected: Vec<Object>,
+}
+
+/// Detects and measures the seeds
+pub fn analyze(source: &Mat, config: &Config) -> Result<Analysis> {
+    let binary = binary(source, config)?;
+    let Detection {
+        objects,
+        mut rejected,
+    } = filter(&binary, config)?;
+    let seeds = measure(source, &objects, config)?;
+    let Analysis {
+        objects,
+        seeds,
+        rejected: mut filtered,
+        ..
+    } = select(objects, seeds, config)?;
+    rejected.append(&mut filtered);
+    let debug = if config.debug {
+        let contours = |objects: &[Object]| {
+            Vector::<Mat>::from_iter(objects.iter().map(|object| object.contour.clone()))
+        };
+        let mut filter = source.try_clone()?;
+        filter.draw_contours(&contours(&objects), GREEN, 1)?;
+        filter.draw_contours(&contours(&rejected), RED, 1)?;
+        let mut mask = Mat::zeros_size(source.size()?, CV_8UC1)?.to_mat()?;
+        mask.draw_contours(&contours(&objects), WHITE, FILLED)?;
+        let distance_transform = mask.distance_transform(DIST_L2, DIST_MASK_5)?;
+        Some(Images {
+            binary,
+            filter,
+            distance_transform,
+        })
+    } else {
+        None
+    };
+    Ok(Analysis {
+        objects,
+        seeds,
+        rejected,
+        debug,
+    })
+}
+
+/// Detects objects: gray, blur, threshold, dilation, contours and area filter
+pub fn detect(source: &Mat, config: &Config) -> Result<Detection> {
+    filter(&binary(source, config)?, config)
+}
+
+/// Measures the objects, their mean colors and textures
+pub fn measure(source: &Mat, objects: &[Object], config: &Config) -> Result<Vec<Seed>> {
+    let hsv = source.convert_color(COLOR_BGR2HSV)?;
+    let gray = source.convert_color(COLOR_BGR2GRAY)?;
+    let mean = |region| -> Result<Hsb> {
+        let statistics = statistics(&hsv, region, &[0, 1, 2])?;
+        Ok(VecN([statistics[0].mean, statistics[1].mean, statistics[2].mean]).into())
+    };
+    let mut seeds = Vec::with_capacity(objects.len());
+    for object in objects {
+        let colors = Colors {
+            contour: mean(Region::Contour(&object.contour))?,
+            max_incircle: mean(Region::Circle(object.max_incircle))?,
+            incircle: mean(Region::Circle(object.incircle))?,
+        };
+        let texture = if config.texture.enabled {
+            Some(texture(
+                &gray,
+                Region::Contour(&object.contour),
+                &config.texture,
+            )?)
+        } else {
+            None
+        };
+        let size = object.rotated_rectangle.size;
+        seeds.push(Seed {
+            area: object.area,
+            circumcircle_radius: object.min_circumcircle.radius as _,
+            incircle_radius: object.max_incircle.radius as _,
+            perimeter: object.perimeter,
+            solidity: solidity(&object.contour)?,
+            elongation: size.width.max(size.height) as f64
+                / size.width.min(size.height).max(f32::EPSILON) as f64,
+            colors,
+            texture,
+            class: object.class.clone(),
+            columns: BTreeMap::new(),
+        });
+    }
+    Ok(seeds)
+}
+
+/// Computes the columns of the seeds and moves the objects the filter
+/// rejects to the rejected ones
+pub fn select(objects: Vec<Object>, seeds: Vec<Seed>, config: &Config) -> Result<Analysis> {
+    let mut analysis = Analysis::default();
+    for (object, mut seed) in objects.into_iter().zip(seeds) {
+        if filter::filter(&mut seed, &config.filter)? {
+            analysis.objects.push(object);
+            analysis.seeds.push(seed);
+        } else {
+            analysis.rejected.push(object);
+        }
+    }
+    Ok(analysis)
+}
+
+/// Binary image: gray, blur, threshold and dilation
+fn binary(source: &Mat, config: &Config) -> Result<Mat> {
+    // Gray
+    let mut gray = source.convert_color(COLOR_BGR2GRAY)?;
+    // Blur
+    if config.blur.ksize > 1 {
+        let mut blurred = Mat::default();
+        median_blur(&gray, &mut blurred, config.blur.ksize | 1)?;
+        gray = blurred;
+    }
+    // Threshold
+    let mut binary = gray.threshold(
+        config.threshold.thresh,
+        config.threshold.max,
+        config.threshold.r#type,
+    )?;
+    // Dilation
+    if config.dilation.iterations > 0 && config.dilation.ksize > 0 {
+        let mut dilated = Mat::default();
+        dilate(
+            &binary,
+            &mut dilated,
+            &Mat::ones(config.dilation.ksize, config.dilation.ksize, CV_8U)?,
+            Point::new(-1, -1),
+            config.dilation.iterations,
+            BORDER_CONSTANT,
+            morphology_default_border_value()?,
+        )?;
+        binary = dilated;
+    }
+    Ok(binary)
+}
+
+/// Splits the contours of the binary image by the minimum area
+fn filter(binary: &Mat, config: &Config) -> Result<Detection> {
+    let contours = binary.find_contours(config.contours.mode, config.contours.method)?;
+    // Filter
+    let mut detection = Detection::default();
+    for contour in &contours {
+        if contour.area()? >= config.contours.min_area {
+            detection.objects.push(Object::new(contour)?);
+        } else {
+            detection.rejected.push(Object::new(contour)?);
+        }
+    }
+    Ok(detection)
+}
@@ -0,0 +1,193 @@
+use crate::object::Circle;
+use anyhow::{ensure, Result};
+use opencv::{
+    core::{no_array, Mat, Point, Rect, Scalar, Vector, CV_8U, CV_8UC1},
+    imgproc::{bounding_rect, circle, draw_contours, FILLED, LINE_8},
+    prelude::*,
+};
+use serde::{Deserialize, Serialize};
+
+/// Region of an image
+#[derive(Clone, Copy, Debug)]
+pub enum Region<'a> {
+    Contour(&'a Mat),
+    Circle(Circle),
+}
+
+impl Region<'_> {
+    /// Bounding rectangle
+    pub fn rectangle(&self) -> Result<Rect> {
+        Ok(match self {
+            Self::Contour(contour) => bounding_rect(contour)?,
+            Self::Circle(circle) => {
+                let radius = circle.radius.round() as i32;
+                let center = Point::new(circle.center.x.round() as _, circle.center.y.round() as _);
+                Rect::new(
+                    center.x - radius,
+                    center.y - radius,
+                    2 * radius + 1,
+                    2 * radius + 1,
+                )
+            }
+        })
+    }
+
+    /// Mask of the region within the rectangle
+    pub fn mask(&self, rectangle: Rect) -> Result<Mat> {
+        let mut mask = Mat::zeros(rectangle.height, rectangle.width, CV_8UC1)?.to_mat()?;
+        let white = Scalar::all(255.0);
+        match self {
+            Self::Contour(contour) => draw_contours(
+                &mut mask,
+                &Vector::<Mat>::from_iter([(*contour).clone()]),
+                -1,
+                white,
+                FILLED,
+                LINE_8,
+                &no_array(),
+                i32::MAX,
+                Point::new(-rectangle.x, -rectangle.y),
+            )?,
+            Self::Circle(shape) => circle(
+                &mut mask,
+                Point::new(
+                    shape.center.x.round() as i32 - rectangle.x,
+                    shape.center.y.round() as i32 - rectangle.y,
+                ),
+                shape.radius.round() as _,
+                white,
+                FILLED,
+                LINE_8,
+                0,
+            )?,
+        }
+        Ok(mask)
+    }
+}
+
+/// Statistics of a channel within a region
+#[derive(Clone, Copy, Debug, Default, Deserialize, Serialize)]
+pub struct Statistics {
+    /// Number of pixels
+    pub count: usize,
+    pub mean: f64,
+    /// Population standard deviation
+    pub std: f64,
+    pub min: f64,
+    pub p5: f64,
+    pub p25: f64,
+    pub median: f64,
+    pub p75: f64,
+    pub p95: f64,
+    pub max: f64,
+    /// Shannon entropy of the histogram, bits
+    pub entropy: f64,
+}
+
+impl Statistics {
+    /// Statistics of a histogram of 8-bit values
+    pub fn new(histogram: &[usize; 256]) -> Self {
+        let count = histogram.iter().sum::<usize>();
+        if count == 0 {
+            return Self::default();
+        }
+        let total = count as f64;
+        let mean = histogram
+            .iter()
+            .enumerate()
+            .map(|(value, &frequency)| value as f64 * frequency as f64)
+            .sum::<f64>()
+            / total;
+        let variance = histogram
+            .iter()
+            .enumerate()
+            .map(|(value, &frequency)| (value as f64 - mean).powi(2) * frequency as f64)
+            .sum::<f64>()
+            / total;
+        let entropy = -histogram
+            .iter()
+            .filter(|&&frequency| frequency != 0)
+            .map(|&frequency| {
+                let probability = frequency as f64 / total;
+                probability * probability.log2()
+            })
+            .sum::<f64>();
+        // Nearest rank
+        let percentile = |percent: f64| {
+            let rank = ((percent / 100.0 * total).ceil() as usize).max(1);
+            let mut cumulative = 0;
+            for (value, &frequency) in histogram.iter().enumerate() {
+                cumulative += frequency;
+                if cumulative >= rank {
+                    return value as f64;
+                }
+            }
+            255.0
+        };
+        Self {
+            count,
+            mean,
+            std: variance.sqrt(),
+            min: percentile(0.0),
+            p5: percentile(5.0),
+            p25: percentile(25.0),
+            median: percentile(50.0),
+            p75: percentile(75.0),
+            p95: percentile(95.0),
+            max: percentile(100.0),
+            entropy,
+        }
+    }
+}
+
+/// Statistics of the channels of an 8-bit image within a region
+///
+/// Only the bounding rectangle of the region is visited, in one pass for all
+/// the channels.
+pub fn statistics(image: &Mat, region: Region, channels: &[usize]) -> Result<Vec<Statistics>> {
+    ensure!(image.depth() == CV_8U, "image depth is not 8-bit");
+    let count = image.channels() as usize;
+    ensure!(
+        channels.iter().all(|&channel| channel < count),
+        "channels {channels:?} are out of range 0..{count}",
+    );
+    let rectangle = region.rectangle()? & Rect::new(0, 0, image.cols(), image.rows());
+    let mut histograms = vec![[0; 256]; channels.len()];
+    if !rectangle.empty() {
+        let mask = region.mask(rectangle)?;
+        let roi = Mat::roi(image, rectangle)?.try_clone()?;
+        for (pixel, &inside) in roi
+            .data_bytes()?
+            .chunks_exact(count)
+            .zip(mask.data_bytes()?)
+        {
+            if inside == 0 {
+                continue;
+            }
+            for (histogram, &channel) in histograms.iter_mut().zip(channels) {
+                histogram[pixel[channel] as usize] += 1;
+            }
+        }
+    }
+    Ok(histograms.iter().map(Statistics::new).collect())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn histogram() {
+        let mut histogram = [0; 256];
+        histogram[1..=100].fill(1);
+        let statistics = Statistics::new(&histogram);
+        assert_eq!(statistics.count, 100);
+        assert_eq!(statistics.mean, 50.5);
+        assert_eq!([statistics.min, statistics.max], [1.0, 100.0]);
+        assert_eq!(
+            [statistics.p5, statistics.median, statistics.p95],
+            [5.0, 50.0, 95.0]
+        );
+        assert!((statistics.entropy - 100f64.log2()).abs() < 1e-9);
+    }
+}
@@ -0,0 +1,267 @@
+use crate::{
+    config::{Layer, Render},
+    object::{Circle, Object},
+    render::{class_color, label},
+    seeds::Seed,
+    Hsb,
+};
+use anyhow::{ensure, Result};
+use opencv::{
+    core::{Mat, Point, Point2f, Vector},
+    imgcodecs::imencode_def,
+    prelude::*,
+};
+use std::{io::Write, path::Path};
+
+/// Background
+#[derive(Clone, Copy, Debug)]
+pub enum Background<'a> {
+    /// Link to the image file
+    Linked(&'a Path),
+    /// Image embedded as a PNG data URI
+    Embedded(&'a Mat),
+}
+
+/// Writes the objects as an SVG drawing over the background
+///
+/// Every object is a `<g>` with its contour path, fitted shapes and label, the
+/// measurements of its seed are attached as `data-*` attributes. Only the
+/// visible layers of the config are written.
+pub fn write(
+    mut writer: impl Write,
+    source: &Mat,
+    background: Background,
+    objects: &[Object],
+    seeds: &[Seed],
+    config: &Render,
+) -> Result<()> {
+    ensure!(
+        objects.len() == seeds.len(),
+        "{} objects for {} seeds",
+        objects.len(),
+        seeds.len(),
+    );
+    let (width, height) = (source.cols(), source.rows());
+    writeln!(
+        writer,
+        r#"<svg xmlns="http://www.w3.org/2000/svg" xmlns:xlink="http://www.w3.org/1999/xlink" width="{width}" height="{height}" viewBox="0 0 {width} {height}">"#
+    )?;
+    let href = match background {
+        Background::Linked(path) => escape(&path.to_string_lossy()),
+        Background::Embedded(image) => {
+            let mut buffer = Vector::new();
+            imencode_def(".png", image, &mut buffer)?;
+            format!("data:image/png;base64,{}", base64(buffer.as_slice()))
+        }
+    };
+    writeln!(
+        writer,
+        r#"  <image id="background" x="0" y="0" width="{width}" height="{height}" xlink:href="{href}"/>"#
+    )?;
+    for (index, (object, seed)) in objects.iter().zip(seeds).enumerate() {
+        write!(
+            writer,
+            r#"  <g id="object{index}" data-index="{index}" data-area="{}" data-perimeter="{}" data-centroid-x="{}" data-centroid-y="{}" data-circumcircle-radius="{}" data-incircle-radius="{}""#,
+            object.area,
+            object.perimeter,
+            object.centroid.x,
+            object.centroid.y,
+            object.min_circumcircle.radius,
+            object.max_incircle.radius,
+        )?;
+        if let Some(class) = &object.class {
+            write!(writer, r#" data-class="{}""#, escape(class))?;
+        }
+        measurements(&mut writer, seed)?;
+        writeln!(writer, ">")?;
+        if config.contour.visible {
+            let points = object.contour.data_typed::<Point>()?;
+            let mut d = String::new();
+            for (index, point) in points.iter().enumerate() {
+                let command = if index == 0 { 'M' } else { 'L' };
+                d.push_str(&format!("{command}{} {} ", point.x, point.y));
+            }
+            d.push('Z');
+            let layer = match &object.class {
+                Some(class) => Layer {
+                    color: class_color(class, &config.contour),
+                    ..config.contour
+                },
+                None => config.contour,
+            };
+            writeln!(
+                writer,
+                r#"    <path class="contour" d="{d}" {}/>"#,
+                stroke(&layer)
+            )?;
+        }
+        if config.centroid.visible {
+            dot(&mut writer, "centroid", object.centroid, &config.centroid)?;
+        }
+        if config.bounding_rectangle.visible {
+            let rect = object.bounding_rectangle;
+            writeln!(
+                writer,
+                r#"    <rect class="bounding_rectangle" x="{}" y="{}" width="{}" height="{}" {}/>"#,
+                rect.x,
+                rect.y,
+                rect.width,
+                rect.height,
+                stroke(&config.bounding_rectangle),
+            )?;
+        }
+        if config.rotated_rectangle.visible {
+            let mut points = [Point2f::default(); 4];
+            object.rotated_rectangle.points(&mut points)?;
+            let points = points
+                .iter()
+                .map(|point| format!("{},{}", point.x, point.y))
+                .collect::<Vec<_>>()
+                .join(" ");
+            writeln!(
+                writer,
+                r#"    <polygon class="rotated_rectangle" points="{points}" {}/>"#,
+                stroke(&config.rotated_rectangle),
+            )?;
+        }
+        for (class, circle, layer) in [
+            (
+                "min_circumcircle",
+                object.min_circumcircle,
+                &config.min_circumcircle,
+            ),
+            ("max_incircle", object.max_incircle, &config.max_incircle),
+            ("incircle", object.incircle, &config.incircle),
+        ] {
+            if layer.visible {
+                self::circle(&mut writer, class, circle, layer)?;
+            }
+        }
+        if config.label.visible {
+            let label = &config.label;
+            writeln!(
+                writer,
+                r#"    <text class="label" x="{}" y="{}" font-family="sans-serif" font-size="{}" {}>{}</text>"#,
+                object.centroid.x,
+                object.centroid.y,
+                // Hershey simplex glyphs are about 22 px high at scale 1
+                22.0 * label.font_scale,
+                fill(label.color),
+                escape(&self::label(index, object, config)),
+            )?;
+        }
+        writeln!(writer, "  </g>")?;
+    }
+    writeln!(writer, "</svg>")?;
+    Ok(())
+}
+
+/// Writes the seed measurements that are not in the object as `data-*`
+/// attributes, the columns prefixed by `column`
+fn measurements(writer: &mut impl Write, seed: &Seed) -> Result<()> {
+    write!(
+        writer,
+        r#" data-circularity="{}" data-solidity="{}" data-elongation="{}""#,
+        seed.circularity(),
+        seed.solidity,
+        seed.elongation,
+    )?;
+    for (region, color) in [
+        ("contour", seed.colors.contour),
+        ("max-incircle", seed.colors.max_incircle),
+        ("incircle", seed.colors.incircle),
+    ] {
+        let Hsb {
+            hue,
+            saturation,
+            brightness,
+        } = color;
+        write!(
+            writer,
+            r#" data-{region}-hue="{hue}" data-{region}-saturation="{saturation}" data-{region}-brightness="{brightness}""#,
+        )?;
+    }
+    if let Some(texture) = &seed.texture {
+        let glcm = texture.glcm;
+        let lbp = texture
+            .lbp
+            .iter()
+            .map(ToString::to_string)
+            .collect::<Vec<_>>()
+            .join(" ");
+        write!(
+            writer,
+            r#" data-contrast="{}" data-homogeneity="{}" data-energy="{}" data-correlation="{}" data-lbp="{lbp}""#,
+            glcm.contrast, glcm.homogeneity, glcm.energy, glcm.correlation,
+        )?;
+    }
+    for (name, value) in &seed.columns {
+        write!(
+            writer,
+            r#" data-column-{}="{value}""#,
+            name.replace('_', "-")
+        )?;
+    }
+    Ok(())
+}
+
+fn circle(writer: &mut impl Write, class: &str, circle: Circle, layer: &Layer) -> Result<()> {
+    writeln!(
+        writer,
+        r#"    <circle class="{class}" cx="{}" cy="{}" r="{}" {}/>"#,
+        circle.center.x,
+        circle.center.y,
+        circle.radius,
+        stroke(layer),
+    )?;
+    Ok(())
+}
+
+fn dot(writer: &mut impl Write, class: &str, center: Point2f, layer: &Layer) -> Result<()> {
+    writeln!(
+        writer,
+        r#"    <circle class="{class}" cx="{}" cy="{}" r="1" {}/>"#,
+        center.x,
+        center.y,
+        fill(layer.color),
+    )?;
+    Ok(())
+}
+
+fn stroke(layer: &Layer) -> String {
+    let [b, g, r, a] = layer.color;
+    format!(
+        r#"fill="none" stroke="rgb({r},{g},{b})" stroke-opacity="{}" stroke-width="{}""#,
+        a / 255.0,
+        layer.thickness.max(1),
+    )
+}
+
+fn fill([b, g, r, a]: [f64; 4]) -> String {
+    format!(r#"fill="rgb({r},{g},{b})" fill-opacity="{}""#, a / 255.0)
+}
+
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+fn base64(bytes: &[u8]) -> String {
+    const ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+    let mut encoded = String::with_capacity(bytes.len().div_ceil(3) * 4);
+    for chunk in bytes.chunks(3) {
+        let n = chunk.iter().enumerate().fold(0u32, |n, (index, &byte)| {
+            n | (byte as u32) << (16 - 8 * index)
+        });
+        for index in 0..4 {
+            if index <= chunk.len() {
+                encoded.push(ALPHABET[(n >> (18 - 6 * index) & 63) as usize] as char);
+            } else {
+                encoded.push('=');
+            }
+        }
+    }
+    encoded
+}
@@ -0,0 +1,357 @@
+use crate::{
+    node::{Error, Node, Parameter, Result, Value},
+    utils::SyncMat,
+};
+use egui::{
+    load::SizedTexture, ColorImage, ComboBox, Context, DragValue, Grid, ScrollArea, TextureHandle,
+    TextureOptions, Ui, Window,
+};
+use egui_snarl::{InPinId, NodeId, OutPinId, Snarl};
+use opencv::{
+    core::{
+        count_non_zero, mean, no_array, normalize, Mat, MatTraitConst, Size, CV_8U, NORM_MINMAX,
+    },
+    imgproc::{
+        contour_area, cvt_color_def, resize, COLOR_BGR2RGBA, COLOR_BGRA2RGBA, COLOR_GRAY2RGBA,
+        INTER_AREA,
+    },
+    prelude::MatTraitConstManual,
+};
+use std::{
+    collections::{HashMap, HashSet},
+    sync::Arc,
+};
+
+const THUMBNAIL: i32 = 128;
+
+/// Parameter sweep
+///
+/// Evaluates the graph up to the target output once per value of a node
+/// parameter and shows the results side by side.
+#[derive(Default)]
+pub(crate) struct Sweep {
+    pub open: bool,
+    pub node: Option<NodeId>,
+    pub target: Option<OutPinId>,
+    pub parameter: String,
+    pub from: f64,
+    pub to: f64,
+    pub steps: usize,
+    pub metric: Metric,
+    cells: Vec<Cell>,
+}
+
+impl Sweep {
+    /// Sweeps the parameters of the node
+    pub fn set_node(&mut self, snarl: &mut Snarl<Node>, node: NodeId) {
+        self.node = Some(node);
+        self.open = true;
+        self.cells.clear();
+        if let Some((name, parameter)) = snarl[node].parameters().into_iter().next() {
+            self.parameter = name.into_owned();
+            let value = parameter.get();
+            (self.from, self.to) = match parameter {
+                // Odd values only
+                Parameter::Odd(_) => ((value - 4.0).max(1.0), value + 4.0),
+                _ if value == 0.0 => (-1.0, 1.0),
+                _ => (value / 2.0, value * 2.0),
+            };
+            self.steps = match parameter {
+                Parameter::Odd(_) => ((self.to - self.from) / 2.0) as usize + 1,
+                _ => 5,
+            };
+        }
+    }
+
+    /// Observes the first output of the node
+    pub fn set_target(&mut self, node: NodeId) {
+        self.target = Some(OutPinId { node, output: 0 });
+        self.open = true;
+        self.cells.clear();
+    }
+
+    pub fn show(&mut self, ctx: &Context, snarl: &mut Snarl<Node>) {
+        let mut open = self.open;
+        Window::new("Sweep").open(&mut open).show(ctx, |ui| {
+            self.show_settings(ui, snarl);
+            ui.separator();
+            self.show_cells(ui, snarl);
+        });
+        self.open = open;
+    }
+
+    fn show_settings(&mut self, ui: &mut Ui, snarl: &mut Snarl<Node>) {
+        let Some(node) = self.node.filter(|&node| snarl.get_node(node).is_some()) else {
+            ui.label("Choose a node to sweep from its menu");
+            return;
+        };
+        let Some(target) = self
+            .target
+            .filter(|target| snarl.get_node(target.node).is_some())
+        else {
+            ui.label("Choose a target node from its menu");
+            return;
+        };
+        // Parameter
+        ui.horizontal(|ui| {
+            ui.label("Parameter:");
+            let names = snarl[node]
+                .parameters()
+                .into_iter()
+                .map(|(name, _)| name)
+                .collect::<Vec<_>>();
+            ComboBox::from_id_source("parameter")
+                .selected_text(&self.parameter)
+                .show_ui(ui, |ui| {
+                    for name in names {
+                        ui.selectable_value(&mut self.parameter, name.to_string(), name);
+                    }
+                });
+        });
+        // Range
+        ui.horizontal(|ui| {
+            ui.label("Range:");
+            ui.add(DragValue::new(&mut self.from)).on_hover_text("From");
+            ui.add(DragValue::new(&mut self.to)).on_hover_text("To");
+            ui.add(DragValue::new(&mut self.steps).clamp_range(2..=64))
+                .on_hover_text("Steps");
+        });
+        // Target
+        ui.horizontal(|ui| {
+            ui.label("Target:");
+            let outputs = snarl[target.node].outputs().len();
+            ui.label(snarl[target.node].title());
+            let mut output = self.target.map_or(0, |target| target.output);
+            if ui
+                .add(DragValue::new(&mut output).clamp_range(0..=outputs.saturating_sub(1)))
+                .on_hover_text("Output")
+                .changed()
+            {
+                self.target = Some(OutPinId {
+                    node: target.node,
+                    output,
+                });
+            }
+        });
+        // Metric
+        ui.horizontal(|ui| {
+            ui.label("Metric:");
+            ComboBox::from_id_source("metric")
+                .selected_text(self.metric.text())
+                .show_ui(ui, |ui| {
+                    for metric in [Metric::Count, Metric::MeanArea, Metric::Mean] {
+                        ui.selectable_value(&mut self.metric, metric, metric.text());
+                    }
+                });
+        });
+        if ui.button("Run").clicked() {
+            self.run(ui.ctx(), snarl, node, target);
+        }
+    }
+
+    fn show_cells(&mut self, ui: &mut Ui, snarl: &mut Snarl<Node>) {
+        let mut apply = None;
+        ScrollArea::both().show(ui, |ui| {
+            Grid::new("sweep").show(ui, |ui| {
+                for (index, cell) in self.cells.iter().enumerate() {
+                    ui.vertical(|ui| {
+                        ui.label(format!("{} = {}", self.parameter, cell.value));
+                        match &cell.result {
+                            Ok(metric) => {
+                                match metric {
+                                    Some(metric) => {
+                                        ui.label(format!("{}: {metric:.2}", self.metric.text()))
+                                    }
+                                    None => ui.label(format!("{}: -", self.metric.text())),
+                                };
+                                if let Some(texture) = &cell.texture {
+                                    ui.image(SizedTexture::from_handle(texture));
+                                }
+                            }
+                            Err(error) => {
+                                ui.label(error.to_string());
+                            }
+                        }
+                        if ui.button("Apply").clicked() {
+                            apply = Some(cell.value);
+                        }
+                    });
+                    if (index + 1) % 4 == 0 {
+                        ui.end_row();
+                    }
+                }
+            });
+        });
+        if let (Some(value), Some(node)) = (apply, self.node) {
+            if let Some((_, mut parameter)) = snarl[node]
+                .parameters()
+                .into_iter()
+                .find(|(name, _)| *name == self.parameter)
+            {
+                parameter.set(value);
+            }
+        }
+    }
+
+    fn run(&mut self, ctx: &Context, snarl: &Snarl<Node>, node: NodeId, target: OutPinId) {
+        self.cells.clear();
+        let wires = snarl.wires().collect::<Vec<_>>();
+        let steps = self.steps.max(2);
+        for step in 0..steps {
+            let mut value = self.from + (self.to - self.from) * step as f64 / (steps - 1) as f64;
+            let mut swept = snarl[node].clone();
+            if let Some((_, mut parameter)) = swept
+                .parameters()
+                .into_iter()
+                .find(|(name, _)| *name == self.parameter)
+            {
+                parameter.set(value);
+                // Snapped value
+                value = parameter.get();
+            }
+            let overrides = HashMap::from([(node, swept)]);
+            let mut evaluator = Evaluator {
+                snarl,
+                wires: &wires,
+                overrides: &overrides,
+                outputs: HashMap::new(),
+                visiting: HashSet::new(),
+            };
+            let output = evaluator
+                .evaluate(target.node)
+                .map(|outputs| outputs.get(target.output).cloned().unwrap_or_default());
+            let cell = match output {
+                Ok(output) => Cell {
+                    value,
+                    result: Ok(self.metric.measure(&output)),
+                    texture: output
+                        .as_matrix()
+                        .and_then(|matrix| thumbnail(ctx, matrix, step).ok()),
+                },
+                Err(error) => Cell {
+                    value,
+                    result: Err(error),
+                    texture: None,
+                },
+            };
+            self.cells.push(cell);
+        }
+    }
+}
+
+/// Metric
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub(crate) enum Metric {
+    /// Number of contours or of non-zero pixels
+    #[default]
+    Count,
+    /// Mean area of contours
+    MeanArea,
+    /// Mean intensity of the first channel
+    Mean,
+}
+
+impl Metric {
+    fn text(&self) -> &'static str {
+        match self {
+            Self::Count => "Count",
+            Self::MeanArea => "Mean area",
+            Self::Mean => "Mean",
+        }
+    }
+
+    fn measure(&self, value: &Value) -> Option<f64> {
+        match (self, value) {
+            (Self::Count, Value::Contours(contours)) => Some(contours.len() as _),
+            (Self::Count, Value::Matrix(matrix)) if matrix.channels() == 1 => {
+                count_non_zero(&**matrix).ok().map(|count| count as _)
+            }
+            (Self::MeanArea, Value::Contours(contours)) if !contours.is_empty() => {
+                let mut sum = 0.0;
+                for contour in contours.iter() {
+                    sum += contour_area(contour, false).ok()?;
+                }
+                Some(sum / contours.len() as f64)
+            }
+            (Self::Mean, Value::Matrix(matrix)) => {
+                mean(&**matrix, &no_array()).ok().map(|mean| mean[0])
+            }
+            _ => None,
+        }
+    }
+}
+
+/// Cell
+struct Cell {
+    value: f64,
+    result: Result<Option<f64>>,
+    texture: Option<TextureHandle>,
+}
+
+/// Evaluator of a graph, with some nodes replaced
+struct Evaluator<'a> {
+    snarl: &'a Snarl<Node>,
+    wires: &'a [(OutPinId, InPinId)],
+    overrides: &'a HashMap<NodeId, Node>,
+    outputs: HashMap<NodeId, Arc<Vec<Value>>>,
+    /// Nodes being evaluated, to detect cycles
+    visiting: HashSet<NodeId>,
+}
+
+impl Evaluator<'_> {
+    fn evaluate(&mut self, id: NodeId) -> Result<Arc<Vec<Value>>> {
+        if let Some(outputs) = self.outputs.get(&id) {
+            return Ok(outputs.clone());
+        }
+        if !self.visiting.insert(id) {
+            return Err(Error::Cycle);
+        }
+        let mut node = self.overrides.get(&id).unwrap_or(&self.snarl[id]).clone();
+        for &(from, to) in self.wires {
+            if to.node == id {
+                let value = self
+                    .evaluate(from.node)?
+                    .get(from.output)
+                    .cloned()
+                    .unwrap_or_default();
+                node.set_value(to.input, value);
+            }
+        }
+        let outputs = Arc::new(node.compute()?);
+        self.visiting.remove(&id);
+        self.outputs.insert(id, outputs.clone());
+        Ok(outputs)
+    }
+}
+
+fn thumbnail(ctx: &Context, matrix: &SyncMat, index: usize) -> opencv::Result<TextureHandle> {
+    let mut normalized = Mat::default();
+    normalize(
+        matrix,
+        &mut normalized,
+        0.0,
+        255.0,
+        NORM_MINMAX,
+        CV_8U,
+        &no_array(),
+    )?;
+    let code = match normalized.channels() {
+        1 => COLOR_GRAY2RGBA,
+        4 => COLOR_BGRA2RGBA,
+        _ => COLOR_BGR2RGBA,
+    };
+    let mut rgba = Mat::default();
+    cvt_color_def(&normalized, &mut rgba, code)?;
+    let scale = THUMBNAIL as f64 / rgba.cols().max(rgba.rows()).max(1) as f64;
+    let size = Size::new(
+        ((rgba.cols() as f64 * scale) as i32).max(1),
+        ((rgba.rows() as f64 * scale) as i32).max(1),
+    );
+    let mut resized = Mat::default();
+    resize(&rgba, &mut resized, size, 0.0, 0.0, INTER_AREA)?;
+    let image = ColorImage::from_rgba_unmultiplied(
+        [size.width as _, size.height as _],
+        resized.data_bytes()?,
+    );
+    Ok(ctx.load_texture(format!("sweep{index}"), image, TextureOptions::default()))
+}
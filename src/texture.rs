@@ -0,0 +1,277 @@
+use crate::{config, statistics::Region};
+use anyhow::{ensure, Result};
+use opencv::{
+    core::{Mat, Rect, CV_8UC1},
+    prelude::*,
+};
+use serde::{Deserialize, Serialize};
+use std::f64::consts::FRAC_PI_4;
+
+/// Bins of the uniform local binary patterns of 8 neighbors
+pub const BINS: usize = 10;
+
+/// Texture
+#[derive(Clone, Copy, Debug, Default, Deserialize, Serialize)]
+pub struct Texture {
+    pub glcm: Glcm,
+    /// Normalized histogram of the uniform local binary patterns, by the
+    /// number of brighter neighbors, the last bin for the non-uniform ones
+    pub lbp: [f64; BINS],
+}
+
+/// Gray-level co-occurrence features, averaged over 0°, 45°, 90° and 135°
+#[derive(Clone, Copy, Debug, Default, Deserialize, Serialize)]
+pub struct Glcm {
+    pub contrast: f64,
+    pub homogeneity: f64,
+    /// Square root of the angular second moment
+    pub energy: f64,
+    pub correlation: f64,
+}
+
+/// Texture of a gray image within a region
+pub fn texture(gray: &Mat, region: Region, config: &config::Texture) -> Result<Texture> {
+    ensure!(gray.typ() == CV_8UC1, "image type is not CV_8UC1");
+    ensure!(
+        (2..=256).contains(&config.levels),
+        "levels {} are out of range 2..=256",
+        config.levels,
+    );
+    let rectangle = region.rectangle()? & Rect::new(0, 0, gray.cols(), gray.rows());
+    if rectangle.empty() {
+        return Ok(Texture::default());
+    }
+    // Padded by the reach of the neighbors, only the padded rectangle is
+    // copied
+    let padding = config.distance.abs().max(config.radius.abs());
+    let area = Rect::new(
+        rectangle.x - padding,
+        rectangle.y - padding,
+        rectangle.width + 2 * padding,
+        rectangle.height + 2 * padding,
+    ) & Rect::new(0, 0, gray.cols(), gray.rows());
+    let roi = Mat::roi(gray, area)?.try_clone()?;
+    let image = Image {
+        data: roi.data_bytes()?,
+        area,
+        mask: region.mask(rectangle)?,
+        rectangle,
+    };
+    Ok(Texture {
+        glcm: image.glcm(config.levels, config.distance)?,
+        lbp: image.lbp(config.radius)?,
+    })
+}
+
+/// Gray image with the mask of a region
+struct Image<'a> {
+    /// Pixels of the area
+    data: &'a [u8],
+    /// Area of the image around the region
+    area: Rect,
+    mask: Mat,
+    rectangle: Rect,
+}
+
+impl Image<'_> {
+    fn get(&self, x: i32, y: i32) -> Option<u8> {
+        let (x, y) = (x - self.area.x, y - self.area.y);
+        if x < 0 || y < 0 || x >= self.area.width || y >= self.area.height {
+            return None;
+        }
+        Some(self.data[(y * self.area.width + x) as usize])
+    }
+
+    fn contains(&self, x: i32, y: i32) -> Result<bool> {
+        let (x, y) = (x - self.rectangle.x, y - self.rectangle.y);
+        if x < 0 || y < 0 || x >= self.rectangle.width || y >= self.rectangle.height {
+            return Ok(false);
+        }
+        Ok(*self.mask.at_2d::<u8>(y, x)? != 0)
+    }
+
+    /// Pixels of the region
+    fn pixels(&self) -> impl Iterator<Item = (i32, i32)> + '_ {
+        let Rect {
+            x,
+            y,
+            width,
+            height,
+        } = self.rectangle;
+        (y..y + height).flat_map(move |row| (x..x + width).map(move |column| (column, row)))
+    }
+
+    fn glcm(&self, levels: usize, distance: i32) -> Result<Glcm> {
+        let quantize = |value: u8| value as usize * levels / 256;
+        let offsets = [
+            (distance, 0),
+            (distance, -distance),
+            (0, -distance),
+            (-distance, -distance),
+        ];
+        let mut glcm = Glcm::default();
+        // Directions with co-occurring pixels in the region
+        let mut directions = 0;
+        for (dx, dy) in offsets {
+            // Symmetric co-occurrence matrix
+            let mut matrix = vec![0.0; levels * levels];
+            for (x, y) in self.pixels() {
+                if !self.contains(x, y)? || !self.contains(x + dx, y + dy)? {
+                    continue;
+                }
+                let (Some(a), Some(b)) = (self.get(x, y), self.get(x + dx, y + dy)) else {
+                    continue;
+                };
+                let (i, j) = (quantize(a), quantize(b));
+                matrix[i * levels + j] += 1.0;
+                matrix[j * levels + i] += 1.0;
+            }
+            let total = matrix.iter().sum::<f64>();
+            if total == 0.0 {
+                continue;
+            }
+            directions += 1;
+            let cells = || {
+                matrix.iter().enumerate().map(|(index, &count)| {
+                    (
+                        (index / levels) as f64,
+                        (index % levels) as f64,
+                        count / total,
+                    )
+                })
+            };
+            let mean = cells().map(|(i, _, p)| i * p).sum::<f64>();
+            let variance = cells().map(|(i, _, p)| (i - mean).powi(2) * p).sum::<f64>();
+            glcm.contrast += cells().map(|(i, j, p)| (i - j).powi(2) * p).sum::<f64>();
+            glcm.homogeneity += cells()
+                .map(|(i, j, p)| p / (1.0 + (i - j).powi(2)))
+                .sum::<f64>();
+            glcm.energy += cells().map(|(_, _, p)| p * p).sum::<f64>().sqrt();
+            // Symmetric, so the marginal distributions are the same
+            glcm.correlation += if variance > 0.0 {
+                cells()
+                    .map(|(i, j, p)| (i - mean) * (j - mean) * p)
+                    .sum::<f64>()
+                    / variance
+            } else {
+                1.0
+            };
+        }
+        if directions == 0 {
+            return Ok(Glcm::default());
+        }
+        let count = directions as f64;
+        glcm.contrast /= count;
+        glcm.homogeneity /= count;
+        glcm.energy /= count;
+        glcm.correlation /= count;
+        Ok(glcm)
+    }
+
+    fn lbp(&self, radius: i32) -> Result<[f64; BINS]> {
+        let neighbors = (0..8)
+            .map(|index| {
+                let angle = index as f64 * FRAC_PI_4;
+                (
+                    (radius as f64 * angle.cos()).round() as i32,
+                    -(radius as f64 * angle.sin()).round() as i32,
+                )
+            })
+            .collect::<Vec<_>>();
+        let mut histogram = [0.0; BINS];
+        for (x, y) in self.pixels() {
+            if !self.contains(x, y)? {
+                continue;
+            }
+            let Some(center) = self.get(x, y) else {
+                continue;
+            };
+            let Some(bits) = neighbors
+                .iter()
+                .map(|&(dx, dy)| Some(self.get(x + dx, y + dy)? >= center))
+                .collect::<Option<Vec<_>>>()
+            else {
+                continue;
+            };
+            let transitions = (0..bits.len())
+                .filter(|&index| bits[index] != bits[(index + 1) % bits.len()])
+                .count();
+            let bin = if transitions <= 2 {
+                bits.iter().filter(|&&bit| bit).count()
+            } else {
+                BINS - 1
+            };
+            histogram[bin] += 1.0;
+        }
+        let total = histogram.iter().sum::<f64>();
+        if total > 0.0 {
+            histogram.iter_mut().for_each(|bin| *bin /= total);
+        }
+        Ok(histogram)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use opencv::core::Scalar;
+
+    /// Checkerboard of single pixels, black at the origin
+    fn checkerboard() -> Vec<u8> {
+        (0..64)
+            .map(|index| {
+                if (index % 8 + index / 8) % 2 == 0 {
+                    0
+                } else {
+                    255
+                }
+            })
+            .collect()
+    }
+
+    fn image(data: &[u8], rectangle: Rect) -> Result<Image<'_>> {
+        Ok(Image {
+            data,
+            area: Rect::new(0, 0, 8, 8),
+            mask: Mat::new_rows_cols_with_default(
+                rectangle.height,
+                rectangle.width,
+                CV_8UC1,
+                Scalar::all(255.0),
+            )?,
+            rectangle,
+        })
+    }
+
+    #[test]
+    fn glcm() -> Result<()> {
+        let data = checkerboard();
+        // Neighbors differ horizontally and vertically, match diagonally
+        let glcm = image(&data, Rect::new(0, 0, 8, 8))?.glcm(2, 1)?;
+        assert!((glcm.contrast - 0.5).abs() < 1e-9);
+        assert!((glcm.homogeneity - 0.75).abs() < 1e-9);
+        assert!(glcm.correlation.abs() < 1e-9);
+        // A column has only vertical neighbors
+        let glcm = image(&data, Rect::new(3, 0, 1, 8))?.glcm(2, 1)?;
+        assert!((glcm.contrast - 1.0).abs() < 1e-9);
+        assert!((glcm.homogeneity - 0.5).abs() < 1e-9);
+        assert!((glcm.correlation + 1.0).abs() < 1e-9);
+        // A pixel has no neighbors
+        let glcm = image(&data, Rect::new(3, 3, 1, 1))?.glcm(2, 1)?;
+        assert_eq!(glcm.contrast, 0.0);
+        assert_eq!(glcm.homogeneity, 0.0);
+        Ok(())
+    }
+
+    #[test]
+    fn lbp() -> Result<()> {
+        let data = checkerboard();
+        // Black pixels have no darker neighbors, white ones alternate
+        let lbp = image(&data, Rect::new(0, 0, 8, 8))?.lbp(1)?;
+        let mut expected = [0.0; BINS];
+        expected[8] = 0.5;
+        expected[BINS - 1] = 0.5;
+        assert_eq!(lbp, expected);
+        Ok(())
+    }
+}
@@ -1,6 +1,6 @@
 use ::opencv::{
     boxed_ref::BoxedRef,
-    core::{Mat, MatTraitConst, MatTraitConstManual, ToInputArray, _InputArray, type_to_string},
+    core::{_InputArray, type_to_string, Mat, MatTraitConst, MatTraitConstManual, ToInputArray},
     Result,
 };
 use std::{
@@ -46,3 +46,5 @@ impl ToInputArray for SyncMat {
         self.0.input_array()
     }
 }
+
+pub mod npy;
@@ -0,0 +1,191 @@
+//! Minimal reader and writer of the NumPy `.npy` format
+//!
+//! Matrices are stored C-ordered with the shape `(rows, columns)` for single
+//! channel matrices and `(rows, columns, channels)` otherwise.
+
+use opencv::{
+    core::{
+        Mat, MatTraitConst, MatTraitConstManual, MatTraitManual, Scalar, CV_16S, CV_16U, CV_32F,
+        CV_32S, CV_64F, CV_8S, CV_8U, CV_MAKETYPE,
+    },
+    Error,
+};
+use std::io::{self, ErrorKind, Read, Write};
+
+const MAGIC: &[u8] = b"\x93NUMPY";
+
+const DESCRIPTORS: [(i32, &str); 7] = [
+    (CV_8U, "|u1"),
+    (CV_8S, "|i1"),
+    (CV_16U, "<u2"),
+    (CV_16S, "<i2"),
+    (CV_32S, "<i4"),
+    (CV_32F, "<f4"),
+    (CV_64F, "<f8"),
+];
+
+/// Writes the matrix
+pub fn write(mat: &impl MatTraitConst, mut writer: impl Write) -> io::Result<()> {
+    let descriptor = DESCRIPTORS
+        .iter()
+        .find(|(depth, _)| *depth == mat.depth())
+        .map(|(_, descriptor)| descriptor)
+        .ok_or_else(|| invalid_data(format!("unsupported depth {}", mat.depth())))?;
+    // The data is written in native byte order
+    let descriptor = match descriptor.strip_prefix('<') {
+        Some(code) if cfg!(target_endian = "big") => format!(">{code}"),
+        _ => descriptor.to_string(),
+    };
+    let shape = match mat.channels() {
+        1 => format!("({}, {})", mat.rows(), mat.cols()),
+        channels => format!("({}, {}, {channels})", mat.rows(), mat.cols()),
+    };
+    let mut header =
+        format!("{{'descr': '{descriptor}', 'fortran_order': False, 'shape': {shape}, }}");
+    // Magic, version and length take 10 bytes, the header ends with a newline
+    // and the total is aligned to 64 bytes
+    let padding = (64 - (10 + header.len() + 1) % 64) % 64;
+    header.extend((0..padding).map(|_| ' '));
+    header.push('\n');
+    writer.write_all(MAGIC)?;
+    writer.write_all(&[1, 0])?;
+    writer.write_all(&(header.len() as u16).to_le_bytes())?;
+    writer.write_all(header.as_bytes())?;
+    // A clone is always continuous
+    let mat = mat.try_clone().map_err(other)?;
+    writer.write_all(mat.data_bytes().map_err(other)?)
+}
+
+/// Reads a matrix
+pub fn read(mut reader: impl Read) -> io::Result<Mat> {
+    let mut magic = [0; 8];
+    reader.read_exact(&mut magic)?;
+    if &magic[..6] != MAGIC {
+        return Err(invalid_data("not a npy file"));
+    }
+    let length = match magic[6] {
+        1 => {
+            let mut length = [0; 2];
+            reader.read_exact(&mut length)?;
+            u16::from_le_bytes(length) as usize
+        }
+        _ => {
+            let mut length = [0; 4];
+            reader.read_exact(&mut length)?;
+            u32::from_le_bytes(length) as usize
+        }
+    };
+    let mut header = vec![0; length];
+    reader.read_exact(&mut header)?;
+    let header = String::from_utf8_lossy(&header);
+    if field(&header, "fortran_order").is_some_and(|value| value.starts_with("True")) {
+        return Err(invalid_data("fortran order is not supported"));
+    }
+    let descriptor = field(&header, "descr")
+        .and_then(|value| value.split('\'').nth(1))
+        .ok_or_else(|| invalid_data("missing descr"))?;
+    // Byte order and type
+    let unsupported = || invalid_data(format!("unsupported descr {descriptor}"));
+    let (Some(order), Some(code)) = (descriptor.get(..1), descriptor.get(1..)) else {
+        return Err(unsupported());
+    };
+    let big_endian = match order {
+        "<" | "|" => false,
+        ">" => true,
+        "=" => cfg!(target_endian = "big"),
+        _ => return Err(unsupported()),
+    };
+    let depth = DESCRIPTORS
+        .iter()
+        .find(|(_, other)| other[1..] == *code)
+        .map(|(depth, _)| *depth)
+        .ok_or_else(unsupported)?;
+    let shape = field(&header, "shape")
+        .and_then(|value| value.split(['(', ')']).nth(1))
+        .ok_or_else(|| invalid_data("missing shape"))?
+        .split(',')
+        .map(str::trim)
+        .filter(|dimension| !dimension.is_empty())
+        .map(|dimension| dimension.parse::<i32>().map_err(invalid_data))
+        .collect::<io::Result<Vec<_>>>()?;
+    let (rows, cols, channels) = match shape[..] {
+        [rows, cols] => (rows, cols, 1),
+        [rows, cols, channels] => (rows, cols, channels),
+        _ => return Err(invalid_data(format!("unsupported shape {shape:?}"))),
+    };
+    let mut mat =
+        Mat::new_rows_cols_with_default(rows, cols, CV_MAKETYPE(depth, channels), Scalar::all(0.0))
+            .map_err(other)?;
+    let data = mat.data_bytes_mut().map_err(other)?;
+    reader.read_exact(data)?;
+    if big_endian != cfg!(target_endian = "big") {
+        let size = code[1..].parse::<usize>().map_err(|_| unsupported())?;
+        for element in data.chunks_exact_mut(size) {
+            element.reverse();
+        }
+    }
+    Ok(mat)
+}
+
+fn field<'a>(header: &'a str, name: &str) -> Option<&'a str> {
+    let (_, value) = header.split_once(&format!("'{name}':"))?;
+    Some(value.trim_start())
+}
+
+fn invalid_data(error: impl ToString) -> io::Error {
+    io::Error::new(ErrorKind::InvalidData, error.to_string())
+}
+
+fn other(error: Error) -> io::Error {
+    io::Error::new(ErrorKind::Other, error.message)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    /// Big-endian `(1, 2)` `float32` array written by NumPy
+    fn big_endian() -> Vec<u8> {
+        let mut header = "{'descr': '>f4', 'fortran_order': False, 'shape': (1, 2), }".to_owned();
+        let padding = (64 - (10 + header.len() + 1) % 64) % 64;
+        header.extend((0..padding).map(|_| ' '));
+        header.push('\n');
+        let mut bytes = MAGIC.to_vec();
+        bytes.extend([1, 0]);
+        bytes.extend((header.len() as u16).to_le_bytes());
+        bytes.extend(header.as_bytes());
+        bytes.extend(1.5f32.to_be_bytes());
+        bytes.extend((-2.0f32).to_be_bytes());
+        bytes
+    }
+
+    #[test]
+    fn byte_order() -> io::Result<()> {
+        let mat = read(&big_endian()[..])?;
+        assert_eq!(mat.depth(), CV_32F);
+        assert_eq!(mat.data_typed::<f32>().map_err(other)?, [1.5, -2.0]);
+        // Written in native byte order
+        let mut bytes = Vec::new();
+        write(&mat, &mut bytes)?;
+        let order = if cfg!(target_endian = "big") {
+            '>'
+        } else {
+            '<'
+        };
+        assert!(String::from_utf8_lossy(&bytes).contains(&format!("'{order}f4'")));
+        let mat = read(&bytes[..])?;
+        assert_eq!(mat.data_typed::<f32>().map_err(other)?, [1.5, -2.0]);
+        Ok(())
+    }
+
+    #[test]
+    fn unsupported_byte_order() {
+        let mut bytes = big_endian();
+        let position = bytes
+            .windows(3)
+            .position(|window| window == b">f4")
+            .unwrap();
+        bytes[position] = b'!';
+        assert!(read(&bytes[..]).is_err());
+    }
+}
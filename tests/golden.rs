@@ -0,0 +1,328 @@
+//! Golden tests
+//!
+//! Runs the pipelines on the sample images of `assets` and compares the
+//! results with the golden files of `tests/golden`: object counts and
+//! measurements within tolerances, renders with a perceptual diff.
+//!
+//! Intended changes are recorded with:
+//!
+//! ```sh
+//! GOLDEN_UPDATE=1 cargo test --test golden -- --include-ignored
+//! ```
+//!
+//! The tests are ignored until the golden files are recorded. Missing golden
+//! files fail the tests unless `GOLDEN_UPDATE` is set. Renders
+//! and diffs of failed comparisons are written to `target/golden`.
+
+use anyhow::{ensure, Result};
+use cv::MatExt;
+use finder::{
+    algae::{choose, kmeans, measure, scale, segment},
+    object::Object,
+    render::render,
+    seeds::detect,
+    Config,
+};
+use opencv::{
+    core::{Mat, Size, VecN, BORDER_DEFAULT, CV_32F, CV_8UC1},
+    imgcodecs::IMREAD_COLOR,
+    imgproc::{cvt_color_def, gaussian_blur, COLOR_BGR2Lab},
+    prelude::*,
+};
+use ron::{
+    de::from_reader,
+    ser::{to_writer_pretty, PrettyConfig},
+};
+use serde::{Deserialize, Serialize};
+use std::{
+    env::var_os,
+    fs::{create_dir_all, File},
+    path::Path,
+};
+
+/// Absolute tolerance of the object count
+const COUNT: usize = 1;
+/// Relative tolerance of the measurements
+const MEASUREMENT: f64 = 0.02;
+/// Color difference (CIE76) a viewer notices
+const DELTA_E: f64 = 5.0;
+/// Fraction of the pixels allowed to differ noticeably
+const PIXELS: f64 = 0.001;
+
+#[test]
+#[ignore = "golden files are not recorded"]
+fn seeds() -> Result<()> {
+    run_seeds(
+        "20240416_164427",
+        "assets/20240416_164427/20240416_164427.jpg",
+    )
+}
+
+#[test]
+#[ignore = "golden files are not recorded"]
+fn water_coins() -> Result<()> {
+    run_seeds("water_coins", "assets/water_coins/water_coins.jpg")
+}
+
+#[test]
+#[ignore = "golden files are not recorded"]
+fn algae() -> Result<()> {
+    let config = Config::default();
+    let source = read("assets/SNAP-212329-0051/SNAP-212329-0051.source.png")?;
+    let template = read("assets/SNAP-212329-0051/template.10mum.png")?;
+    let scale = scale(&source, &template, &config.algae.scale)?;
+    let clusterings = kmeans(&source, &config)?;
+    let clustering = choose(&clusterings, config.algae.segmentation.criterion)
+        .expect("no k-means clustering matches the criterion");
+    let segmentation = segment(clustering, &config)?;
+    let cells = measure(&source, clustering, &segmentation.contours, Some(&scale))?;
+    let measurements = cells
+        .iter()
+        .map(|cell| Measurement {
+            area: cell.area,
+            perimeter: cell.perimeter,
+            circumcircle_radius: cell.circumcircle_radius,
+            incircle_radius: cell.incircle_radius,
+        })
+        .collect();
+    let objects = Object::from_contours(&segmentation.contours)?;
+    let image = render(&source, &objects, &config.render)?;
+    check("SNAP-212329-0051", measurements, &image)
+}
+
+fn run_seeds(name: &str, path: &str) -> Result<()> {
+    let config = Config::default();
+    let source = read(path)?;
+    let detection = detect(&source, &config)?;
+    let measurements = detection
+        .objects
+        .iter()
+        .map(|object| Measurement {
+            area: object.area,
+            perimeter: object.perimeter,
+            circumcircle_radius: object.min_circumcircle.radius as _,
+            incircle_radius: object.max_incircle.radius as _,
+        })
+        .collect();
+    let image = render(&source, &detection.objects, &config.render)?;
+    check(name, measurements, &image)
+}
+
+/// Golden file
+#[derive(Debug, Deserialize, Serialize)]
+struct Golden {
+    count: usize,
+    /// Sorted by area
+    measurements: Vec<Measurement>,
+}
+
+/// Measurement
+#[derive(Clone, Copy, Debug, Deserialize, Serialize)]
+struct Measurement {
+    area: f64,
+    perimeter: f64,
+    circumcircle_radius: f64,
+    incircle_radius: f64,
+}
+
+impl Measurement {
+    const NAMES: [&'static str; 4] = [
+        "area",
+        "perimeter",
+        "circumcircle_radius",
+        "incircle_radius",
+    ];
+
+    fn values(&self) -> [f64; 4] {
+        [
+            self.area,
+            self.perimeter,
+            self.circumcircle_radius,
+            self.incircle_radius,
+        ]
+    }
+}
+
+fn check(name: &str, mut measurements: Vec<Measurement>, image: &Mat) -> Result<()> {
+    let root = Path::new(env!("CARGO_MANIFEST_DIR"));
+    let golden = root.join("tests/golden");
+    let output = root.join("target/golden");
+    let ron = golden.join(format!("{name}.ron"));
+    let png = golden.join(format!("{name}.png"));
+    measurements.sort_by(|a, b| a.area.total_cmp(&b.area));
+    let actual = Golden {
+        count: measurements.len(),
+        measurements,
+    };
+    // Update
+    if var_os("GOLDEN_UPDATE").is_some() {
+        create_dir_all(&golden)?;
+        to_writer_pretty(
+            File::create(&ron)?,
+            &actual,
+            PrettyConfig::new().depth_limit(2),
+        )?;
+        image.write(&png)?;
+        eprintln!("{name}: golden files recorded");
+        return Ok(());
+    }
+    // Compare
+    ensure!(
+        ron.exists() && png.exists(),
+        "{name} has no golden files in {}, record them with GOLDEN_UPDATE=1",
+        golden.display(),
+    );
+    let expected: Golden = from_reader(File::open(&ron)?)?;
+    let mut failures = compare(&expected, &actual);
+    let difference = difference(&read(&png)?, image)?;
+    if difference.fraction > PIXELS {
+        create_dir_all(&output)?;
+        image.write(output.join(format!("{name}.png")))?;
+        difference
+            .image
+            .write(output.join(format!("{name}.diff.png")))?;
+        failures.push(format!(
+            "render: {:.3}% of the pixels differ by more than ΔE {DELTA_E} (max {:.3}%), see {}",
+            difference.fraction * 100.0,
+            PIXELS * 100.0,
+            output.display(),
+        ));
+    }
+    ensure!(
+        failures.is_empty(),
+        "{name} differs from the golden files, run with GOLDEN_UPDATE=1 if intended:\n{}",
+        failures.join("\n"),
+    );
+    Ok(())
+}
+
+/// Compares counts, then distributions of the measurements and, when the
+/// counts are the same, each object
+fn compare(expected: &Golden, actual: &Golden) -> Vec<String> {
+    let mut failures = Vec::new();
+    if expected.count.abs_diff(actual.count) > COUNT {
+        failures.push(format!(
+            "count: {} != {} (±{COUNT})",
+            actual.count, expected.count,
+        ));
+    }
+    for (index, name) in Measurement::NAMES.into_iter().enumerate() {
+        let column = |golden: &Golden| {
+            golden
+                .measurements
+                .iter()
+                .map(|measurement| measurement.values()[index])
+                .collect::<Vec<_>>()
+        };
+        let (expected, actual) = (column(expected), column(actual));
+        for (statistic, function) in [("mean", mean as fn(&[f64]) -> f64), ("median", median)] {
+            let (expected, actual) = (function(&expected), function(&actual));
+            if !close(expected, actual) {
+                failures.push(format!("{name} {statistic}: {actual} != {expected}"));
+            }
+        }
+    }
+    if expected.count == actual.count {
+        for (index, (expected, actual)) in expected
+            .measurements
+            .iter()
+            .zip(&actual.measurements)
+            .enumerate()
+        {
+            for (name, (expected, actual)) in Measurement::NAMES
+                .into_iter()
+                .zip(expected.values().into_iter().zip(actual.values()))
+            {
+                if !close(expected, actual) {
+                    failures.push(format!("object {index} {name}: {actual} != {expected}"));
+                }
+            }
+        }
+    }
+    failures
+}
+
+fn close(expected: f64, actual: f64) -> bool {
+    (actual - expected).abs() <= MEASUREMENT * expected.abs().max(1.0)
+}
+
+fn mean(values: &[f64]) -> f64 {
+    if values.is_empty() {
+        return 0.0;
+    }
+    values.iter().sum::<f64>() / values.len() as f64
+}
+
+fn median(values: &[f64]) -> f64 {
+    let mut values = values.to_vec();
+    values.sort_by(f64::total_cmp);
+    match values.len() {
+        0 => 0.0,
+        length if length % 2 == 0 => (values[length / 2 - 1] + values[length / 2]) / 2.0,
+        length => values[length / 2],
+    }
+}
+
+/// Perceptual difference
+struct Difference {
+    /// Fraction of the pixels with a noticeable difference
+    fraction: f64,
+    /// Noticeably different pixels in white
+    image: Mat,
+}
+
+/// Compares images in CIELAB after a slight blur, so that antialiasing and
+/// subpixel shifts of the drawings do not count
+fn difference(expected: &Mat, actual: &Mat) -> Result<Difference> {
+    ensure!(
+        expected.size()? == actual.size()?,
+        "render size {:?} != {:?}",
+        actual.size()?,
+        expected.size()?,
+    );
+    let lab = |image: &Mat| -> Result<Mat> {
+        let mut blurred = Mat::default();
+        gaussian_blur(
+            image,
+            &mut blurred,
+            Size::new(3, 3),
+            0.0,
+            0.0,
+            BORDER_DEFAULT,
+        )?;
+        let mut float = Mat::default();
+        blurred.convert_to(&mut float, CV_32F, 1.0 / 255.0, 0.0)?;
+        let mut lab = Mat::default();
+        cvt_color_def(&float, &mut lab, COLOR_BGR2Lab)?;
+        Ok(lab)
+    };
+    let (expected, actual) = (lab(expected)?, lab(actual)?);
+    let mut image = Mat::zeros_size(expected.size()?, CV_8UC1)?.to_mat()?;
+    let mut count = 0;
+    for ((expected, actual), pixel) in expected
+        .data_typed::<VecN<f32, 3>>()?
+        .iter()
+        .zip(actual.data_typed::<VecN<f32, 3>>()?)
+        .zip(image.data_typed_mut::<u8>()?)
+    {
+        let delta_e = (0..3)
+            .map(|index| (expected[index] - actual[index]).powi(2) as f64)
+            .sum::<f64>()
+            .sqrt();
+        if delta_e > DELTA_E {
+            *pixel = 255;
+            count += 1;
+        }
+    }
+    Ok(Difference {
+        fraction: count as f64 / image.total() as f64,
+        image,
+    })
+}
+
+fn read(path: impl AsRef<Path>) -> Result<Mat> {
+    let path = Path::new(env!("CARGO_MANIFEST_DIR")).join(path);
+    let image = Mat::read(&path, IMREAD_COLOR)?;
+    ensure!(!image.empty(), "failed to read {}", path.display());
+    Ok(image)
+}